@@ -4,6 +4,10 @@
 
 const std = @import("std");
 const foilz = @import("src/archiver.zig");
+const dir_template = @import("src/dir_template.zig");
+const runtime_config = @import("src/runtime_config.zig");
+const winres = @import("src/winres.zig");
+const MetaStruct = @import("src/metadata.zig").MetaStruct;
 const builtin = @import("builtin");
 
 const log = std.log;
@@ -30,6 +34,10 @@ pub fn build(b: *Builder) !void {
 
     // Run build steps!
     _ = try run_archiver();
+    _ = try pack_components();
+    _ = try run_license_archiver();
+    _ = try copy_sbom();
+    _ = try copy_runtime_config();
     _ = try build_wrapper();
 
     log.info("DONE 🚀", .{});
@@ -40,12 +48,247 @@ pub fn run_archiver() !void {
 
     const release_path = try std.process.getEnvVarOwned(allocator, "__BURRITO_RELEASE_PATH");
     try foilz.pack_directory(release_path, "./payload.foilz");
+    try xz_compress("payload.foilz", "src/payload.foilz.xz");
+}
 
+fn xz_compress(source: []const u8, dest: []const u8) !void {
+    const command = try std.fmt.allocPrint(allocator, "xz -9ez --check=crc32 --stdout --keep {s} > {s}", .{ source, dest });
     if (builtin.os.tag == .windows) {
-        _ = builder.exec(&[_][]const u8{ "cmd", "/C", "xz -9ez --check=crc32 --stdout --keep payload.foilz > src/payload.foilz.xz" });
+        _ = builder.exec(&[_][]const u8{ "cmd", "/C", command });
+    } else {
+        _ = builder.exec(&[_][]const u8{ "/bin/sh", "-c", command });
+    }
+}
+
+// An optional component as the Elixir side describes it, its files were moved to `components/<name>`
+const ComponentSpec = struct {
+    name: []const u8,
+    url: []const u8,
+    fetch: []const u8 = "on_demand",
+};
+
+// Same fields as `Component` in src/components.zig
+const PackedComponent = struct {
+    name: []const u8,
+    url: []const u8,
+    fetch: []const u8,
+    sha256: []const u8,
+    size: u64,
+    files: u64,
+    bytes: u64,
+};
+
+// Packs each optional component into its own archive, shipped next to the binary for publishing at its URL.
+// The wrapper only embeds their descriptions, in src/components.json
+pub fn pack_components() !void {
+    var packed_components = std.ArrayList(PackedComponent).init(allocator);
+
+    if (std.process.getEnvVarOwned(allocator, "__BURRITO_COMPONENTS") catch null) |specs_json| {
+        const release_name = try std.process.getEnvVarOwned(allocator, "__BURRITO_RELEASE_NAME");
+        const specs = try std.json.parseFromSlice([]const ComponentSpec, allocator, specs_json, .{});
+
+        for (specs.value) |spec| {
+            log.info("Packing optional component {s}... 🧩", .{spec.name});
+
+            const source_dir = try std.fs.cwd().realpathAlloc(allocator, try std.fs.path.join(allocator, &[_][]const u8{ "components", spec.name }));
+            const archive_path = try std.fmt.allocPrint(allocator, "components/{s}.foilz", .{spec.name});
+            const compressed_path = try std.fmt.allocPrint(allocator, "{s}.xz", .{archive_path});
+            try foilz.pack_directory(source_dir, archive_path);
+            try xz_compress(archive_path, compressed_path);
+
+            const totals = try count_archive_files(archive_path);
+            const archive_size = (try std.fs.cwd().statFile(archive_path)).size;
+            try packed_components.append(.{
+                .name = spec.name,
+                .url = spec.url,
+                .fetch = spec.fetch,
+                .sha256 = try hash_file(compressed_path),
+                .size = archive_size,
+                .files = totals.files,
+                .bytes = totals.bytes,
+            });
+
+            builder.installBinFile(compressed_path, try std.fmt.allocPrint(allocator, "{s}.{s}.component", .{ release_name, spec.name }));
+        }
+    }
+
+    const file = try std.fs.cwd().createFile("src/components.json", .{ .truncate = true });
+    defer file.close();
+    try std.json.stringify(packed_components.items, .{}, file.writer());
+}
+
+pub fn run_license_archiver() !void {
+    const licenses_path = std.process.getEnvVarOwned(allocator, "__BURRITO_LICENSES_PATH") catch null;
+
+    if (licenses_path) |path| {
+        log.info("Packing license files... 📜", .{});
+        try foilz.pack_directory(path, "src/licenses.foilz");
+    } else {
+        // The wrapper always embeds a license archive, even if it's empty
+        const file = try std.fs.cwd().createFile("src/licenses.foilz", .{ .truncate = true });
+        defer file.close();
+        const writer = file.writer();
+        try foilz.write_magic_number(&writer);
+        try foilz.write_magic_number(&writer);
+    }
+}
+
+pub fn copy_sbom() !void {
+    const sbom_path = std.process.getEnvVarOwned(allocator, "__BURRITO_SBOM_PATH") catch null;
+
+    // Like the licenses, the wrapper always embeds an SBOM, even if it's empty
+    if (sbom_path) |path| {
+        log.info("Embedding SBOM {s} 🧾", .{path});
+        try std.fs.cwd().copyFile(path, std.fs.cwd(), "src/sbom.bin", .{});
+    } else {
+        const file = try std.fs.cwd().createFile("src/sbom.bin", .{ .truncate = true });
+        file.close();
+    }
+}
+
+pub fn copy_runtime_config() !void {
+    const config_path = std.process.getEnvVarOwned(allocator, "__BURRITO_RUNTIME_CONFIG_PATH") catch null;
+
+    // Always embedded as well, an empty config changes nothing
+    if (config_path) |path| {
+        log.info("Embedding runtime config {s} ⚙️", .{path});
+        const source = try std.fs.cwd().readFileAlloc(allocator, path, 1024 * 1024);
+
+        // Checked against the options the wrapper knows now, rather than on every launch
+        var diagnostic = runtime_config.Diagnostic{};
+        var meta = MetaStruct{};
+        const entries = runtime_config.parse(allocator, source, &diagnostic) catch |err| return config_error(path, diagnostic, err);
+        runtime_config.apply(&meta, entries, &diagnostic) catch |err| return config_error(path, diagnostic, err);
+
+        try std.fs.cwd().writeFile("src/runtime_config.toml", source);
+    } else {
+        try std.fs.cwd().writeFile("src/runtime_config.toml", "");
+    }
+}
+
+// Writes src/wrapper.res for Windows targets that asked for it, returns whether it did
+fn write_windows_resources() !bool {
+    const version_info_json = std.process.getEnvVarOwned(allocator, "__BURRITO_WINDOWS_VERSION_INFO") catch return false;
+    const icon_path = std.process.getEnvVarOwned(allocator, "__BURRITO_WINDOWS_ICON_PATH") catch null;
+
+    const version_info = try std.json.parseFromSlice(winres.VersionInfo, allocator, version_info_json, .{ .ignore_unknown_fields = true });
+    const icon = if (icon_path) |path| try std.fs.cwd().readFileAlloc(allocator, path, 16 * 1024 * 1024) else null;
+
+    const file = try std.fs.cwd().createFile("src/wrapper.res", .{ .truncate = true });
+    defer file.close();
+    var buffered = std.io.bufferedWriter(file.writer());
+    winres.write(allocator, buffered.writer(), icon, version_info.value) catch |err| {
+        if (err == error.InvalidIcon) {
+            log.err("{s} is not a valid .ico file", .{icon_path.?});
+        }
+        return err;
+    };
+    try buffered.flush();
+    return true;
+}
+
+fn config_error(path: []const u8, diagnostic: runtime_config.Diagnostic, err: anyerror) anyerror {
+    log.err("{s}:{}: {s}", .{ path, diagnostic.line, diagnostic.message });
+    return err;
+}
+
+fn hash_payload() ![]const u8 {
+    return try hash_file("src/payload.foilz.xz");
+}
+
+fn hash_file(path: []const u8) ![]const u8 {
+    const data = try std.fs.cwd().readFileAlloc(allocator, path, foilz.MAX_READ_SIZE);
+    var digest: [std.crypto.hash.sha2.Sha256.digest_length]u8 = undefined;
+    std.crypto.hash.sha2.Sha256.hash(data, &digest, .{});
+    return try std.fmt.allocPrint(allocator, "{s}", .{std.fmt.fmtSliceHexLower(&digest)});
+}
+
+// Embedding one huge file makes the compiler hold all of it (and then some) in memory at once, the payload is
+// embedded as chunks of this size instead, which the wrapper feeds to the decompressor one after the other
+const PAYLOAD_CHUNK_SIZE = 16 * 1024 * 1024;
+
+// Splits the compressed payload into `src/payload_chunks/<n>.bin`, returns how many chunks there are
+fn split_payload() !u64 {
+    std.fs.cwd().deleteTree("src/payload_chunks") catch {};
+    try std.fs.cwd().makePath("src/payload_chunks");
+
+    const payload = try std.fs.cwd().openFile("src/payload.foilz.xz", .{});
+    defer payload.close();
+
+    var buffer = try allocator.alloc(u8, PAYLOAD_CHUNK_SIZE);
+    defer allocator.free(buffer);
+
+    var count: u64 = 0;
+    while (true) {
+        const len = try payload.readAll(buffer);
+        if (len == 0) break;
+
+        const chunk_path = try std.fmt.allocPrint(allocator, "src/payload_chunks/{d}.bin", .{count});
+        try std.fs.cwd().writeFile(chunk_path, buffer[0..len]);
+        count += 1;
+
+        if (len < buffer.len) break;
+    }
+
+    return count;
+}
+
+// See `InstallStrategy` in src/burrito.zig
+const known_install_strategies = [_][]const u8{ "user_data", "system", "portable", "ephemeral" };
+
+fn get_install_strategy() ![]const u8 {
+    const strategy = std.process.getEnvVarOwned(allocator, "__BURRITO_INSTALL_STRATEGY") catch return "user_data";
+    for (known_install_strategies) |known| {
+        if (std.mem.eql(u8, strategy, known)) break;
     } else {
-        _ = builder.exec(&[_][]const u8{ "/bin/sh", "-c", "xz -9ez --check=crc32 --stdout --keep payload.foilz > src/payload.foilz.xz" });
+        log.err("Unknown install strategy: {s}, expected one of: {s}", .{ strategy, known_install_strategies });
+        return error.UnknownInstallStrategy;
+    }
+    if (!std.mem.eql(u8, strategy, "user_data")) {
+        log.info("Installing to the {s} directory by default 📂", .{strategy});
     }
+    return strategy;
+}
+
+const known_features = [_][]const u8{ "maintenance", "update", "telemetry", "progress" };
+
+fn get_excluded_features() !std.BufSet {
+    var excluded = std.BufSet.init(allocator);
+    const list = std.process.getEnvVarOwned(allocator, "__BURRITO_EXCLUDE_FEATURES") catch return excluded;
+
+    var names = std.mem.tokenizeScalar(u8, list, ',');
+    while (names.next()) |name| {
+        for (known_features) |known| {
+            if (std.mem.eql(u8, name, known)) break;
+        } else {
+            log.err("Unknown feature to exclude: {s}, expected one of: {s}", .{ name, known_features });
+            return error.UnknownFeature;
+        }
+        try excluded.insert(name);
+    }
+
+    return excluded;
+}
+
+fn upper(comptime name: []const u8) []const u8 {
+    comptime {
+        var result: [name.len]u8 = undefined;
+        _ = std.ascii.upperString(&result, name);
+        const final = result;
+        return &final;
+    }
+}
+
+fn count_archive_files(path: []const u8) !struct { files: u64, bytes: u64 } {
+    const archive = try std.fs.cwd().readFileAlloc(allocator, path, foilz.MAX_READ_SIZE);
+    var records = try foilz.iterate_records(archive);
+    var files: u64 = 0;
+    var bytes: u64 = 0;
+    while (try records.next()) |record| {
+        files += 1;
+        bytes += record.data.len;
+    }
+    return .{ .files = files, .bytes = bytes };
 }
 
 pub fn build_wrapper() !void {
@@ -55,6 +298,9 @@ pub fn build_wrapper() !void {
     const plugin_path = std.process.getEnvVarOwned(allocator, "__BURRITO_PLUGIN_PATH") catch null;
     const is_prod = std.process.getEnvVarOwned(allocator, "__BURRITO_IS_PROD") catch "true";
     _ = is_prod;
+    const sidecar_payload = std.process.getEnvVarOwned(allocator, "__BURRITO_SIDECAR_PAYLOAD") catch null;
+    const sbom_path = std.process.getEnvVarOwned(allocator, "__BURRITO_SBOM_PATH") catch null;
+    const io_uring = std.process.getEnvVarOwned(allocator, "__BURRITO_IO_URING") catch null;
 
     var file = try std.fs.cwd().openFile("payload.foilz", .{});
     defer file.close();
@@ -75,10 +321,63 @@ pub fn build_wrapper() !void {
     exe_options.addOption([]const u8, "RELEASE_NAME", release_name);
     exe_options.addOption(u64, "UNCOMPRESSED_SIZE", uncompressed_size);
 
+    // Totals for progress events, the payload is extracted while it's decompressed so the wrapper can't count them
+    const payload_totals = try count_archive_files("payload.foilz");
+    exe_options.addOption(u64, "PAYLOAD_FILE_COUNT", payload_totals.files);
+    exe_options.addOption(u64, "PAYLOAD_FILE_BYTES", payload_totals.bytes);
+
     exe_options.addOption(bool, "IS_PROD", true);
 
+    // Extracting the SBOM keeps its original file name
+    exe_options.addOption([]const u8, "SBOM_NAME", if (sbom_path) |path| std.fs.path.basename(path) else "");
+
+    // The payload is checked against its hash before extraction.
+    // A sidecar payload is installed next to the binary, the wrapper only knows its hash
+    exe_options.addOption([]const u8, "PAYLOAD_SHA256", try hash_payload());
+    exe_options.addOption(bool, "SIDECAR_PAYLOAD", sidecar_payload != null);
+    exe_options.addOption(u64, "PAYLOAD_CHUNK_COUNT", if (sidecar_payload != null) 0 else try split_payload());
+    if (sidecar_payload != null) {
+        log.info("Payload will be shipped as a separate file 📎", .{});
+        const payload_name = try std.fmt.allocPrint(allocator, "{s}{s}.payload", .{ release_name, target.exeFileExt() });
+        builder.installBinFile("src/payload.foilz.xz", payload_name);
+    }
+
+    // Where installs go, checked here so the wrapper never has to deal with a bad template
+    const install_suffix = std.process.getEnvVarOwned(allocator, "__BURRITO_INSTALL_SUFFIX") catch dir_template.DEFAULT_SUFFIX;
+    const install_dir_template = std.process.getEnvVarOwned(allocator, "__BURRITO_INSTALL_DIR_TEMPLATE") catch dir_template.DEFAULT_TEMPLATE;
+    dir_template.validate_suffix(install_suffix) catch |err| {
+        log.err("Invalid install suffix `{s}`", .{install_suffix});
+        return err;
+    };
+    dir_template.validate(install_dir_template) catch |err| {
+        log.err("Invalid install directory template `{s}`, placeholders are {s} and {{version}} is required", .{ install_dir_template, dir_template.placeholders });
+        return err;
+    };
+    exe_options.addOption([]const u8, "INSTALL_SUFFIX", install_suffix);
+    exe_options.addOption([]const u8, "INSTALL_DIR_TEMPLATE", install_dir_template);
+    exe_options.addOption([]const u8, "INSTALL_STRATEGY", try get_install_strategy());
+
+    // Linux only, the wrapper falls back to regular writes where io_uring isn't available
+    exe_options.addOption(bool, "IO_URING", io_uring != null);
+
+    // Optional parts of the wrapper, see src/features.zig
+    const excluded_features = try get_excluded_features();
+    inline for (known_features) |feature| {
+        const excluded = excluded_features.contains(feature);
+        if (excluded) {
+            log.info("Leaving out {s} ✂️", .{feature});
+        }
+        exe_options.addOption(bool, "FEATURE_" ++ comptime upper(feature), !excluded);
+    }
+
     if (target.isWindows()) {
         wrapper_exe.addIncludePath(.{ .path = "src/" });
+
+        // lld-link takes the compiled resources like an object file
+        if (try write_windows_resources()) {
+            log.info("Embedding Windows icon and version information 🪟", .{});
+            wrapper_exe.addObjectFile(.{ .path = "src/wrapper.res" });
+        }
     }
 
     // Link standard C libary to the wrapper
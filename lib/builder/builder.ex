@@ -44,7 +44,7 @@ defmodule Burrito.Builder do
   @phases [
     fetch: [Fetch.Init, Fetch.ResolveERTS],
     patch: [Patch.CopyERTS, Patch.RecompileNIFs],
-    build: [Build.PackAndBuild, Build.CopyRelease]
+    build: [Build.PackAndBuild, Build.CopyRelease, Build.MacAppBundle]
   ]
 
   def build(%Mix.Release{} = release) do
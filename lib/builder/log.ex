@@ -4,15 +4,15 @@ defmodule Burrito.Builder.Log do
   end
 
   def success(type, message) do
-    IO.puts(:stderr, IO.ANSI.green() <> get_prefix(type) <> message <> IO.ANSI.reset())
+    IO.puts(:stderr, colorize(IO.ANSI.green(), get_prefix(type) <> message))
   end
 
   def warning(type, message) do
-    IO.puts(:stderr, IO.ANSI.yellow() <> get_prefix(type) <> message <> IO.ANSI.reset())
+    IO.puts(:stderr, colorize(IO.ANSI.yellow(), get_prefix(type) <> message))
   end
 
   def error(type, message) do
-    IO.puts(:stderr, IO.ANSI.red() <> get_prefix(type) <> message <> IO.ANSI.reset())
+    IO.puts(:stderr, colorize(IO.ANSI.red(), get_prefix(type) <> message))
   end
 
   defp get_prefix(type) do
@@ -22,4 +22,17 @@ defmodule Burrito.Builder.Log do
       :step -> "--> "
     end
   end
+
+  # Plain text when NO_COLOR is set (https://no-color.org), or when Elixir decided the terminal can't do colors
+  defp colorize(color, text) do
+    if color_enabled?() do
+      color <> text <> IO.ANSI.reset()
+    else
+      text
+    end
+  end
+
+  defp color_enabled? do
+    System.get_env("NO_COLOR", "") == "" and IO.ANSI.enabled?()
+  end
 end
@@ -94,19 +94,31 @@ defmodule Burrito.Builder.Target do
         :darwin -> "macos"
         :windows -> "windows"
         :linux -> "linux"
+        :freebsd -> "freebsd"
       end
 
-    triplet = "#{target.cpu}-#{os}"
+    triplet = "#{translate_cpu_to_zig(target.cpu)}-#{os}"
 
     if target.qualifiers[:libc] do
-      libc = translate_libc_to_zig(target.qualifiers[:libc])
+      libc = translate_libc_to_zig(target.qualifiers[:libc], target.cpu)
       "#{triplet}-#{libc}"
     else
       triplet
     end
   end
 
-  defp translate_libc_to_zig(:gnu), do: "gnu"
-  defp translate_libc_to_zig(:musl), do: "musl"
-  defp translate_libc_to_zig(abi), do: Atom.to_string(abi)
+  # Extra `zig build` arguments a target needs on top of its triplet
+  @spec zig_cpu_args(Burrito.Builder.Target.t()) :: [String.t()]
+  def zig_cpu_args(%Target{cpu: :armv7}), do: ["-Dcpu=generic+v7a"]
+  def zig_cpu_args(%Target{}), do: []
+
+  defp translate_cpu_to_zig(:armv7), do: "arm"
+  defp translate_cpu_to_zig(cpu), do: Atom.to_string(cpu)
+
+  # 32-bit ARM Linux uses the hard-float ABI variants
+  defp translate_libc_to_zig(:gnu, :armv7), do: "gnueabihf"
+  defp translate_libc_to_zig(:musl, :armv7), do: "musleabihf"
+  defp translate_libc_to_zig(:gnu, _cpu), do: "gnu"
+  defp translate_libc_to_zig(:musl, _cpu), do: "musl"
+  defp translate_libc_to_zig(abi, _cpu), do: Atom.to_string(abi)
 end
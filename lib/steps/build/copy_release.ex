@@ -47,6 +47,21 @@ defmodule Burrito.Steps.Build.CopyRelease do
     # Mark resulting bin as executable
     File.chmod!(output_bin_path, 0o744)
 
+    # With `sidecar_payload: true` the payload ships as `<binary>.payload`, next to the binary
+    payload_path = bin_path <> ".payload"
+
+    if File.exists?(payload_path) do
+      File.copy!(payload_path, output_bin_path <> ".payload")
+      File.rm!(payload_path)
+    end
+
+    # Optional components are published separately, at their URLs
+    for component_path <- Path.wildcard(Path.join(Path.dirname(bin_path), "#{release_name}.*.component")) do
+      component_name = component_path |> Path.basename() |> String.replace_prefix(release_name, "")
+      File.copy!(component_path, Path.rootname(output_bin_path, ".exe") <> component_name)
+      File.rm!(component_path)
+    end
+
     IO.puts(@success_banner <> "\tOutput Path: #{output_bin_path}\n\n")
 
     context
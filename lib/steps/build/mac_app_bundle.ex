@@ -0,0 +1,121 @@
+defmodule Burrito.Steps.Build.MacAppBundle do
+  alias Burrito.Builder.Context
+  alias Burrito.Builder.Log
+  alias Burrito.Builder.Step
+
+  @behaviour Step
+
+  # Wraps the MacOS binary CopyRelease produced into `burrito_out/<name>.app`, see the `macos_app_bundle` option.
+  # The wrapper notices it runs from a bundle and behaves like a Mac application (see src/app_bundle.zig)
+  @impl Step
+  def execute(%Context{target: %{os: :darwin}} = context) do
+    options = context.mix_release.options[:burrito] || []
+
+    case get_bundle_options(options) do
+      nil -> context
+      bundle_options -> build_bundle(context, bundle_options)
+    end
+  end
+
+  def execute(%Context{} = context), do: context
+
+  # `macos_app_bundle: true` builds a bundle with the defaults, a keyword list overrides them
+  defp get_bundle_options(options) do
+    case Keyword.get(options, :macos_app_bundle, false) do
+      true -> []
+      bundle_options when is_list(bundle_options) -> bundle_options
+      _ -> nil
+    end
+  end
+
+  defp build_bundle(context, bundle_options) do
+    release_name = Atom.to_string(context.mix_release.name)
+    target_name = Atom.to_string(context.target.alias)
+    bin_path = Path.join([File.cwd!(), "burrito_out", "#{release_name}_#{target_name}"])
+
+    name = Keyword.get(bundle_options, :name, Macro.camelize(release_name))
+    bundle_path = Path.join([File.cwd!(), "burrito_out", "#{name}_#{target_name}.app"])
+    macos_path = Path.join([bundle_path, "Contents", "MacOS"])
+    resources_path = Path.join([bundle_path, "Contents", "Resources"])
+
+    Log.info(:step, "Building the app bundle #{bundle_path}")
+
+    File.rm_rf!(bundle_path)
+    File.mkdir_p!(macos_path)
+    File.mkdir_p!(resources_path)
+
+    File.cp!(bin_path, Path.join(macos_path, release_name))
+    File.chmod!(Path.join(macos_path, release_name), 0o755)
+
+    if File.exists?(bin_path <> ".payload") do
+      File.cp!(bin_path <> ".payload", Path.join(macos_path, release_name <> ".payload"))
+    end
+
+    icon_name = maybe_copy_icon(bundle_options[:icon], resources_path)
+
+    plist =
+      info_plist(%{
+        "CFBundleName" => name,
+        "CFBundleDisplayName" => name,
+        "CFBundleIdentifier" =>
+          Keyword.get(bundle_options, :identifier, "com.example.#{release_name}"),
+        "CFBundleExecutable" => release_name,
+        "CFBundleIconFile" => icon_name,
+        "CFBundlePackageType" => "APPL",
+        "CFBundleShortVersionString" => context.mix_release.version,
+        "CFBundleVersion" => context.mix_release.version,
+        "CFBundleInfoDictionaryVersion" => "6.0",
+        "LSMinimumSystemVersion" => Keyword.get(bundle_options, :minimum_system_version),
+        "LSUIElement" => Keyword.get(bundle_options, :background_only, false),
+        "NSHighResolutionCapable" => true
+      })
+
+    File.write!(Path.join([bundle_path, "Contents", "Info.plist"]), plist)
+
+    context
+  end
+
+  defp maybe_copy_icon(nil, _resources_path), do: nil
+
+  defp maybe_copy_icon(icon, resources_path) do
+    path = Path.expand(icon)
+
+    if !File.regular?(path) do
+      Log.error(:step, "The app bundle icon #{path} does not exist")
+      raise "App bundle icon not found"
+    end
+
+    File.cp!(path, Path.join(resources_path, Path.basename(path)))
+    Path.basename(path)
+  end
+
+  defp info_plist(entries) do
+    body =
+      entries
+      |> Enum.reject(fn {_key, value} -> is_nil(value) end)
+      |> Enum.sort()
+      |> Enum.map_join(fn {key, value} ->
+        "  <key>#{escape(key)}</key>\n  #{plist_value(value)}\n"
+      end)
+
+    """
+    <?xml version="1.0" encoding="UTF-8"?>
+    <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+    <plist version="1.0">
+    <dict>
+    #{body}</dict>
+    </plist>
+    """
+  end
+
+  defp plist_value(true), do: "<true/>"
+  defp plist_value(false), do: "<false/>"
+  defp plist_value(value), do: "<string>#{escape(to_string(value))}</string>"
+
+  defp escape(text) do
+    text
+    |> String.replace("&", "&amp;")
+    |> String.replace("<", "&lt;")
+    |> String.replace(">", "&gt;")
+  end
+end
@@ -13,10 +13,18 @@ defmodule Burrito.Steps.Build.PackAndBuild do
     build_triplet = Target.make_triplet(context.target)
 
     plugin_path = maybe_get_plugin_path(options[:plugin])
+    licenses_path = maybe_collect_licenses(context.self_dir, options)
 
-    zig_build_args = ["-Dtarget=#{build_triplet}"]
+    zig_build_args = ["-Dtarget=#{build_triplet}"] ++ Target.zig_cpu_args(context.target)
 
-    create_metadata_file(context.self_dir, zig_build_args, context.mix_release)
+    create_metadata_file(
+      context.self_dir,
+      zig_build_args,
+      context.mix_release,
+      context.target,
+      context.work_dir,
+      options
+    )
 
     # TODO: Why do we need to do this???
     # This is to bypass a VERY strange bug inside Linux containers...
@@ -25,6 +33,9 @@ defmodule Burrito.Steps.Build.PackAndBuild do
     # File system to suddenly "wake up" to all the files inside it.
     Path.join(context.work_dir, ["/lib", "/.burrito"]) |> File.touch!()
 
+    maybe_remove_cookie(context.work_dir, options)
+    components = maybe_split_components(context, options)
+
     build_result =
       System.cmd("zig", ["build"] ++ zig_build_args,
         cd: context.self_dir,
@@ -32,7 +43,20 @@ defmodule Burrito.Steps.Build.PackAndBuild do
           {"__BURRITO_IS_PROD", is_prod(context.target)},
           {"__BURRITO_RELEASE_PATH", context.work_dir},
           {"__BURRITO_RELEASE_NAME", release_name},
-          {"__BURRITO_PLUGIN_PATH", plugin_path}
+          {"__BURRITO_PLUGIN_PATH", plugin_path},
+          {"__BURRITO_LICENSES_PATH", licenses_path},
+          {"__BURRITO_SIDECAR_PAYLOAD", maybe_sidecar_payload(options)},
+          {"__BURRITO_IO_URING", maybe_io_uring(options)},
+          {"__BURRITO_EXCLUDE_FEATURES", maybe_exclude_features(options)},
+          {"__BURRITO_SBOM_PATH", maybe_get_sbom_path(options[:sbom])},
+          {"__BURRITO_RUNTIME_CONFIG_PATH", maybe_get_runtime_config_path(options[:runtime_config])},
+          {"__BURRITO_WINDOWS_ICON_PATH", maybe_get_windows_icon_path(context.target, options)},
+          {"__BURRITO_WINDOWS_VERSION_INFO",
+           maybe_windows_version_info(context.target, context.mix_release, options)},
+          {"__BURRITO_INSTALL_SUFFIX", options[:install_dir][:suffix]},
+          {"__BURRITO_INSTALL_DIR_TEMPLATE", options[:install_dir][:template]},
+          {"__BURRITO_INSTALL_STRATEGY", options |> Keyword.get(:install_strategy) |> maybe_to_string()},
+          {"__BURRITO_COMPONENTS", components}
         ],
         into: IO.stream()
       )
@@ -55,24 +79,304 @@ defmodule Burrito.Steps.Build.PackAndBuild do
     end
   end
 
+  defp maybe_sidecar_payload(options) do
+    if Keyword.get(options, :sidecar_payload, false), do: "1"
+  end
+
+  defp maybe_exclude_features(options) do
+    case Keyword.get(options, :exclude_features, []) do
+      [] -> nil
+      features -> Enum.map_join(features, ",", &to_string/1)
+    end
+  end
+
+  defp maybe_io_uring(options) do
+    if Keyword.get(options, :io_uring, false), do: "1"
+  end
+
+  @component_fetch_modes [:on_demand, :on_launch]
+
+  # Where the data of each of the `optional_packs` lives in a release, a pack's `paths` add to these
+  @pack_paths [
+    docs: ["doc", "lib/*/doc", "lib/*/priv/doc", "erts-*/doc", "erts-*/man"],
+    locales: ["lib/*/priv/gettext", "lib/*/priv/locales", "lib/*/priv/cldr"]
+  ]
+
+  # Moves the files of each optional component and pack out of the release into `components/<name>`, the wrapper
+  # build packs them into their own archives. Returns the components for the wrapper build, or nil without any.
+  defp maybe_split_components(%Context{} = context, options) do
+    components_path = Path.join(context.self_dir, "components")
+    File.rm_rf!(components_path)
+
+    components =
+      options
+      |> Keyword.get(:optional_components, [])
+      |> Enum.map(fn {name, component} ->
+        if !component[:url], do: raise("The optional component #{name} has no `url`")
+        split_component(context, components_path, to_string(name), component)
+      end)
+
+    packs =
+      options
+      |> Keyword.get(:optional_packs, [])
+      |> Enum.flat_map(&split_pack(context, components_path, &1))
+
+    case components ++ packs do
+      [] ->
+        nil
+
+      all ->
+        if all |> Enum.uniq_by(& &1.name) |> length() != length(all) do
+          raise "Optional components and packs need unique names"
+        end
+
+        Jason.encode!(all)
+    end
+  end
+
+  # `optional_packs: [:docs]` splits a pack with the defaults, `[docs: [url: ...]]` sets its options
+  defp split_pack(context, components_path, pack) when is_atom(pack),
+    do: split_pack(context, components_path, {pack, []})
+
+  defp split_pack(context, components_path, {pack, pack_options}) do
+    default_paths =
+      Keyword.get(@pack_paths, pack) ||
+        raise "Unknown optional pack #{inspect(pack)}, expected one of #{inspect(Keyword.keys(@pack_paths))}"
+
+    # Unlike a component's paths, a pack's may match nothing, e.g. in a release without translations
+    paths =
+      (default_paths ++ Keyword.get(pack_options, :paths, []))
+      |> Enum.filter(&(Path.wildcard(Path.join(context.work_dir, &1)) != []))
+
+    if paths == [] do
+      Log.info(:step, "The release has no #{pack} to split into an optional pack")
+      []
+    else
+      [split_component(context, components_path, Atom.to_string(pack), Keyword.put(pack_options, :paths, paths))]
+    end
+  end
+
+  defp split_component(context, components_path, name, component) do
+    url = component[:url]
+    fetch = Keyword.get(component, :fetch, :on_demand)
+
+    if !String.match?(name, ~r/^[a-z0-9_\-]+$/) do
+      raise "Invalid optional component name #{name}, use lowercase letters, digits, `_` and `-` only"
+    end
+
+    if fetch not in @component_fetch_modes do
+      raise "Invalid `fetch` for the optional component #{name}, expected one of #{inspect(@component_fetch_modes)}"
+    end
+
+    if fetch == :on_launch and !url do
+      raise "The optional component #{name} is fetched on launch, but has no `url`"
+    end
+
+    matches =
+      component
+      |> Keyword.get(:paths, [])
+      |> Enum.flat_map(fn pattern ->
+        case Path.wildcard(Path.join(context.work_dir, pattern)) do
+          [] -> raise "The path #{pattern} of the optional component #{name} matches nothing in the release"
+          matches -> matches
+        end
+      end)
+
+    if matches == [] do
+      raise "The optional component #{name} has no `paths`"
+    end
+
+    for match <- matches do
+      dest = Path.join([components_path, name, Path.relative_to(match, context.work_dir)])
+      File.mkdir_p!(Path.dirname(dest))
+      File.rename!(match, dest)
+    end
+
+    Log.info(:step, "Moved #{length(matches)} path(s) into the optional component #{name}")
+
+    %{
+      name: name,
+      url: expand_component_url(context, name, url),
+      fetch: to_string(fetch)
+    }
+  end
+
+  # Packs without a URL are installed from their file only
+  defp expand_component_url(_context, _name, nil), do: ""
+
+  defp expand_component_url(context, name, url) do
+    url
+    |> String.replace("{name}", name)
+    |> String.replace("{version}", context.mix_release.version)
+    |> String.replace("{target}", Atom.to_string(context.target.alias))
+  end
+
+  # When the cookie comes from the OS credential store, it shouldn't ship in the payload at all
+  defp maybe_remove_cookie(work_dir, options) do
+    if Keyword.get(options, :cookie_source, :file) == :keychain do
+      Path.join(work_dir, ["releases", "/COOKIE"]) |> File.rm()
+    end
+  end
+
+  defp maybe_get_sbom_path(nil), do: nil
+
+  defp maybe_get_sbom_path(sbom_path) do
+    path = Path.expand(sbom_path)
+
+    if !File.regular?(path) do
+      Log.error(:step, "The SBOM file #{path} does not exist")
+      raise "SBOM not found"
+    end
+
+    path
+  end
+
+  defp maybe_get_runtime_config_path(nil), do: nil
+
+  defp maybe_get_runtime_config_path(config_path) do
+    path = Path.expand(config_path)
+
+    if !File.regular?(path) do
+      Log.error(:step, "The runtime config file #{path} does not exist")
+      raise "Runtime config not found"
+    end
+
+    path
+  end
+
+  # `windows_resources: true` embeds the version information with the defaults, a keyword list overrides them
+  defp get_windows_resources(%Target{os: :windows}, options) do
+    case Keyword.get(options, :windows_resources, false) do
+      true -> []
+      resources when is_list(resources) -> resources
+      _ -> nil
+    end
+  end
+
+  defp get_windows_resources(_target, _options), do: nil
+
+  defp maybe_get_windows_icon_path(target, options) do
+    with resources when is_list(resources) <- get_windows_resources(target, options),
+         icon when is_binary(icon) <- resources[:icon] do
+      path = Path.expand(icon)
+
+      if !File.regular?(path) do
+        Log.error(:step, "The Windows icon file #{path} does not exist")
+        raise "Windows icon not found"
+      end
+
+      path
+    else
+      _ -> nil
+    end
+  end
+
+  defp maybe_windows_version_info(target, release, options) do
+    if resources = get_windows_resources(target, options) do
+      release_name = Atom.to_string(release.name)
+      product_name = Keyword.get(resources, :product_name, release_name)
+
+      Jason.encode!(%{
+        product_name: product_name,
+        company_name: Keyword.get(resources, :company_name, ""),
+        file_description: Keyword.get(resources, :description, product_name),
+        legal_copyright: Keyword.get(resources, :copyright, ""),
+        original_filename: "#{release_name}.exe",
+        version: release.version
+      })
+    end
+  end
+
   defp maybe_get_plugin_path(nil), do: nil
 
   defp maybe_get_plugin_path(plugin_path) do
     Path.join(File.cwd!(), [plugin_path])
   end
 
-  defp create_metadata_file(self_path, args, release) do
+  @license_patterns ["LICENSE*", "LICENCE*", "NOTICE*", "COPYING*"]
+
+  # Copies the license files of the project and each of its dependencies into `licenses/<name>/`,
+  # the wrapper embeds them so `--burrito licenses` can print them
+  defp maybe_collect_licenses(self_path, options) do
+    if Keyword.get(options, :licenses, true) do
+      licenses_path = Path.join(self_path, "licenses")
+      File.rm_rf!(licenses_path)
+      File.mkdir_p!(licenses_path)
+
+      project_name = Mix.Project.config()[:app] |> to_string()
+      sources = [{project_name, File.cwd!()} | Enum.to_list(Mix.Project.deps_paths())]
+
+      count =
+        for {name, path} <- sources,
+            pattern <- @license_patterns,
+            file <- Path.wildcard(Path.join(path, pattern)),
+            File.regular?(file),
+            reduce: 0 do
+          acc ->
+            dest = Path.join([licenses_path, to_string(name), Path.basename(file)])
+            File.mkdir_p!(Path.dirname(dest))
+            File.cp!(file, dest)
+            acc + 1
+        end
+
+      Log.info(:step, "Collected #{count} license files")
+      licenses_path
+    end
+  end
+
+  defp create_metadata_file(self_path, args, release, target, work_dir, options) do
     Log.info(:step, "Generating wrapper metadata file...")
 
     {zig_version_string, 0} = System.cmd("zig", ["version"], cd: self_path)
 
+    crash_dumps = Keyword.get(options, :crash_dumps, [])
+    update = Keyword.get(options, :update, [])
+    uninstall_entry = get_uninstall_entry(options)
+
     metadata_map = %{
       app_name: Atom.to_string(release.name),
       zig_version: zig_version_string |> String.trim(),
       zig_build_arguments: args,
       app_version: release.version,
       options: inspect(release.options),
-      erts_version: release.erts_version |> to_string()
+      erts_version: release.erts_version |> to_string(),
+      otp_version: get_otp_version(target),
+      target: Target.make_triplet(target),
+      static_erts:
+        Keyword.get_lazy(options, :static_erts, fn -> static_erts?(work_dir, release) end),
+      build_timestamp: get_build_time() |> DateTime.to_iso8601(),
+      git_sha: get_git_sha(),
+      pty: Keyword.get(options, :pty, false),
+      windows_console: Keyword.get(options, :windows_console, :erl) |> to_string(),
+      log_target: Keyword.get(options, :log_target, :console) |> to_string(),
+      shutdown_timeout: Keyword.get(options, :shutdown_timeout, 0),
+      nice: Keyword.get(options, :nice),
+      windows_priority: options |> Keyword.get(:windows_priority) |> maybe_to_string(),
+      max_open_files: Keyword.get(options, :max_open_files),
+      crash_dump_keep: Keyword.get(crash_dumps, :keep, 5),
+      crash_dump_max_bytes: Keyword.get(crash_dumps, :max_size),
+      crash_dump_compress: Keyword.get(crash_dumps, :compress, false),
+      update_url: Keyword.get(update, :url),
+      update_public_key: Keyword.get(update, :public_key),
+      update_channel: update |> Keyword.get(:channel, :stable) |> to_string(),
+      keep_previous_version: Keyword.get(options, :keep_previous_version, false),
+      clear_quarantine: Keyword.get(options, :clear_quarantine, true),
+      short_install_dir: Keyword.get(options, :short_install_dir, false),
+      trusted_modes: Keyword.get(options, :trusted_modes, false),
+      cookie_source: options |> Keyword.get(:cookie_source, :file) |> to_string(),
+      tamper_check: options |> Keyword.get(:tamper_check, :warn) |> to_string(),
+      policy_install_dir: Keyword.get(options, :policy_install_dir),
+      selinux_type: Keyword.get(options, :selinux_type),
+      sandbox_extraction: Keyword.get(options, :sandbox_extraction, false),
+      telemetry_url: Keyword.get(options, :telemetry_url),
+      cookie_keychain_service: Keyword.get(options, :cookie_keychain_service),
+      windows_uninstall_entry: uninstall_entry != nil,
+      windows_display_name: uninstall_entry[:display_name],
+      windows_publisher: uninstall_entry[:publisher],
+      completions: options |> Keyword.get(:completions, []) |> Enum.map(&to_string/1),
+      translations: options |> Keyword.get(:translations, %{}) |> flatten_translations(),
+      support_url: Keyword.get(options, :support_url),
+      unique_node_name: Keyword.get(options, :unique_node_name, false)
     }
 
     encoded = Jason.encode!(metadata_map)
@@ -80,6 +384,85 @@ defmodule Burrito.Steps.Build.PackAndBuild do
     Path.join(self_path, ["src/", "_metadata.json"]) |> File.write!(encoded)
   end
 
+  # `%{"de" => %{payload_corrupted: "..."}}` becomes one entry per text, the wrapper looks them up by locale and key
+  defp flatten_translations(translations) do
+    for {locale, texts} <- translations, {key, text} <- texts do
+      %{locale: to_string(locale), key: to_string(key), text: text}
+    end
+  end
+
+  # Custom ERTS builds don't tell us which OTP release they came from
+  defp get_otp_version(%Target{erts_source: {_, opts}}) when is_list(opts), do: opts[:version]
+  defp get_otp_version(_target), do: nil
+
+  # A fully static ERTS (e.g. a static musl build) has no ELF interpreter,
+  # the wrapper then skips its dynamic linker and libc checks
+  defp static_erts?(work_dir, release) do
+    erlexec = Path.join([work_dir, "erts-#{release.erts_version}", "bin", "erlexec"])
+
+    case File.read(erlexec) do
+      {:ok, <<0x7F, "ELF", class, 1, _::binary>> = elf} -> not has_interpreter?(elf, class)
+      _ -> false
+    end
+  end
+
+  @pt_interp 3
+
+  # Walks the program headers of a little-endian ELF file looking for PT_INTERP
+  defp has_interpreter?(elf, class) do
+    {phoff, phentsize, phnum} =
+      case class do
+        2 ->
+          <<_::binary-size(32), phoff::little-64, _::binary-size(14), phentsize::little-16,
+            phnum::little-16, _::binary>> = elf
+
+          {phoff, phentsize, phnum}
+
+        1 ->
+          <<_::binary-size(28), phoff::little-32, _::binary-size(10), phentsize::little-16,
+            phnum::little-16, _::binary>> = elf
+
+          {phoff, phentsize, phnum}
+      end
+
+    Enum.any?(0..(phnum - 1)//1, fn index ->
+      <<_::binary-size(phoff + index * phentsize), p_type::little-32, _::binary>> = elf
+      p_type == @pt_interp
+    end)
+  end
+
+  # Reproducible builds pin the build time through `SOURCE_DATE_EPOCH`
+  defp get_build_time do
+    with epoch when is_binary(epoch) <- System.get_env("SOURCE_DATE_EPOCH"),
+         {seconds, ""} <- Integer.parse(epoch),
+         {:ok, time} <- DateTime.from_unix(seconds) do
+      time
+    else
+      _ -> DateTime.utc_now() |> DateTime.truncate(:second)
+    end
+  end
+
+  defp get_git_sha do
+    with git when is_binary(git) <- System.find_executable("git"),
+         {sha, 0} <- System.cmd(git, ["rev-parse", "--short", "HEAD"], stderr_to_stdout: true) do
+      String.trim(sha)
+    else
+      _ -> nil
+    end
+  end
+
+  # `windows_uninstall_entry: true` registers with the defaults, a keyword list overrides them
+  defp get_uninstall_entry(options) do
+    case Keyword.get(options, :windows_uninstall_entry, false) do
+      true -> []
+      entry when is_list(entry) -> entry
+      _ -> nil
+    end
+  end
+
+  defp maybe_to_string(nil), do: nil
+  defp maybe_to_string(value), do: to_string(value)
+
   defp is_prod(%Target{debug?: debug?}) do
     cond do
       debug? -> "0"
@@ -95,12 +478,28 @@ defmodule Burrito.Steps.Build.PackAndBuild do
     out = Path.join(self_path, "zig-out")
     payload = Path.join(self_path, "payload.foilz")
     compressed_payload = Path.join(self_path, ["src/", "payload.foilz.xz"])
+    payload_chunks = Path.join(self_path, ["src/", "payload_chunks"])
+    licenses = Path.join(self_path, "licenses")
+    licenses_payload = Path.join(self_path, ["src/", "licenses.foilz"])
+    sbom = Path.join(self_path, ["src/", "sbom.bin"])
+    runtime_config = Path.join(self_path, ["src/", "runtime_config.toml"])
+    windows_resources = Path.join(self_path, ["src/", "wrapper.res"])
+    components = Path.join(self_path, "components")
+    components_json = Path.join(self_path, ["src/", "components.json"])
     metadata = Path.join(self_path, ["src/", "_metadata.json"])
 
     File.rmdir(cache)
     File.rmdir(out)
     File.rm(payload)
     File.rm(compressed_payload)
+    File.rm_rf(payload_chunks)
+    File.rm_rf(licenses)
+    File.rm(licenses_payload)
+    File.rm(sbom)
+    File.rm(runtime_config)
+    File.rm(windows_resources)
+    File.rm_rf(components)
+    File.rm(components_json)
     File.rm(metadata)
 
     :ok
@@ -0,0 +1,38 @@
+defmodule Burrito.Util.Signals do
+  @moduledoc """
+  This module provides a way to react to OS signals that reach the Erlang VM of a Burrito binary.
+
+  When the wrapper supervises the VM (see the `pty` and `shutdown_timeout` options), it forwards `SIGHUP`
+  to the VM, otherwise the VM receives it directly. Either way, the VM ignores `SIGHUP` unless told otherwise,
+  so register a callback to get the usual "reload config on HUP" behaviour:
+
+  ```elixir
+  def start(_, _) do
+    Burrito.Util.Signals.on_sighup(fn -> MyApp.Config.reload() end)
+    # ...
+  end
+  ```
+  """
+
+  @behaviour :gen_event
+
+  @spec on_sighup((-> any())) :: :ok | {:error, term()}
+  def on_sighup(callback) when is_function(callback, 0) do
+    :ok = :os.set_signal(:sighup, :handle)
+    :gen_event.add_handler(:erl_signal_server, __MODULE__, callback)
+  end
+
+  @impl :gen_event
+  def init(callback), do: {:ok, callback}
+
+  @impl :gen_event
+  def handle_event(:sighup, callback) do
+    callback.()
+    {:ok, callback}
+  end
+
+  def handle_event(_signal, callback), do: {:ok, callback}
+
+  @impl :gen_event
+  def handle_call(_request, callback), do: {:ok, :ok, callback}
+end
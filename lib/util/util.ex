@@ -1,10 +1,11 @@
 defmodule Burrito.Util do
-  @spec get_current_os :: :darwin | :linux | :windows
+  @spec get_current_os :: :darwin | :linux | :freebsd | :windows
   def get_current_os do
     case :os.type() do
       {:win32, _} -> :windows
       {:unix, :darwin} -> :darwin
       {:unix, :linux} -> :linux
+      {:unix, :freebsd} -> :freebsd
     end
   end
 
@@ -32,8 +33,12 @@ defmodule Burrito.Util do
 
     case arch_string do
       "x86_64" -> :x86_64
+      "amd64" -> :x86_64
       "arm64" -> :aarch64
       "aarch64" -> :aarch64
+      "riscv64" -> :riscv64
+      "arm" -> :armv7
+      "armv7l" -> :armv7
       _ -> :unknown
     end
   end
@@ -0,0 +1,141 @@
+/////
+// A wrapper inside a MacOS app bundle (`MyApp.app/Contents/MacOS/<binary>`, see the `macos_app_bundle` option) acts
+// like a regular Mac application: it installs under `~/Library/Application Support/<bundle identifier>` instead of
+// the shared `.burrito` directory, and passes the bundle's `Info.plist` details to the VM.
+//
+// When double-clicked, Finder starts the binary with no terminal, `/` as its working directory, and on older
+// MacOS versions a `-psn_...` argument. Our messages then go to the system log, the VM starts in the app's data
+// directory, and the argument is dropped.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const log = std.log;
+const mem = std.mem;
+
+const MAX_PLIST_SIZE = 1024 * 1024;
+
+pub const Bundle = struct {
+    // The `.app` directory
+    path: []const u8,
+    // `CFBundleIdentifier`, or the app name if the bundle has none
+    identifier: []const u8,
+    // `CFBundleName` and `CFBundleShortVersionString`
+    name: ?[]const u8 = null,
+    version: ?[]const u8 = null,
+};
+
+var active: ?Bundle = null;
+var gui_launch = false;
+
+// Checks whether we run from inside an app bundle, once at startup
+pub fn init(allocator: mem.Allocator, meta: *const MetaStruct) void {
+    if (builtin.os.tag != .macos) {
+        return;
+    }
+
+    const exe_path = fs.selfExePathAlloc(allocator) catch return;
+    const macos_dir = fs.path.dirname(exe_path) orelse return;
+    const contents_dir = fs.path.dirname(macos_dir) orelse return;
+    const bundle_path = fs.path.dirname(contents_dir) orelse return;
+    if (!mem.eql(u8, fs.path.basename(macos_dir), "MacOS") or !mem.eql(u8, fs.path.basename(contents_dir), "Contents") or !mem.endsWith(u8, bundle_path, ".app")) {
+        return;
+    }
+
+    var bundle = Bundle{ .path = bundle_path, .identifier = meta.app_name };
+    const plist_path = fs.path.join(allocator, &[_][]const u8{ contents_dir, "Info.plist" }) catch return;
+    if (fs.cwd().readFileAlloc(allocator, plist_path, MAX_PLIST_SIZE)) |plist| {
+        bundle.identifier = get_plist_string(allocator, plist, "CFBundleIdentifier") orelse meta.app_name;
+        bundle.name = get_plist_string(allocator, plist, "CFBundleName");
+        bundle.version = get_plist_string(allocator, plist, "CFBundleShortVersionString");
+    } else |err| {
+        log.warn("Could not read the app bundle's Info.plist: {!}", .{err});
+    }
+
+    log.debug("Running from the app bundle {s} ({s})", .{ bundle.path, bundle.identifier });
+    active = bundle;
+    gui_launch = !std.io.getStdIn().isTty();
+}
+
+pub fn get() ?*const Bundle {
+    return if (active) |*bundle| bundle else null;
+}
+
+// Started from Finder, the Dock or `open`, rather than from a terminal
+pub fn is_gui_launch() bool {
+    return gui_launch;
+}
+
+// Removes the process serial number argument older MacOS versions pass to applications started from Finder
+pub fn remove_finder_args(args: [][]u8) [][]u8 {
+    var kept: usize = 0;
+    for (args) |arg| {
+        if (mem.startsWith(u8, arg, "-psn_")) continue;
+        args[kept] = arg;
+        kept += 1;
+    }
+    return args[0..kept];
+}
+
+// Where the install and the app's own files go, `~/Library/Application Support/<bundle identifier>`
+pub fn get_data_dir(allocator: mem.Allocator, bundle: *const Bundle) ![]const u8 {
+    return try fs.getAppDataDir(allocator, bundle.identifier);
+}
+
+// Tells the VM which bundle it runs from, e.g. to find files in `Contents/Resources`
+pub fn apply_env(allocator: mem.Allocator, env_map: *std.process.EnvMap) !void {
+    const bundle = get() orelse return;
+
+    try env_map.put("BURRITO_APP_BUNDLE", bundle.path);
+    try env_map.put("BURRITO_APP_BUNDLE_ID", bundle.identifier);
+    try env_map.put("BURRITO_APP_BUNDLE_RESOURCES", try fs.path.join(allocator, &[_][]const u8{ bundle.path, "Contents", "Resources" }));
+    if (bundle.version) |version| {
+        try env_map.put("BURRITO_APP_BUNDLE_VERSION", version);
+    }
+}
+
+// Finder starts applications in `/`, which the VM can't write to, so a GUI launch starts it in the data directory
+pub fn enter_working_dir(allocator: mem.Allocator) void {
+    const bundle = get() orelse return;
+    if (!gui_launch) {
+        return;
+    }
+
+    const data_dir = get_data_dir(allocator, bundle) catch return;
+    std.os.chdir(data_dir) catch |err| {
+        log.warn("Could not change into {s}: {!}", .{ data_dir, err });
+    };
+}
+
+// Reads `<key>name</key><string>value</string>` from an XML property list, binary ones aren't supported
+fn get_plist_string(allocator: mem.Allocator, plist: []const u8, comptime key: []const u8) ?[]const u8 {
+    const key_tag = "<key>" ++ key ++ "</key>";
+    const key_end = (mem.indexOf(u8, plist, key_tag) orelse return null) + key_tag.len;
+    const rest = mem.trimLeft(u8, plist[key_end..], " \t\r\n");
+    if (!mem.startsWith(u8, rest, "<string>")) {
+        return null;
+    }
+    const value = rest["<string>".len..];
+    const value_end = mem.indexOf(u8, value, "</string>") orelse return null;
+    return unescape_xml(allocator, value[0..value_end]) catch null;
+}
+
+fn unescape_xml(allocator: mem.Allocator, text: []const u8) ![]const u8 {
+    const entities = [_][2][]const u8{
+        .{ "&lt;", "<" },
+        .{ "&gt;", ">" },
+        .{ "&quot;", "\"" },
+        .{ "&apos;", "'" },
+        // Last, so `&amp;lt;` stays `&lt;`
+        .{ "&amp;", "&" },
+    };
+    var result = text;
+    for (entities) |entity| {
+        result = try mem.replaceOwned(u8, allocator, result, entity[0], entity[1]);
+    }
+    return result;
+}
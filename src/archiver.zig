@@ -19,7 +19,7 @@
 //                 │         │       File Bytes       │◄───────── Raw bytes of file
 //                 │         │                        │
 //                 │         ├────────────────────────┤
-//                 └──────── │   usize   File Mode    │◄───────── POSIX File Mode (Ignored on Windows)
+//                 └──────── │   u64   File Mode      │◄───────── POSIX File Mode (Ignored on Windows)
 //                           ├────────────────────────┤
 //                           │                        │
 //                           │ Magic Trailer: 'FOILZ' │
@@ -37,12 +37,23 @@ const fs = std.fs;
 const log = std.log;
 const mem = std.mem;
 const os = std.os;
+const linux = os.linux;
 const gzip = std.compress.gzip;
 
+const logger = @import("logger.zig");
+const progress = @import("progress.zig");
+const trace = @import("trace.zig");
+
 const xz = @cImport(@cInclude("xz.h"));
 
 const MAGIC = "FOILZ";
-const MAX_READ_SIZE = 1000000000;
+
+// Bumped whenever the layout of the archive changes
+pub const FORMAT_VERSION: u32 = 1;
+// The compression applied to the archive before it's embedded into the wrapper
+pub const CODEC = "xz";
+
+pub const MAX_READ_SIZE = 1000000000;
 
 pub fn pack_directory(path: []const u8, archive_path: []const u8) anyerror!void {
     var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
@@ -58,41 +69,57 @@ pub fn pack_directory(path: []const u8, archive_path: []const u8) anyerror!void
     var walker = try dir.walk(allocator);
 
     var count: u32 = 0;
+    var case_folded_paths = CaseFoldedPaths.init(allocator);
 
     try write_magic_number(&foilz_writer);
 
+    // The walker returns entries in whatever order the file system keeps them, sorting them makes
+    // the payload (and so the wrapper) byte-for-byte reproducible across builds of the same release
+    var file_paths = std.ArrayList([]const u8).init(allocator);
     while (try walker.next()) |entry| {
         if (entry.kind == .file) {
-            // Replace some path string data for the tar index name
-            // specifically replace: '../_build/prod/rel/' --> ''
-            // This just makes it easier to write the files out later on the destination machine
-            const needle = path;
-            const replacement = "";
-            const replacement_size = mem.replacementSize(u8, entry.path, needle, replacement);
-            var dest_buff: [fs.MAX_PATH_BYTES]u8 = undefined;
-            const index = dest_buff[0..replacement_size];
-            _ = mem.replace(u8, entry.path, needle, replacement, index);
+            try file_paths.append(try allocator.dupe(u8, entry.path));
+        }
+    }
+    mem.sort([]const u8, file_paths.items, {}, path_less_than);
 
-            // Read the entire contents of the file into a buffer
-            const file = try entry.dir.openFile(entry.basename, .{});
-            defer file.close();
+    for (file_paths.items) |entry_path| {
+        // Replace some path string data for the tar index name
+        // specifically replace: '../_build/prod/rel/' --> ''
+        // This just makes it easier to write the files out later on the destination machine
+        const needle = path;
+        const replacement = "";
+        const replacement_size = mem.replacementSize(u8, entry_path, needle, replacement);
+        var dest_buff: [fs.MAX_PATH_BYTES]u8 = undefined;
+        const index = dest_buff[0..replacement_size];
+        _ = mem.replace(u8, entry_path, needle, replacement, index);
 
-            // Allocate memory for the file
-            var file_arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
-            defer file_arena.deinit();
-            var file_allocator = file_arena.allocator();
+        // The payload must extract the same on case-insensitive file systems (MacOS, Windows)
+        if (try case_folded_paths.check(index)) |other| {
+            log.err("The release contains two paths that only differ by case: {s} and {s}", .{ other, index });
+            log.err("They would overwrite each other when extracted on MacOS or Windows, please rename one of them.", .{});
+            return error.CaseCollision;
+        }
 
-            // Read the file
-            const file_buffer = try file.readToEndAlloc(file_allocator, MAX_READ_SIZE);
-            const stat = try file.stat();
+        // Read the entire contents of the file into a buffer
+        const file = try dir.dir.openFile(entry_path, .{});
+        defer file.close();
 
-            // Write file record to archive
-            try write_file_record(&foilz_writer, index, file_buffer, stat.mode);
+        // Allocate memory for the file
+        var file_arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+        defer file_arena.deinit();
+        var file_allocator = file_arena.allocator();
 
-            count = count + 1;
+        // Read the file
+        const file_buffer = try file.readToEndAlloc(file_allocator, MAX_READ_SIZE);
+        const stat = try file.stat();
 
-            direct_log("\rinfo: 🔍 Files Packed: {}", .{count});
-        }
+        // Write file record to archive
+        try write_file_record(&foilz_writer, index, file_buffer, stat.mode);
+
+        count = count + 1;
+
+        direct_log("\rinfo: 🔍 Files Packed: {}", .{count});
     }
     direct_log("\n", .{});
 
@@ -108,38 +135,125 @@ pub fn pack_directory(path: []const u8, archive_path: []const u8) anyerror!void
     arch_file.close();
 }
 
+// Orders by parent directory first, so the files of a directory are packed next to each other (see `Batch`),
+// e.g. `a/b.txt`, `a/c.txt`, then `a/b/c.txt`
+fn path_less_than(_: void, a: []const u8, b: []const u8) bool {
+    const dir_order = mem.order(u8, fs.path.dirname(a) orelse "", fs.path.dirname(b) orelse "");
+    if (dir_order != .eq) {
+        return dir_order == .lt;
+    }
+    return mem.order(u8, fs.path.basename(a), fs.path.basename(b)) == .lt;
+}
+
+// Remembers paths by their lowercase form, to find paths that are the same on a case-insensitive file system
+const CaseFoldedPaths = struct {
+    allocator: std.mem.Allocator,
+    paths: std.StringHashMap([]const u8),
+
+    fn init(allocator: std.mem.Allocator) CaseFoldedPaths {
+        return .{ .allocator = allocator, .paths = std.StringHashMap([]const u8).init(allocator) };
+    }
+
+    // Records `path`, returns the path seen earlier that it collides with, if any
+    fn check(self: *CaseFoldedPaths, path: []const u8) !?[]const u8 {
+        const folded = try std.ascii.allocLowerString(self.allocator, path);
+        const result = try self.paths.getOrPut(folded);
+        if (result.found_existing) {
+            return result.value_ptr.*;
+        }
+        result.value_ptr.* = try self.allocator.dupe(u8, path);
+        return null;
+    }
+};
+
 pub fn write_magic_number(foilz_writer: *const fs.File.Writer) !void {
     _ = try foilz_writer.write(MAGIC);
 }
 
-pub fn write_file_record(foilz_writer: *const fs.File.Writer, name: []const u8, data: []const u8, mode: usize) !void {
+pub fn write_file_record(foilz_writer: *const fs.File.Writer, name: []const u8, data: []const u8, mode: u64) !void {
     _ = try foilz_writer.writeInt(u64, name.len, .Little);
     _ = try foilz_writer.write(name);
     _ = try foilz_writer.writeInt(u64, data.len, .Little);
     if (data.len > 0) {
         _ = try foilz_writer.write(data);
     }
-    _ = try foilz_writer.writeInt(usize, mode, .Little);
+    _ = try foilz_writer.writeInt(u64, mode, .Little);
 }
 
 pub fn validate_magic(first_bytes: []const u8) bool {
     return mem.eql(u8, first_bytes, MAGIC);
 }
 
-pub fn unpack_files(data: []const u8, dest_path: []const u8, uncompressed_size: u64) !void {
-    // Decompress the data in the payload
+pub const Record = struct {
+    name: []const u8,
+    data: []const u8,
+    mode: u64,
+};
 
-    var decompress_arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
-    defer decompress_arena.deinit();
-    var allocator = decompress_arena.allocator();
+// Walks the file records of a decompressed FOILZ archive. Lengths are checked against what's left of the archive,
+// a corrupted or hand-made archive fails with `error.BadRecord` instead of reading past its end.
+pub const RecordIterator = struct {
+    archive: []const u8,
+    // We start at position 5 to skip the header
+    cursor: usize = 5,
 
-    var decompressed: []u8 = try allocator.alloc(u8, uncompressed_size);
+    pub fn next(self: *RecordIterator) !?Record {
+        //////
+        // Read until we reach the end of the trailer
+        // Look ahead 5 bytes and see
+        if (self.archive.len - self.cursor < MAGIC.len) {
+            return error.BadRecord;
+        }
+        if (self.archive.len - self.cursor == MAGIC.len) {
+            if (!validate_magic(self.archive[self.cursor..])) {
+                return error.BadRecord;
+            }
+            return null;
+        }
+
+        //////
+        // Read the file name
+        const name_len = try self.read_u64();
+        if (name_len == 0 or name_len > MAX_NAME_LEN) {
+            return error.BadRecord;
+        }
+        const file_name = try self.take(name_len);
+
+        //////
+        // Read the file data from the payload
+        const file_len = try self.read_u64();
+        const file_data = try self.take(file_len);
+
+        //////
+        // Read the mode for this file, always 8 bytes, even on 32-bit targets
+        const file_mode = try self.read_u64();
+
+        return Record{ .name = file_name, .data = file_data, .mode = file_mode };
+    }
+
+    fn read_u64(self: *RecordIterator) !u64 {
+        return std.mem.readIntLittle(u64, (try self.take(@sizeOf(u64)))[0..@sizeOf(u64)]);
+    }
+
+    fn take(self: *RecordIterator, len: u64) ![]const u8 {
+        if (len > self.archive.len - self.cursor) {
+            return error.BadRecord;
+        }
+        const bytes = self.archive[self.cursor .. self.cursor + @as(usize, @intCast(len))];
+        self.cursor += bytes.len;
+        return bytes;
+    }
+};
+
+// Decompresses an embedded payload back into a FOILZ archive
+pub fn decompress(allocator: std.mem.Allocator, data: []const u8, uncompressed_size: u64) ![]u8 {
+    var decompressed: []u8 = try allocator.alloc(u8, @intCast(uncompressed_size));
 
     var xz_buffer: xz.xz_buf = .{
         .in = data.ptr,
         .in_size = data.len,
         .out = decompressed.ptr,
-        .out_size = uncompressed_size,
+        .out_size = decompressed.len,
         .in_pos = 0,
         .out_pos = 0,
     };
@@ -154,83 +268,945 @@ pub fn unpack_files(data: []const u8, dest_path: []const u8, uncompressed_size:
         return error.ParseError;
     }
 
-    // Validate the header of the payload
-    if (!validate_magic(decompressed[0..5])) {
+    return decompressed;
+}
+
+// Validates the header of a decompressed archive, and returns an iterator over its records
+pub fn iterate_records(archive: []const u8) !RecordIterator {
+    if (archive.len < MAGIC.len or !validate_magic(archive[0..MAGIC.len])) {
         return error.BadHeader;
     }
 
-    // We start at position 5 to skip the header
-    var cursor: u64 = 5;
+    return RecordIterator{ .archive = archive };
+}
+
+// Decompressed chunks are handed from the decoder thread to the extraction as they're ready, only a few of them
+// exist at a time, so the whole uncompressed archive is never held in memory
+const CHUNK_SIZE = 4 * 1024 * 1024;
+const CHUNK_COUNT = 4;
+// Payloads are packed with `xz -9`, which uses a 64 MiB dictionary
+const MAX_DICT_SIZE = 64 * 1024 * 1024;
+// Files read ahead of the writer threads, beyond this the reader waits for them to catch up
+const MAX_IN_FLIGHT_BYTES = 64 * 1024 * 1024;
+// Nothing in a release comes close, anything longer is a corrupted record
+const MAX_NAME_LEN = 4096;
+
+const Chunk = struct {
+    data: []u8,
+    len: usize = 0,
+};
+
+// A bounded channel of chunks, filled and read in order
+const Pipe = struct {
+    chunks: [CHUNK_COUNT]Chunk,
+    mutex: std.Thread.Mutex = .{},
+    not_empty: std.Thread.Condition = .{},
+    not_full: std.Thread.Condition = .{},
+    // The next chunk to read, and how many are filled
+    head: usize = 0,
+    count: usize = 0,
+    // Set by the decoder at the end of the stream, or when it failed
+    finished: bool = false,
+    failure: ?anyerror = null,
+    // Set by the reader when it gives up, so the decoder doesn't wait for it forever
+    closed: bool = false,
+
+    // Returns the next chunk to fill, null if the reader went away
+    fn acquire_empty(self: *Pipe) ?*Chunk {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        while (self.count == CHUNK_COUNT and !self.closed) {
+            self.not_full.wait(&self.mutex);
+        }
+        if (self.closed) return null;
+        return &self.chunks[(self.head + self.count) % CHUNK_COUNT];
+    }
+
+    fn publish(self: *Pipe, len: usize) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        self.chunks[(self.head + self.count) % CHUNK_COUNT].len = len;
+        self.count += 1;
+        self.not_empty.signal();
+    }
+
+    fn finish(self: *Pipe, failure: ?anyerror) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        self.finished = true;
+        self.failure = failure;
+        self.not_empty.signal();
+    }
+
+    // Returns the next filled chunk, null at the end of the stream
+    fn take(self: *Pipe) !?*Chunk {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        while (self.count == 0 and !self.finished) {
+            self.not_empty.wait(&self.mutex);
+        }
+        if (self.count == 0) {
+            if (self.failure) |err| return err;
+            return null;
+        }
+        return &self.chunks[self.head];
+    }
+
+    // Gives the chunk returned by `take()` back to the decoder, wiping the plaintext it held
+    fn release(self: *Pipe) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        const chunk = &self.chunks[self.head];
+        std.crypto.utils.secureZero(u8, chunk.data[0..chunk.len]);
+        self.head = (self.head + 1) % CHUNK_COUNT;
+        self.count -= 1;
+        self.not_full.signal();
+    }
+
+    fn close(self: *Pipe) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        self.closed = true;
+        self.not_full.signal();
+    }
+};
+
+const PipeReader = struct {
+    pipe: *Pipe,
+    current: ?*Chunk = null,
+    position: usize = 0,
+
+    const Reader = std.io.Reader(*PipeReader, anyerror, read);
+
+    fn reader(self: *PipeReader) Reader {
+        return .{ .context = self };
+    }
+
+    fn read(self: *PipeReader, buffer: []u8) anyerror!usize {
+        while (true) {
+            if (self.current) |chunk| {
+                if (self.position < chunk.len) {
+                    const count = @min(buffer.len, chunk.len - self.position);
+                    @memcpy(buffer[0..count], chunk.data[self.position .. self.position + count]);
+                    self.position += count;
+                    return count;
+                }
+                self.pipe.release();
+                self.current = null;
+                self.position = 0;
+            }
+            self.current = (try self.pipe.take()) orelse return 0;
+        }
+    }
+};
+
+fn decode(pipe: *Pipe, compressed_chunks: []const []const u8) void {
+    if (decode_into(pipe, compressed_chunks)) {
+        pipe.finish(null);
+    } else |err| {
+        pipe.finish(err);
+    }
+}
+
+// The compressed payload may come in several chunks (see build.zig), they're fed to the decoder one after the other
+fn decode_into(pipe: *Pipe, compressed_chunks: []const []const u8) !void {
+    if (compressed_chunks.len == 0) {
+        return error.EmptyPayload;
+    }
+
+    xz.xz_crc32_init();
+    const state = xz.xz_dec_init(xz.XZ_DYNALLOC, MAX_DICT_SIZE) orelse return error.OutOfMemory;
+    defer xz.xz_dec_end(state);
+
+    var input_index: usize = 0;
+    var xz_buffer: xz.xz_buf = .{
+        .in = compressed_chunks[0].ptr,
+        .in_size = compressed_chunks[0].len,
+        .in_pos = 0,
+        .out = undefined,
+        .out_size = 0,
+        .out_pos = 0,
+    };
+
+    while (true) {
+        const chunk = pipe.acquire_empty() orelse return;
+        xz_buffer.out = chunk.data.ptr;
+        xz_buffer.out_size = chunk.data.len;
+        xz_buffer.out_pos = 0;
+
+        // Returns with the output chunk full, the input chunk used up, or the end of the stream
+        const ret = while (true) {
+            const timer = trace.start(.decompress);
+            const chunk_ret = xz.xz_dec_run(state, &xz_buffer);
+            timer.stop();
+
+            if (chunk_ret != xz.XZ_OK or xz_buffer.out_pos == xz_buffer.out_size) {
+                break chunk_ret;
+            }
+            if (xz_buffer.in_pos == xz_buffer.in_size) {
+                input_index += 1;
+                if (input_index == compressed_chunks.len) {
+                    break xz.XZ_DATA_ERROR;
+                }
+                xz_buffer.in = compressed_chunks[input_index].ptr;
+                xz_buffer.in_size = compressed_chunks[input_index].len;
+                xz_buffer.in_pos = 0;
+            }
+        };
+
+        pipe.publish(xz_buffer.out_pos);
+
+        switch (ret) {
+            xz.XZ_OK => {},
+            xz.XZ_STREAM_END => return,
+            else => {
+                std.log.err("XZ/LZMA Decode Failed: {}", .{ret});
+                return error.ParseError;
+            },
+        }
+    }
+}
+
+pub const UnpackOptions = struct {
+    // Apply the modes from the payload as they are, see `sanitize_mode()`
+    trusted_modes: bool = false,
+    // Threads writing files, with 1 they're written by the calling thread
+    threads: usize = 1,
+    // Create and write files through io_uring where the kernel allows it (Linux only)
+    io_uring: bool = false,
+    // Leave files that already have the contents (and mode) from the payload alone, for repairing an install
+    skip_unchanged: bool = false,
+    // The size of the decompressed archive, records claiming to be larger are rejected as corrupted
+    max_size: u64 = MAX_READ_SIZE,
+    // Carries the totals for progress events
+    counter: progress.Counter = .{ .phase = "extract", .total_files = 0, .total_bytes = 0 },
+};
+
+// Files of one directory, written by a single worker. Records are packed grouped by their directory (see
+// `path_less_than()`), so a directory's files are next to each other.
+const Batch = struct {
+    dir_name: []const u8,
+    records: []Record,
+    bytes: u64,
+};
+
+// State shared by the extraction workers
+const Extraction = struct {
+    // Records are freed by the worker that wrote them, so this one must be thread-safe
+    allocator: std.mem.Allocator,
+    dest_path: []const u8,
+    root_dir: fs.Dir,
+    trusted_modes: bool,
+    // Cleared by the first worker that finds io_uring unavailable, the others don't try again
+    io_uring: std.atomic.Atomic(bool),
+    skip_unchanged: bool,
+    // Files are created with the payload's mode minus the umask, which is what an unchanged file has
+    umask: u64,
+    counter: progress.Counter,
+    unchanged_files: u64 = 0,
+    mutex: std.Thread.Mutex = .{},
+    written: std.Thread.Condition = .{},
+    in_flight_bytes: u64 = 0,
+    failure: ?anyerror = null,
+
+    fn fail(self: *Extraction, err: anyerror) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        if (self.failure == null) {
+            self.failure = err;
+        }
+    }
+
+    fn has_failed(self: *Extraction) bool {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        return self.failure != null;
+    }
+
+    fn count_file(self: *Extraction, bytes: u64) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        self.counter.add(bytes);
+    }
+
+    fn count_unchanged_file(self: *Extraction, bytes: u64) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        self.unchanged_files += 1;
+        self.counter.add(bytes);
+    }
+
+    // Waits until the workers are below the read-ahead limit, then accounts for `bytes` more
+    fn reserve(self: *Extraction, bytes: u64) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        while (self.in_flight_bytes > 0 and self.in_flight_bytes + bytes > MAX_IN_FLIGHT_BYTES) {
+            self.written.wait(&self.mutex);
+        }
+        self.in_flight_bytes += bytes;
+    }
+
+    fn unreserve(self: *Extraction, bytes: u64) void {
+        self.mutex.lock();
+        defer self.mutex.unlock();
+        self.in_flight_bytes -= bytes;
+        self.written.signal();
+    }
+
+    fn free_batch(self: *Extraction, batch: Batch) void {
+        for (batch.records) |record| {
+            // The plaintext release (including its COOKIE file) shouldn't outlive the extraction in our memory
+            std.crypto.utils.secureZero(u8, @constCast(record.data));
+            self.allocator.free(record.data);
+            self.allocator.free(record.name);
+        }
+        self.allocator.free(batch.records);
+    }
+};
+
+// Decompresses the payload and writes its records under `dest_path` at the same time. Each record is validated
+// before it's written.
+pub fn unpack_payload(allocator: std.mem.Allocator, compressed_chunks: []const []const u8, dest_path: []const u8, options: UnpackOptions) !void {
+    var chunks: [CHUNK_COUNT]Chunk = undefined;
+    for (&chunks) |*chunk| {
+        chunk.* = .{ .data = try allocator.alloc(u8, CHUNK_SIZE) };
+    }
+    // Runs after the decoder is joined, a chunk can still hold plaintext if we stopped early
+    defer for (&chunks) |*chunk| std.crypto.utils.secureZero(u8, chunk.data);
+    var pipe = Pipe{ .chunks = chunks };
+
+    const decoder = try std.Thread.spawn(.{}, decode, .{ &pipe, compressed_chunks });
+    defer decoder.join();
+    // Also unblocks the decoder if we stop reading early
+    defer pipe.close();
+
+    var pipe_reader = PipeReader{ .pipe = &pipe };
+    const reader = pipe_reader.reader();
+
+    var gpa = std.heap.GeneralPurposeAllocator(.{ .thread_safe = true }){};
+    defer _ = gpa.deinit();
+
+    // Outside of Windows, everything is written relative to directories we already hold open (see `open_sub_dir`)
+    var extraction = Extraction{
+        .allocator = gpa.allocator(),
+        .dest_path = dest_path,
+        .root_dir = if (builtin.os.tag == .windows) undefined else try fs.openDirAbsolute(dest_path, .{ .no_follow = true }),
+        .trusted_modes = options.trusted_modes,
+        .io_uring = std.atomic.Atomic(bool).init(builtin.os.tag == .linux and options.io_uring),
+        .skip_unchanged = options.skip_unchanged,
+        .umask = if (options.skip_unchanged) get_umask() else 0,
+        .counter = options.counter,
+    };
+    defer if (builtin.os.tag != .windows) extraction.root_dir.close();
+
+    var pool: std.Thread.Pool = undefined;
+    var wait_group = std.Thread.WaitGroup{};
+    const use_pool = options.threads > 1;
+    if (use_pool) {
+        // Workers free their jobs while we keep allocating, `allocator` is usually an arena and not thread-safe
+        try pool.init(.{ .allocator = extraction.allocator, .n_jobs = @intCast(options.threads) });
+    }
+    // Declared last, so the workers are done before the directory and the allocator they use go away
+    defer if (use_pool) pool.deinit();
+    defer if (use_pool) pool.waitAndWork(&wait_group);
+
+    log.debug("Unpacking with {} threads", .{options.threads});
+
+    var header: [MAGIC.len]u8 = undefined;
+    try reader.readNoEof(&header);
+    if (!validate_magic(&header)) {
+        return error.BadHeader;
+    }
+
+    var case_folded_paths = CaseFoldedPaths.init(allocator);
     var file_count: u64 = 0;
+    var batch_records = std.ArrayList(Record).init(extraction.allocator);
+    defer {
+        for (batch_records.items) |record| {
+            extraction.allocator.free(record.data);
+            extraction.allocator.free(record.name);
+        }
+        batch_records.deinit();
+    }
+    var batch_dir_name: []const u8 = "";
+    var batch_bytes: u64 = 0;
+    // What the archive can still hold after its header and trailer
+    var remaining: u64 = options.max_size -| 2 * MAGIC.len;
 
-    //////
-    // Read until we reach the end of the trailer
-    // Look ahead 5 bytes and see
-    while (cursor < decompressed.len - 5) {
-        //////
-        // Read the file name
-        var string_len = std.mem.readIntSliceLittle(u64, decompressed[cursor .. cursor + @sizeOf(u64)]);
-        cursor = cursor + @sizeOf(u64);
+    while (read_record(reader, extraction.allocator, remaining) catch |err| {
+        logger.set_error_context("reading the payload", null);
+        return err;
+    }) |record| {
+        errdefer {
+            extraction.allocator.free(record.data);
+            extraction.allocator.free(record.name);
+        }
 
-        var file_name = decompressed[cursor .. cursor + string_len];
-        cursor = cursor + string_len;
+        // Two lengths and the mode come with each record
+        remaining -|= record.name.len + record.data.len + 3 * @sizeOf(u64);
 
-        //////
-        // Read the file data from the payload
-        var file_len = std.mem.readIntSliceLittle(u64, decompressed[cursor .. cursor + @sizeOf(u64)]);
-        cursor = cursor + @sizeOf(u64);
+        const file_name = record.name;
+        const dir_name = fs.path.dirnamePosix(file_name) orelse "";
 
-        var file_data = decompressed[cursor .. cursor + file_len];
-        cursor = cursor + file_len;
+        // Payloads are checked when packed, but an older or hand-made one could still hit this
+        if (builtin.os.tag == .windows or builtin.os.tag.isDarwin()) {
+            if (try case_folded_paths.check(file_name)) |other| {
+                log.err("The payload contains two paths that only differ by case: {s} and {s}", .{ other, file_name });
+                log.err("This file system is case-insensitive, so one would overwrite the other.", .{});
+                return fail_at(&extraction, "unpacking", file_name, error.CaseCollision);
+            }
+        }
 
-        //////
-        // Read the mode for this file
-        var file_mode = std.mem.readIntSliceLittle(usize, decompressed[cursor .. cursor + @sizeOf(usize)]);
-        cursor = cursor + @sizeOf(usize);
+        // Never let a corrupted or malicious payload write outside of the install directory
+        if (!try is_contained(allocator, dest_path, file_name)) {
+            log.err("The payload contains a path that escapes the install directory: {s}", .{file_name});
+            logger.set_error_context("unpacking", file_name);
+            return error.PathTraversal;
+        }
 
-        //////
-        // Write the file
-        const full_file_path = try fs.path.join(allocator, &[_][]const u8{ dest_path[0..], file_name });
+        if (batch_records.items.len > 0 and !mem.eql(u8, dir_name, batch_dir_name)) {
+            try submit_batch(&extraction, &batch_records, batch_dir_name, batch_bytes, if (use_pool) &pool else null, &wait_group);
+            batch_bytes = 0;
+        }
+
+        try batch_records.append(record);
+        // Points into the record, which lives as long as the batch
+        batch_dir_name = fs.path.dirnamePosix(batch_records.items[batch_records.items.len - 1].name) orelse "";
+        batch_bytes += record.data.len;
+        file_count += 1;
+
+        if (extraction.has_failed()) break;
+    }
+
+    if (batch_records.items.len > 0 and !extraction.has_failed()) {
+        try submit_batch(&extraction, &batch_records, batch_dir_name, batch_bytes, if (use_pool) &pool else null, &wait_group);
+    }
+
+    if (use_pool) {
+        pool.waitAndWork(&wait_group);
+    }
+
+    if (extraction.failure) |err| {
+        return err;
+    }
+
+    if (options.skip_unchanged) {
+        log.debug("Unpacked {} files, {} of them were already unchanged", .{ file_count, extraction.unchanged_files });
+    } else {
+        log.debug("Unpacked {} files", .{file_count});
+    }
+}
+
+// Reads the next record from a decompressed archive stream, null once the trailer is reached. Nothing is allocated
+// for a record that claims more than `max_len` bytes, the most the rest of the archive can hold.
+pub fn read_record(reader: anytype, allocator: std.mem.Allocator, max_len: u64) !?Record {
+    // Either the trailer, or the start of the name length. A name that long can't be in a valid archive.
+    var length_bytes: [@sizeOf(u64)]u8 = undefined;
+    try reader.readNoEof(length_bytes[0..MAGIC.len]);
+    if (validate_magic(length_bytes[0..MAGIC.len])) {
+        return null;
+    }
+    try reader.readNoEof(length_bytes[MAGIC.len..]);
+
+    const name_len = std.mem.readIntLittle(u64, &length_bytes);
+    if (name_len == 0 or name_len > MAX_NAME_LEN or name_len > max_len) {
+        return error.BadRecord;
+    }
+    const name = try allocator.alloc(u8, @intCast(name_len));
+    errdefer allocator.free(name);
+    try reader.readNoEof(name);
+
+    const data_len = try reader.readIntLittle(u64);
+    if (data_len > MAX_READ_SIZE or data_len > max_len - name_len) {
+        return error.BadRecord;
+    }
+    const data = try allocator.alloc(u8, @intCast(data_len));
+    errdefer allocator.free(data);
+    try reader.readNoEof(data);
+
+    // Always 8 bytes, even on 32-bit targets
+    const mode = try reader.readIntLittle(u64);
+
+    return Record{ .name = name, .data = data, .mode = mode };
+}
+
+// Hands the collected records to a worker, or writes them right away without a pool
+fn submit_batch(extraction: *Extraction, records: *std.ArrayList(Record), dir_name: []const u8, bytes: u64, pool: ?*std.Thread.Pool, wait_group: *std.Thread.WaitGroup) !void {
+    const batch = Batch{ .dir_name = dir_name, .records = try records.toOwnedSlice(), .bytes = bytes };
+
+    if (pool) |thread_pool| {
+        extraction.reserve(bytes);
+        wait_group.start();
+        thread_pool.spawn(extract_batch_in_pool, .{ extraction, batch, wait_group }) catch |err| {
+            wait_group.finish();
+            extraction.unreserve(bytes);
+            extraction.free_batch(batch);
+            return err;
+        };
+    } else {
+        extract_batch(extraction, batch);
+        extraction.free_batch(batch);
+    }
+}
+
+fn extract_batch_in_pool(extraction: *Extraction, batch: Batch, wait_group: *std.Thread.WaitGroup) void {
+    defer wait_group.finish();
+    defer extraction.unreserve(batch.bytes);
+    defer extraction.free_batch(batch);
+    extract_batch(extraction, batch);
+}
+
+fn extract_batch(extraction: *Extraction, batch: Batch) void {
+    // Once one file failed the install is lost anyway
+    if (extraction.has_failed()) {
+        return;
+    }
+
+    write_batch(extraction, batch) catch |err| {
+        log.err("Failed to unpack into {s}/{s}: {!}", .{ extraction.dest_path, batch.dir_name, err });
+        extraction.fail(err);
+    };
+}
+
+fn write_batch(extraction: *Extraction, batch: Batch) !void {
+    // If we're on windows don't try and use file_mode because NTFS doesn't have that!
+    if (builtin.os.tag == .windows) {
+        // Workers can't share an arena, each batch gets its own
+        var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+        defer arena.deinit();
+        const allocator = arena.allocator();
 
         //////
         // Create any directories needed
-        const dir_name = fs.path.dirname(file_name);
-        try create_dirs(dest_path[0..], dir_name.?, allocator);
+        const dir_timer = trace.start(.set_permissions);
+        create_dirs(extraction.dest_path, batch.dir_name, allocator) catch |err| {
+            return fail_at(extraction, "creating the directory", batch.dir_name, err);
+        };
+        dir_timer.stop();
 
-        log.debug("Unpacked File: {s}", .{full_file_path});
+        // Every file of a batch shares this prefix, it's only built once
+        const batch_dir_path = try extended_length_path(allocator, try fs.path.join(allocator, &[_][]const u8{ extraction.dest_path, batch.dir_name }));
 
-        //////
-        // Write the file to disk!
+        for (batch.records) |record| {
+            //////
+            // Write the file
+            const full_file_path = try fs.path.join(allocator, &[_][]const u8{ batch_dir_path, fs.path.basenamePosix(record.name) });
 
-        // If we're on windows don't try and use file_mode because NTFS doesn't have that!
-        if (builtin.os.tag == .windows) {
-            const file = try fs.createFileAbsolute(full_file_path, .{ .truncate = true });
-            if (file_len > 0) {
-                try file.writeAll(file_data);
+            if (extraction.skip_unchanged) {
+                if (fs.openFileAbsolute(full_file_path, .{})) |existing| {
+                    defer existing.close();
+                    if (is_unchanged(existing, record.data, null)) {
+                        extraction.count_unchanged_file(record.data.len);
+                        continue;
+                    }
+                } else |_| {}
             }
-            file.close();
-        } else {
-            const file = try fs.createFileAbsolute(full_file_path, .{ .truncate = true, .mode = @intCast(file_mode) });
-            if (file_len > 0) {
-                try file.writeAll(file_data);
+
+            log.debug("Unpacked File: {s}", .{full_file_path});
+
+            const create_timer = trace.start(.set_permissions);
+            const file = fs.createFileAbsolute(full_file_path, .{ .exclusive = true }) catch |err| switch (err) {
+                error.PathAlreadyExists => blk: {
+                    log.debug("Replacing stale file: {s}", .{full_file_path});
+                    fs.deleteFileAbsolute(full_file_path) catch |delete_err| {
+                        return fail_at(extraction, "replacing", record.name, delete_err);
+                    };
+                    break :blk fs.createFileAbsolute(full_file_path, .{ .exclusive = true }) catch |create_err| {
+                        return fail_at(extraction, "creating", record.name, create_err);
+                    };
+                },
+                else => return fail_at(extraction, "creating", record.name, err),
+            };
+            create_timer.stop();
+
+            const write_timer = trace.start(.write_files);
+            if (record.data.len > 0) {
+                preallocate(file.handle, record.data.len);
+                file.writeAll(record.data) catch |err| {
+                    file.close();
+                    return fail_at(extraction, "writing", record.name, err);
+                };
             }
             file.close();
+            write_timer.stop();
+
+            extraction.count_file(record.data.len);
+        }
+    } else {
+        const dir_timer = trace.start(.set_permissions);
+        var dir = open_sub_dir(extraction.root_dir, batch.dir_name) catch |err| {
+            return fail_at(extraction, "creating the directory", batch.dir_name, err);
+        };
+        defer dir.close();
+        dir_timer.stop();
+
+        // Repairs compare every file before writing it, the ring would only save syscalls on the few that changed
+        if (builtin.os.tag == .linux and !extraction.skip_unchanged and extraction.io_uring.load(.Monotonic)) {
+            if (try write_batch_io_uring(extraction, dir, batch.records)) {
+                return;
+            }
         }
 
-        file_count = file_count + 1;
+        for (batch.records) |record| {
+            const name = fs.path.basenamePosix(record.name);
+            const mode = sanitize_mode(record.mode, extraction.trusted_modes);
+
+            if (extraction.skip_unchanged) {
+                if (os.openat(dir.fd, name, os.O.RDONLY | os.O.NOFOLLOW | os.O.CLOEXEC, 0)) |fd| {
+                    const existing = fs.File{ .handle = fd };
+                    defer existing.close();
+                    if (is_unchanged(existing, record.data, mode & ~extraction.umask)) {
+                        extraction.count_unchanged_file(record.data.len);
+                        continue;
+                    }
+                } else |_| {}
+            }
+
+            log.debug("Unpacked File: {s}/{s}", .{ extraction.dest_path, record.name });
+
+            write_file_at(dir, name, record.data, mode) catch |err| {
+                return fail_at(extraction, "writing", record.name, err);
+            };
+
+            extraction.count_file(record.data.len);
+        }
     }
+}
+
+// Queued per submission, a few syscalls then create or write this many files
+const IO_URING_ENTRIES = 64;
+
+// Creates and writes the files of a batch in a few io_uring submissions, instead of three syscalls per file. A file
+// that can't be created that way (e.g. a stale one in the way) is written with `write_file_at()`.
+// Returns false if io_uring isn't available, the caller then writes the whole batch itself.
+fn write_batch_io_uring(extraction: *Extraction, dir: fs.Dir, records: []const Record) !bool {
+    var ring = linux.IO_Uring.init(IO_URING_ENTRIES, 0) catch |err| {
+        if (extraction.io_uring.swap(false, .Monotonic)) {
+            log.debug("io_uring is not available, extracting without it: {!}", .{err});
+        }
+        return false;
+    };
+    defer ring.deinit();
+
+    const flags = os.O.WRONLY | os.O.CREAT | os.O.EXCL | os.O.NOFOLLOW | os.O.CLOEXEC;
+
+    var start: usize = 0;
+    while (start < records.len) : (start += IO_URING_ENTRIES) {
+        const group = records[start..@min(start + IO_URING_ENTRIES, records.len)];
+        var names: [IO_URING_ENTRIES][:0]u8 = undefined;
+        var fds: [IO_URING_ENTRIES]os.fd_t = undefined;
+
+        for (group, 0..) |record, i| {
+            names[i] = try extraction.allocator.dupeZ(u8, fs.path.basenamePosix(record.name));
+        }
+        defer for (group, 0..) |_, i| extraction.allocator.free(names[i]);
+
+        // Create the files with their modes
+        const create_timer = trace.start(.set_permissions);
+        for (group, 0..) |record, i| {
+            _ = try ring.openat(i, dir.fd, names[i], flags, @intCast(sanitize_mode(record.mode, extraction.trusted_modes)));
+        }
+        _ = try ring.submit_and_wait(@intCast(group.len));
+        for (group) |_| {
+            const cqe = try ring.copy_cqe();
+            fds[@intCast(cqe.user_data)] = cqe.res;
+        }
+        create_timer.stop();
+
+        // Write the contents of the ones that were created
+        const write_timer = trace.start(.write_files);
+        var pending: u32 = 0;
+        for (group, 0..) |record, i| {
+            if (fds[i] >= 0 and record.data.len > 0) {
+                preallocate(fds[i], record.data.len);
+                _ = try ring.write(i, fds[i], record.data, 0);
+                pending += 1;
+            }
+        }
+        var write_failure: ?anyerror = null;
+        var failed_record: []const u8 = "";
+        if (pending > 0) {
+            _ = try ring.submit_and_wait(pending);
+        }
+        for (0..pending) |_| {
+            const cqe = try ring.copy_cqe();
+            const index: usize = @intCast(cqe.user_data);
+            const data = group[index].data;
+            if (cqe.res < 0) {
+                if (write_failure == null) {
+                    write_failure = os.unexpectedErrno(@enumFromInt(-cqe.res));
+                    failed_record = group[index].name;
+                }
+                continue;
+            }
+            // Regular files are rarely written short, but it's allowed
+            const written: usize = @intCast(cqe.res);
+            if (written < data.len) {
+                const file = fs.File{ .handle = fds[index] };
+                file.pwriteAll(data[written..], written) catch |err| {
+                    if (write_failure == null) {
+                        write_failure = err;
+                        failed_record = group[index].name;
+                    }
+                };
+            }
+        }
 
-    log.debug("Unpacked {} files", .{file_count});
+        var opened: u32 = 0;
+        for (group, 0..) |_, i| {
+            if (fds[i] >= 0) {
+                _ = try ring.close(i, fds[i]);
+                opened += 1;
+            }
+        }
+        if (opened > 0) {
+            _ = try ring.submit_and_wait(opened);
+        }
+        for (0..opened) |_| {
+            _ = try ring.copy_cqe();
+        }
+        write_timer.stop();
+
+        if (write_failure) |err| {
+            return fail_at(extraction, "writing", failed_record, err);
+        }
+
+        for (group, 0..) |record, i| {
+            log.debug("Unpacked File: {s}/{s}", .{ extraction.dest_path, record.name });
+            if (fds[i] < 0) {
+                write_file_at(dir, names[i], record.data, sanitize_mode(record.mode, extraction.trusted_modes)) catch |err| {
+                    return fail_at(extraction, "writing", record.name, err);
+                };
+            }
+            extraction.count_file(record.data.len);
+        }
+    }
+
+    return true;
+}
+
+// Records which file of the install `err` happened on, for the final error message
+fn fail_at(extraction: *const Extraction, operation: []const u8, record_name: []const u8, err: anyerror) anyerror {
+    var path_buf: [fs.MAX_PATH_BYTES]u8 = undefined;
+    const path = std.fmt.bufPrint(&path_buf, "{s}/{s}", .{ extraction.dest_path, record_name }) catch record_name;
+    logger.set_error_context(operation, path);
+    return err;
+}
+
+// Modes come straight from the payload, so setuid/setgid/sticky and group/world write bits are dropped unless the
+// release opted into `trusted_modes`. Only the permission bits are ever applied.
+pub fn sanitize_mode(mode: u64, trusted: bool) u64 {
+    if (trusted) {
+        return mode & 0o7777;
+    }
+    return mode & 0o755;
+}
+
+// Record paths are relative and `/` separated, anything absolute, with a drive or with a `..` component is rejected
+// outright, and the resolved path must still land under the destination.
+fn is_contained(allocator: std.mem.Allocator, dest_path: []const u8, file_name: []const u8) !bool {
+    if (file_name.len == 0 or file_name[0] == '/' or mem.indexOfAny(u8, file_name, "\\:\x00") != null) {
+        return false;
+    }
+
+    var components = mem.split(u8, file_name, "/");
+    while (components.next()) |component| {
+        if (mem.eql(u8, component, "..")) {
+            return false;
+        }
+    }
+
+    const root = try fs.path.resolve(allocator, &[_][]const u8{dest_path});
+    const resolved = try fs.path.resolve(allocator, &[_][]const u8{ root, file_name });
+    return resolved.len > root.len and mem.startsWith(u8, resolved, root) and fs.path.isSep(resolved[root.len]);
+}
+
+// Opens (and creates) each directory of a record path relative to its parent without following symlinks, so
+// swapping a directory under the install root for a link mid-extraction can't redirect where files are written.
+// Every batch walks down from the root, and most of the directories on the way already exist by then, so they are
+// opened first and only created when missing.
+fn open_sub_dir(root_dir: fs.Dir, sub_dir_names: []const u8) !fs.Dir {
+    var dir = try root_dir.openDir(".", .{ .no_follow = true });
+    errdefer dir.close();
+
+    var iterator = mem.split(u8, sub_dir_names, "/");
+    while (iterator.next()) |sub_dir| {
+        if (sub_dir.len == 0) {
+            continue;
+        }
+
+        const next = dir.openDir(sub_dir, .{ .no_follow = true }) catch |err| switch (err) {
+            error.FileNotFound => blk: {
+                dir.makeDir(sub_dir) catch |make_err| switch (make_err) {
+                    // Another worker got there first
+                    error.PathAlreadyExists => {},
+                    else => return make_err,
+                };
+                break :blk try dir.openDir(sub_dir, .{ .no_follow = true });
+            },
+            else => return err,
+        };
+        dir.close();
+        dir = next;
+    }
+
+    return dir;
+}
+
+// Files are always created fresh, never truncated or written through an existing file or link. Anything already in
+// the way (left over from an interrupted install, or a reinstall over a damaged one) is unlinked first, which only
+// ever removes the entry itself, never what a link points at.
+// That's one syscall to create the file with its mode and one to write it: records are already whole in memory, so
+// buffering would only add a copy.
+fn write_file_at(dir: fs.Dir, name: []const u8, data: []const u8, mode: u64) !void {
+    const flags = os.O.WRONLY | os.O.CREAT | os.O.EXCL | os.O.NOFOLLOW | os.O.CLOEXEC;
+    // The mode is applied when the file is created
+    const create_timer = trace.start(.set_permissions);
+    const fd = os.openat(dir.fd, name, flags, @intCast(mode)) catch |err| switch (err) {
+        error.PathAlreadyExists => blk: {
+            log.debug("Replacing stale file: {s}", .{name});
+            try os.unlinkat(dir.fd, name, 0);
+            break :blk try os.openat(dir.fd, name, flags, @intCast(mode));
+        },
+        else => return err,
+    };
+    create_timer.stop();
+
+    const write_timer = trace.start(.write_files);
+    defer write_timer.stop();
+    const file = fs.File{ .handle = fd };
+    defer file.close();
+
+    if (data.len > 0) {
+        preallocate(fd, data.len);
+        try file.writeAll(data);
+    }
+}
+
+// True if `file` is a regular file holding exactly `data`, with `mode` unless that's null (Windows has no modes).
+// The record is right there in memory, so the contents are compared directly instead of hashing both sides. A size
+// mismatch, the common case for a damaged file, is caught without reading anything.
+extern "c" fn umask(mode: std.c.mode_t) std.c.mode_t;
+
+// Reading the umask means setting it, it's put back right away. Called before the workers start.
+fn get_umask() u64 {
+    switch (builtin.os.tag) {
+        .windows => return 0,
+        .linux => {
+            const previous = os.linux.syscall1(.umask, 0o022);
+            _ = os.linux.syscall1(.umask, previous);
+            return previous & 0o777;
+        },
+        else => {
+            const previous = umask(0o022);
+            _ = umask(previous);
+            return @as(u64, previous) & 0o777;
+        },
+    }
+}
+
+fn is_unchanged(file: fs.File, data: []const u8, mode: ?u64) bool {
+    const stat = file.stat() catch return false;
+    if (stat.kind != .file or stat.size != data.len) {
+        return false;
+    }
+    if (mode) |expected_mode| {
+        if (stat.mode & 0o7777 != expected_mode) {
+            return false;
+        }
+    }
+
+    const compare_timer = trace.start(.write_files);
+    defer compare_timer.stop();
+
+    var buffer: [64 * 1024]u8 = undefined;
+    var offset: usize = 0;
+    while (offset < data.len) {
+        const read = file.read(buffer[0..@min(buffer.len, data.len - offset)]) catch return false;
+        if (read == 0 or !mem.eql(u8, buffer[0..read], data[offset .. offset + read])) {
+            return false;
+        }
+        offset += read;
+    }
+    return true;
+}
+
+// Below this, preallocating costs more than the fragmentation it saves
+const PREALLOCATE_MIN_SIZE = 64 * 1024;
+
+// macOS `fcntl(F_PREALLOCATE)`
+const F_PREALLOCATE = 42;
+const F_ALLOCATEALL = 0x4;
+const F_PEOFPOSMODE = 3;
+const FStore = extern struct {
+    fst_flags: c_uint,
+    fst_posmode: c_int,
+    fst_offset: i64,
+    fst_length: i64,
+    fst_bytesalloc: i64 = 0,
+};
+
+// Windows `SetFileInformationByHandle(FileAllocationInfo)`
+const FILE_ALLOCATION_INFO_CLASS = 5;
+const FileAllocationInfo = extern struct {
+    allocation_size: i64,
+};
+extern "kernel32" fn SetFileInformationByHandle(file: os.windows.HANDLE, class: c_int, info: *const anyopaque, size: os.windows.DWORD) callconv(os.windows.WINAPI) os.windows.BOOL;
+
+// Reserves the blocks of a file we're about to write `size` bytes into, so the file system can lay them out in one
+// go. Only a hint, file systems that can't do it just get the writes as before.
+fn preallocate(handle: os.fd_t, size: u64) void {
+    if (size < PREALLOCATE_MIN_SIZE) {
+        return;
+    }
+
+    switch (builtin.os.tag) {
+        .linux => _ = linux.fallocate(handle, 0, 0, @intCast(size)),
+        .macos => {
+            var store = FStore{ .fst_flags = F_ALLOCATEALL, .fst_posmode = F_PEOFPOSMODE, .fst_offset = 0, .fst_length = @intCast(size) };
+            _ = os.fcntl(handle, F_PREALLOCATE, @intFromPtr(&store)) catch {};
+        },
+        .windows => {
+            const info = FileAllocationInfo{ .allocation_size = @intCast(size) };
+            _ = SetFileInformationByHandle(handle, FILE_ALLOCATION_INFO_CLASS, &info, @sizeOf(FileAllocationInfo));
+        },
+        else => {},
+    }
 }
 
 fn create_dirs(dest_path: []const u8, sub_dir_names: []const u8, allocator: std.mem.Allocator) !void {
+    // A single check instead of a mkdir per component, when an earlier batch already created the directory
+    const batch_dir_path = try fs.path.join(allocator, &[_][]const u8{ dest_path, sub_dir_names });
+    if (fs.accessAbsolute(try extended_length_path(allocator, batch_dir_path), .{})) {
+        return;
+    } else |_| {}
+
     var iterator = mem.split(u8, sub_dir_names, "/");
     var full_dir_path = try fs.path.join(allocator, &[_][]const u8{ dest_path, "" });
 
     while (iterator.next()) |sub_dir| {
         full_dir_path = try fs.path.join(allocator, &[_][]const u8{ full_dir_path, sub_dir });
-        os.mkdir(full_dir_path, 0o755) catch {};
+        os.mkdir(try extended_length_path(allocator, full_dir_path), 0o755) catch {};
+    }
+}
+
+// Windows limits paths to MAX_PATH (260) characters unless the LongPathsEnabled policy is set, and deeply nested
+// OTP lib paths go past that. The `\\?\` prefix lifts the limit, but turns off path normalization, so the path
+// must be absolute and use backslashes only.
+fn extended_length_path(allocator: std.mem.Allocator, path: []const u8) ![]const u8 {
+    if (builtin.os.tag != .windows or mem.startsWith(u8, path, "\\\\?\\")) {
+        return path;
     }
+
+    const normalized = try allocator.dupe(u8, path);
+    mem.replaceScalar(u8, normalized, '/', '\\');
+
+    // Network shares use their own form of the prefix
+    if (mem.startsWith(u8, normalized, "\\\\")) {
+        return try std.fmt.allocPrint(allocator, "\\\\?\\UNC\\{s}", .{normalized[2..]});
+    }
+
+    return try std.fmt.allocPrint(allocator, "\\\\?\\{s}", .{normalized});
 }
 
 // Adapted from `std.log`, but without forcing a newline
@@ -0,0 +1,115 @@
+/////
+// `--burrito bench-startup`: runs this binary over and over with the app already installed, each run stopping right
+// before it would start the VM, and reports percentiles of how long that took. This is the overhead every launch
+// pays, so comparing it across releases catches wrapper regressions. `--budget <ms>` turns it into a check for CI.
+/////
+
+const std = @import("std");
+
+const logger = @import("logger.zig");
+const trace = @import("trace.zig");
+
+// Set on the runs we start, they exit instead of launching the VM
+const CHILD_ENV_NAME = "__BURRITO_BENCH_STARTUP";
+
+pub const Options = struct {
+    runs: usize = 50,
+    // Fail if the median wall time is above this, in milliseconds
+    budget_ms: ?f64 = null,
+    json: bool = false,
+};
+
+const Percentiles = struct {
+    min: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+};
+
+pub fn is_child() bool {
+    return std.process.hasEnvVarConstant(CHILD_ENV_NAME);
+}
+
+// Called by a benchmark run where it would hand over to the VM: reports how long the wrapper took, then exits
+pub fn finish_child() noreturn {
+    std.io.getStdOut().writer().print("{}\n", .{trace.elapsed_us()}) catch {};
+    std.process.exit(0);
+}
+
+pub fn run(allocator: std.mem.Allocator, options: Options) !void {
+    const exe_path = try std.fs.selfExePathAlloc(allocator);
+
+    var env_map = try std.process.getEnvMap(allocator);
+    try env_map.put(CHILD_ENV_NAME, "1");
+
+    var wall_ms = try allocator.alloc(f64, options.runs);
+    var wrapper_ms = try allocator.alloc(f64, options.runs);
+
+    for (0..options.runs) |i| {
+        const started = std.time.nanoTimestamp();
+        const result = try std.ChildProcess.exec(.{
+            .allocator = allocator,
+            .argv = &[_][]const u8{exe_path},
+            .env_map = &env_map,
+        });
+        wall_ms[i] = @as(f64, @floatFromInt(std.time.nanoTimestamp() - started)) / std.time.ns_per_ms;
+
+        const reported = std.mem.trim(u8, result.stdout, " \r\n");
+        const wrapper_us = std.fmt.parseInt(i64, reported, 10) catch {
+            logger.err("Run {} did not reach the launch, it printed: {s}{s}", .{ i + 1, result.stdout, result.stderr });
+            return error.BenchmarkRunFailed;
+        };
+        wrapper_ms[i] = @as(f64, @floatFromInt(wrapper_us)) / std.time.us_per_ms;
+    }
+
+    const wall = get_percentiles(wall_ms);
+    const wrapper = get_percentiles(wrapper_ms);
+
+    const stdout = std.io.getStdOut().writer();
+    if (options.json) {
+        try std.json.stringify(.{ .runs = options.runs, .wall_ms = wall, .wrapper_ms = wrapper }, .{}, stdout);
+        try stdout.writeByte('\n');
+    } else {
+        try stdout.print("Startup overhead over {} runs, up to starting the VM (ms):\n\n", .{options.runs});
+        try stdout.print("  {s: <10} {s: >8} {s: >8} {s: >8} {s: >8} {s: >8}\n", .{ "", "min", "p50", "p90", "p99", "max" });
+        try print_row(stdout, "wall", wall);
+        try print_row(stdout, "wrapper", wrapper);
+        try stdout.print("\n`wall` includes starting the process, `wrapper` is measured from inside it.\n", .{});
+    }
+
+    if (options.budget_ms) |budget| {
+        if (wall.p50 > budget) {
+            logger.err("The median startup overhead of {d:.2}ms is over the budget of {d:.2}ms", .{ wall.p50, budget });
+            return error.StartupOverBudget;
+        }
+    }
+}
+
+fn print_row(writer: anytype, label: []const u8, percentiles: Percentiles) !void {
+    try writer.print("  {s: <10} {d:>8.2} {d:>8.2} {d:>8.2} {d:>8.2} {d:>8.2}\n", .{
+        label,
+        percentiles.min,
+        percentiles.p50,
+        percentiles.p90,
+        percentiles.p99,
+        percentiles.max,
+    });
+}
+
+fn get_percentiles(samples: []f64) Percentiles {
+    std.mem.sort(f64, samples, {}, std.sort.asc(f64));
+    return .{
+        .min = samples[0],
+        .p50 = percentile(samples, 50),
+        .p90 = percentile(samples, 90),
+        .p99 = percentile(samples, 99),
+        .max = samples[samples.len - 1],
+    };
+}
+
+// Nearest-rank percentile of sorted samples
+fn percentile(sorted: []const f64, p: usize) f64 {
+    const rank = (p * sorted.len + 99) / 100;
+    return sorted[@max(rank, 1) - 1];
+}
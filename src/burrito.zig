@@ -0,0 +1,384 @@
+/////
+// The steps the wrapper goes through to run a release, for plugins (and other Zig code built into the wrapper) that
+// want to drive them themselves, e.g. installing ahead of time or launching with their own arguments:
+//
+//   const burrito = @import("root").burrito;
+//   const meta = try burrito.get_metadata(allocator);
+//   const install_dir = try burrito.resolve_install_dir(&meta);
+//   _ = try burrito.ensure_installed(&meta, install_dir, .{});
+//   try burrito.launch(&meta, install_dir, args, .foreground);
+//
+// Each step logs, reports progress, telemetry and failures exactly like a regular run of the binary does.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const app_bundle = @import("app_bundle.zig");
+const components = @import("components.zig");
+const defender = @import("defender.zig");
+const elevate = @import("elevate.zig");
+const exit_codes = @import("exit_codes.zig");
+const i18n = @import("i18n.zig");
+const install = @import("install.zig");
+const launcher = @import("erlang_launcher.zig");
+const logger = @import("logger.zig");
+const mac_policy = @import("mac_policy.zig");
+const maint = @import("maintenance.zig");
+const metadata = @import("metadata.zig");
+const progress = @import("progress.zig");
+const quarantine = @import("quarantine.zig");
+const relocate = @import("relocate.zig");
+const rosetta = @import("rosetta.zig");
+const runtime_config = @import("runtime_config.zig");
+const shared_libs = @import("shared_libs.zig");
+const shutil = @import("shutil.zig");
+const support_bundle = @import("support_bundle.zig");
+const tamper = @import("tamper.zig");
+const telemetry = @import("telemetry.zig");
+const termux = @import("termux.zig");
+const trace = @import("trace.zig");
+const uninstall_entry = @import("uninstall_entry.zig");
+const wrapper = @import("wrapper.zig");
+const wsl = @import("wsl.zig");
+
+const fs = std.fs;
+const log = std.log;
+
+pub const MetaStruct = metadata.MetaStruct;
+pub const LaunchMode = launcher.LaunchMode;
+
+// Install dir suffix, `.burrito` unless the release picked its own
+const install_suffix = build_options.INSTALL_SUFFIX;
+
+// Where a binary installs by default, picked when it's built with the `install_strategy` option
+pub const InstallStrategy = enum {
+    // The user's data directory, e.g. `~/.local/share/.burrito`
+    user_data,
+    // A directory shared by all users, e.g. `/var/lib/.burrito`, installing there needs admin rights
+    system,
+    // Next to the binary, so the two can be moved or carried around together
+    portable,
+    // A private directory in the temporary directory, which the OS clears out eventually
+    ephemeral,
+};
+
+pub const install_strategy = std.meta.stringToEnum(InstallStrategy, build_options.INSTALL_STRATEGY) orelse
+    @compileError("Unknown install strategy: " ++ build_options.INSTALL_STRATEGY);
+
+var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+var allocator = arena.allocator();
+
+pub const InstallOptions = struct {
+    // Reinstall from scratch even if this version is already installed
+    clean: bool = false,
+};
+
+// The metadata of the release packed into this binary, with the embedded runtime config applied
+pub fn get_metadata(metadata_allocator: std.mem.Allocator) !MetaStruct {
+    const meta = metadata.parse(metadata_allocator, wrapper.RELEASE_METADATA_JSON) orelse return error.InvalidMetadata;
+
+    // The config was checked when the binary was built, this only fails if it was built with an older wrapper
+    var configured = meta;
+    var diagnostic = runtime_config.Diagnostic{};
+    const entries = runtime_config.parse(metadata_allocator, wrapper.RUNTIME_CONFIG, &diagnostic) catch |err| return config_ignored(meta, diagnostic, err);
+    runtime_config.apply(&configured, entries, &diagnostic) catch |err| return config_ignored(meta, diagnostic, err);
+    return configured;
+}
+
+fn config_ignored(meta: MetaStruct, diagnostic: runtime_config.Diagnostic, err: anyerror) MetaStruct {
+    logger.warn("Ignoring the embedded runtime config, line {}: {s} ({!})", .{ diagnostic.line, diagnostic.message, err });
+    return meta;
+}
+
+fn get_system_data_dir(dir_allocator: std.mem.Allocator) []const u8 {
+    return switch (builtin.os.tag) {
+        .windows => std.process.getEnvVarOwned(dir_allocator, "ProgramData") catch "C:\\ProgramData",
+        .macos => "/Library/Application Support",
+        else => "/var/lib",
+    };
+}
+
+// The temp dir is shared by every user, who could plant an install there for us to run. Ephemeral installs go to
+// the per-user runtime directory, or a private `<temp dir>/<app>-<uid>`. Windows' temp dir is per-user already.
+fn get_ephemeral_dir(dir_allocator: std.mem.Allocator) ![]const u8 {
+    if (builtin.os.tag == .windows) {
+        return shutil.get_temp_dir(dir_allocator);
+    }
+
+    const uid = switch (builtin.os.tag) {
+        .linux => std.os.linux.getuid(),
+        else => std.c.getuid(),
+    };
+
+    if (std.process.getEnvVarOwned(dir_allocator, "XDG_RUNTIME_DIR")) |runtime_dir| {
+        if (fs.path.isAbsolute(runtime_dir) and is_private_dir(runtime_dir, uid)) {
+            return runtime_dir;
+        }
+    } else |_| {}
+
+    const private_dir = try std.fmt.allocPrint(dir_allocator, "{s}/{s}-{}", .{ shutil.get_temp_dir(dir_allocator), build_options.RELEASE_NAME, uid });
+    std.os.mkdir(private_dir, 0o700) catch |err| switch (err) {
+        error.PathAlreadyExists => {},
+        else => return err,
+    };
+    if (!is_private_dir(private_dir, uid)) {
+        log.err("{s} must be a directory owned by the current user, and only accessible by them", .{private_dir});
+        return error.UnsafeTempDir;
+    }
+    return private_dir;
+}
+
+// A real directory (not a link to one) owned by `uid`, which nobody else can access
+fn is_private_dir(path: []const u8, uid: std.os.uid_t) bool {
+    const stat = std.os.fstatat(std.os.AT.FDCWD, path, std.os.AT.SYMLINK_NOFOLLOW) catch return false;
+    return std.os.S.ISDIR(stat.mode) and stat.uid == uid and stat.mode & 0o077 == 0;
+}
+
+fn get_base_install_dir(meta: *const MetaStruct) ![]const u8 {
+    // If we have a override for the install path, use that, otherwise, continue to return
+    // the standard install path
+    const upper_name = try std.ascii.allocUpperString(allocator, build_options.RELEASE_NAME);
+    const env_install_dir_name = try std.fmt.allocPrint(allocator, "{s}_INSTALL_DIR", .{upper_name});
+
+    if (std.process.getEnvVarOwned(allocator, env_install_dir_name)) |new_path| {
+        logger.info("Install path is being overriden using `{s}`", .{env_install_dir_name});
+        logger.info("New install path is: {s}", .{new_path});
+        if (termux.get_prefix() != null and termux.is_shared_storage(new_path)) {
+            logger.warn("Android mounts shared storage as noexec, the application will not be able to run from {s}", .{new_path});
+        }
+        if (wsl.is_wsl() and wsl.is_windows_drive(allocator, new_path)) {
+            logger.warn("{s} is on a Windows drive, installing and running from there under WSL is slow and may fail", .{new_path});
+        }
+        return try fs.path.join(allocator, &[_][]const u8{ new_path, install_suffix });
+    } else |err| switch (err) {
+        error.InvalidUtf8 => {},
+        error.EnvironmentVariableNotFound => {},
+        error.OutOfMemory => {},
+    }
+
+    // An install moved with `--burrito relocate` leaves a marker pointing at its new root
+    if (relocate.read_marker(allocator)) |root| {
+        log.debug("Install was relocated to: {s}", .{root});
+        return try relocate.get_base_dir(allocator, root);
+    }
+
+    switch (install_strategy) {
+        .user_data => {},
+        .system => return try fs.path.join(allocator, &[_][]const u8{ get_system_data_dir(allocator), install_suffix }),
+        .portable => {
+            const exe_dir = fs.selfExeDirPathAlloc(allocator) catch {
+                install_dir_error();
+                return "";
+            };
+            return try fs.path.join(allocator, &[_][]const u8{ exe_dir, install_suffix });
+        },
+        .ephemeral => {
+            const temp_dir = get_ephemeral_dir(allocator) catch |err| {
+                log.err("No private temporary directory to install into: {!}", .{err});
+                install_dir_error();
+                return "";
+            };
+            return try fs.path.join(allocator, &[_][]const u8{ temp_dir, install_suffix });
+        },
+    }
+
+    // A Mac app keeps its files under its own identifier, like any other app
+    if (app_bundle.get()) |bundle| {
+        return app_bundle.get_data_dir(allocator, bundle) catch {
+            install_dir_error();
+            return "";
+        };
+    }
+
+    // Termux keeps everything under its own prefix
+    if (termux.get_prefix()) |prefix| {
+        const termux_base = try termux.get_base_dir(allocator, prefix);
+        return try fs.path.join(allocator, &[_][]const u8{ termux_base, install_suffix });
+    }
+
+    // Hardened systems may deny executing from the home directory, a release can name a location the policy allows
+    if (meta.policy_install_dir) |policy_dir| {
+        if (mac_policy.get_active()) |policy| {
+            log.debug("{s} is enforcing, installing under {s}", .{ @tagName(policy), policy_dir });
+            return try fs.path.join(allocator, &[_][]const u8{ policy_dir, install_suffix });
+        }
+    }
+
+    const app_dir = fs.getAppDataDir(allocator, install_suffix) catch {
+        install_dir_error();
+        return "";
+    };
+
+    // Under WSL, `XDG_DATA_HOME` may point at a Windows drive, stay on the Linux file system if we can
+    if (wsl.is_wsl() and wsl.is_windows_drive(allocator, app_dir)) {
+        if (wsl.get_linux_data_dir(allocator)) |data_dir| {
+            log.debug("Data directory {s} is on a Windows drive, installing under {s} instead", .{ app_dir, data_dir });
+            return try fs.path.join(allocator, &[_][]const u8{ data_dir, install_suffix });
+        }
+        logger.warn("{s} is on a Windows drive, installing and running from there under WSL is slow and may fail", .{app_dir});
+    }
+
+    return app_dir;
+}
+
+// Where this release is installed: under `<APP>_INSTALL_DIR`, a relocated root, the directory of the install strategy
+// the binary was built with, or for the default strategy the app bundle's data directory, the Termux prefix, the
+// release's `policy_install_dir` or the user's data directory, in that order. The base directory is created if
+// needed. Exits with instructions for the user if no usable directory can be found.
+pub fn resolve_install_dir(meta: *const MetaStruct) ![]const u8 {
+    // Combine the hash of the payload and a base dir to get a safe install directory
+    const base_install_path = try get_base_install_dir(meta);
+
+    // Parse the ERTS version and app version from the metadata JSON string
+    const dir_name = try install.get_dir_name(allocator, meta);
+
+    // Ensure that base directory is created, on Windows an elevated install can still create it later
+    std.fs.cwd().makePath(base_install_path) catch |err| {
+        if (builtin.os.tag != .windows or err != error.AccessDenied) {
+            install_dir_error();
+            return "";
+        }
+    };
+
+    // Construct the full app install path
+    const name = fs.path.join(allocator, &[_][]const u8{ base_install_path, dir_name }) catch {
+        install_dir_error();
+        return "";
+    };
+
+    return name;
+}
+
+fn install_dir_error() void {
+    const upper_name = std.ascii.allocUpperString(allocator, build_options.RELEASE_NAME) catch {
+        return;
+    };
+    const env_install_dir_name = std.fmt.allocPrint(allocator, "{s}_INSTALL_DIR", .{upper_name}) catch {
+        return;
+    };
+
+    logger.err("{s}", .{i18n.text(.install_dir_default_failed)});
+    logger.err(
+        "Please override the default {s} install directory using the `{s}` environment variable.",
+        .{ build_options.RELEASE_NAME, env_install_dir_name },
+    );
+    logger.err("On Linux or MacOS you can run the command: `export {s}=/some/other/path`", .{env_install_dir_name});
+    logger.err("On Windows you can use: `SET {s}=D:\\some\\other\\path`", .{env_install_dir_name});
+    logger.report_failure(error.InstallDirUnavailable, .{ .env_var = env_install_dir_name });
+    exit_codes.exit_with(error.InstallDirUnavailable);
+}
+
+// Installs the payload into `install_dir` unless this exact payload is already installed there, returns whether it
+// installed. An existing install is checked for tampering instead (see the `tamper_check` option).
+pub fn ensure_installed(meta: *const MetaStruct, install_dir: []const u8, options: InstallOptions) !bool {
+    const metadata_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, "_metadata.json" });
+
+    // If the metadata file exists, don't install again.
+    // Launching an installed app is the common case, and is kept free of anything only an install needs.
+    var needs_install: bool = false;
+    std.fs.accessAbsolute(metadata_path, .{}) catch |err| {
+        if (err == error.FileNotFound) {
+            needs_install = true;
+        } else {
+            log.err("We failed to open the destination directory with an unexpected error: {!}", .{err});
+            return err;
+        }
+    };
+
+    // Unless it was installed from a different payload of the same version, e.g. a hotfix rebuild
+    var payload_changed = false;
+    if (!needs_install and !install.is_payload_current(allocator, install_dir, build_options.PAYLOAD_SHA256)) {
+        logger.info("{s}", .{i18n.format(.reinstalling, &.{meta.app_version})});
+        needs_install = true;
+        payload_changed = true;
+    }
+
+    // If we need an install, install the payload onto the target machine
+    if (needs_install or options.clean) {
+        logger.set_phase("install");
+
+        // Only mentioned on install, not on every launch
+        rosetta.warn_if_translated();
+
+        const install_started = std.time.milliTimestamp();
+        progress.phase_started("install");
+
+        // Ensure the destination directory is created, unless only an elevated copy of ourselves can do that
+        const needs_elevation = elevate.needs_elevation(allocator, install_dir);
+        if (!needs_elevation) {
+            try std.fs.cwd().makePath(install_dir);
+        }
+
+        // A rebuilt payload of the same version mostly has the same files, only the ones that differ are rewritten
+        const install_result = if (needs_elevation) blk: {
+            // The elevated copy also cleans up older versions, we're not allowed to
+            logger.info("{s}", .{i18n.format(.elevation_requested, &.{install_dir})});
+            break :blk elevate.run_elevated_install(allocator, install_dir);
+        } else wrapper.install_payload(meta, install_dir, if (payload_changed) .repair else if (options.clean and !needs_install) .clean else .overwrite);
+
+        install_result catch |err| {
+            progress.failed("install", err);
+            logger.report_failure(err, .{ .install_dir = install_dir, .elevated = needs_elevation });
+            telemetry.report_install(allocator, meta, err, std.time.milliTimestamp() - install_started);
+            support_bundle.offer(allocator, install_dir, meta, err);
+            return err;
+        };
+
+        const install_ms = std.time.milliTimestamp() - install_started;
+        progress.phase_finished("install");
+        telemetry.report_install(allocator, meta, null, install_ms);
+        logger.event("install_finished", .{ .install_dir = install_dir, .duration_ms = install_ms });
+        defender.hint_if_slow(allocator, fs.path.dirname(install_dir).?, install_ms);
+
+        // Catch missing libraries now, rather than with a loader error from deep inside the VM
+        if (!meta.static_erts) {
+            shared_libs.check_install(allocator, install_dir, meta);
+        }
+
+        if (meta.windows_uninstall_entry) {
+            uninstall_entry.register(allocator, install_dir, meta);
+        }
+
+        // Older versions only pile up when a new one is installed, the elevated copy cleans up itself
+        if (!needs_elevation) {
+            try maint.do_clean_old_versions(fs.path.dirname(install_dir).?, install_dir, meta.keep_previous_version);
+        }
+
+        return true;
+    }
+
+    log.debug("Skipping archive unpacking, this machine already has the app installed!", .{});
+    try tamper.check(allocator, install_dir, meta);
+    return false;
+}
+
+// Starts the VM from `install_dir`. In the foreground mode this replaces the current process (or waits for the VM
+// on Windows, and when supervising it), so it only returns on failure.
+pub fn launch(meta: *const MetaStruct, install_dir: []const u8, args: []const []const u8, mode: LaunchMode) !void {
+    components.install_on_launch(allocator, install_dir, meta);
+
+    // Get Env
+    const env_span = trace.begin("build environment");
+    var env_map = try wrapper.build_env_map(install_dir, meta, true);
+    env_span.end();
+
+    logger.set_phase("launch");
+    log.debug("Launching erlang...", .{});
+    app_bundle.enter_working_dir(allocator);
+
+    logger.event("launch", .{ .install_dir = install_dir, .mode = @tagName(mode) });
+    progress.phase_started("launch");
+
+    launcher.launch(install_dir, &env_map, meta, args, mode) catch |err| {
+        logger.err("Failed to launch the Erlang VM: {!}", .{err});
+        logger.event("launch_failed", .{ .install_dir = install_dir, .error_code = @errorName(err) });
+        logger.report_failure(err, .{ .install_dir = install_dir, .mode = @tagName(mode) });
+        progress.failed("launch", err);
+        quarantine.explain_launch_error(err, install_dir);
+        mac_policy.explain_launch_error(err, install_dir);
+        support_bundle.offer(allocator, install_dir, meta, err);
+        return err;
+    };
+}
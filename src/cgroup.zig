@@ -0,0 +1,92 @@
+/////
+// Reads the CPU quota applied to us through Linux cgroups (v2 or v1), so the VM can be started with a matching
+// number of schedulers instead of one per host core.
+/////
+
+const std = @import("std");
+
+const MAX_READ_SIZE = 4096;
+
+// Returns the number of CPUs our cgroup quota allows, or null if there is no quota (or we can't tell)
+pub fn cpu_limit(allocator: std.mem.Allocator) ?u32 {
+    const quota = read_v2_quota(allocator) orelse read_v1_quota(allocator) orelse return null;
+    if (quota.period == 0) {
+        return null;
+    }
+
+    // Round up, a quota of 1.5 CPUs still wants 2 schedulers
+    const limit = (quota.quota + quota.period - 1) / quota.period;
+    return @intCast(@max(limit, 1));
+}
+
+const Quota = struct {
+    quota: u64,
+    period: u64,
+};
+
+fn read_v2_quota(allocator: std.mem.Allocator) ?Quota {
+    // Our own cgroup first, inside a container with a cgroup namespace this is the root anyway
+    const cgroup_path = own_v2_cgroup(allocator) orelse "/";
+    const own_cpu_max = std.fmt.allocPrint(allocator, "/sys/fs/cgroup{s}/cpu.max", .{std.mem.trimRight(u8, cgroup_path, "/")}) catch return null;
+
+    const content = read_file(allocator, own_cpu_max) orelse read_file(allocator, "/sys/fs/cgroup/cpu.max") orelse return null;
+
+    // Format: "<quota|max> <period>"
+    var parts = std.mem.tokenizeAny(u8, content, " \n");
+    const quota_str = parts.next() orelse return null;
+    const period_str = parts.next() orelse return null;
+
+    if (std.mem.eql(u8, quota_str, "max")) {
+        return null;
+    }
+
+    return Quota{
+        .quota = std.fmt.parseInt(u64, quota_str, 10) catch return null,
+        .period = std.fmt.parseInt(u64, period_str, 10) catch return null,
+    };
+}
+
+fn own_v2_cgroup(allocator: std.mem.Allocator) ?[]const u8 {
+    const content = read_file(allocator, "/proc/self/cgroup") orelse return null;
+
+    // The unified hierarchy line looks like "0::/some/path"
+    var lines = std.mem.tokenizeScalar(u8, content, '\n');
+    while (lines.next()) |line| {
+        if (std.mem.startsWith(u8, line, "0::")) {
+            return line[3..];
+        }
+    }
+
+    return null;
+}
+
+fn read_v1_quota(allocator: std.mem.Allocator) ?Quota {
+    const dirs = [_][]const u8{ "/sys/fs/cgroup/cpu", "/sys/fs/cgroup/cpu,cpuacct" };
+
+    for (dirs) |dir| {
+        const quota_path = std.fmt.allocPrint(allocator, "{s}/cpu.cfs_quota_us", .{dir}) catch return null;
+        const period_path = std.fmt.allocPrint(allocator, "{s}/cpu.cfs_period_us", .{dir}) catch return null;
+
+        const quota_content = read_file(allocator, quota_path) orelse continue;
+        const period_content = read_file(allocator, period_path) orelse continue;
+
+        // A quota of -1 means there is no limit
+        const quota = std.fmt.parseInt(i64, std.mem.trim(u8, quota_content, " \n"), 10) catch continue;
+        if (quota <= 0) {
+            return null;
+        }
+
+        return Quota{
+            .quota = @intCast(quota),
+            .period = std.fmt.parseInt(u64, std.mem.trim(u8, period_content, " \n"), 10) catch continue,
+        };
+    }
+
+    return null;
+}
+
+fn read_file(allocator: std.mem.Allocator, path: []const u8) ?[]const u8 {
+    const file = std.fs.openFileAbsolute(path, .{}) catch return null;
+    defer file.close();
+    return file.readToEndAlloc(allocator, MAX_READ_SIZE) catch null;
+}
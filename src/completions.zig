@@ -0,0 +1,118 @@
+/////
+// Generates shell completion scripts for the wrapper binary. The first argument completes to `--burrito` and any
+// words the application listed in its `completions` option, the second one (after `--burrito`) to the
+// maintenance commands.
+/////
+
+const std = @import("std");
+
+pub const Shell = enum {
+    bash,
+    zsh,
+    fish,
+    powershell,
+};
+
+pub const Spec = struct {
+    // The name the binary is invoked as
+    binary_name: []const u8,
+    maintenance_commands: []const []const u8,
+    app_words: []const []const u8,
+};
+
+pub fn parse_shell(name: []const u8) ?Shell {
+    return std.meta.stringToEnum(Shell, name);
+}
+
+pub fn write(writer: anytype, shell: Shell, spec: Spec) !void {
+    switch (shell) {
+        .bash => try write_bash(writer, spec),
+        .zsh => try write_zsh(writer, spec),
+        .fish => try write_fish(writer, spec),
+        .powershell => try write_powershell(writer, spec),
+    }
+}
+
+fn write_bash(writer: anytype, spec: Spec) !void {
+    try writer.writeAll("_");
+    try write_identifier(writer, spec.binary_name);
+    try writer.writeAll("_completions() {\n");
+    try writer.writeAll("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    try writer.writeAll("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+    try writer.writeAll("        COMPREPLY=($(compgen -W \"--burrito");
+    try write_words(writer, spec.app_words, " ", "");
+    try writer.writeAll("\" -- \"$cur\"))\n");
+    try writer.writeAll("    elif [ \"$COMP_CWORD\" -eq 2 ] && [ \"${COMP_WORDS[1]}\" = \"--burrito\" ]; then\n");
+    try writer.writeAll("        COMPREPLY=($(compgen -W \"");
+    try write_words(writer, spec.maintenance_commands, "", " ");
+    try writer.writeAll("\" -- \"$cur\"))\n");
+    try writer.writeAll("    fi\n}\n");
+    try writer.writeAll("complete -F _");
+    try write_identifier(writer, spec.binary_name);
+    try writer.print("_completions {s}\n", .{spec.binary_name});
+}
+
+fn write_zsh(writer: anytype, spec: Spec) !void {
+    try writer.print("#compdef {s}\n\n", .{spec.binary_name});
+    try writer.writeAll("_");
+    try write_identifier(writer, spec.binary_name);
+    try writer.writeAll("() {\n");
+    try writer.writeAll("    if (( CURRENT == 2 )); then\n");
+    try writer.writeAll("        compadd -- --burrito");
+    try write_words(writer, spec.app_words, " ", "");
+    try writer.writeAll("\n");
+    try writer.writeAll("    elif (( CURRENT == 3 )) && [[ ${words[2]} == --burrito ]]; then\n");
+    try writer.writeAll("        compadd --");
+    try write_words(writer, spec.maintenance_commands, " ", "");
+    try writer.writeAll("\n    fi\n}\n\n");
+    try writer.writeAll("compdef _");
+    try write_identifier(writer, spec.binary_name);
+    try writer.print(" {s}\n", .{spec.binary_name});
+}
+
+fn write_fish(writer: anytype, spec: Spec) !void {
+    try writer.print("complete -c {s} -f -n 'test (count (commandline -opc)) -eq 1' -a '--burrito", .{spec.binary_name});
+    try write_words(writer, spec.app_words, " ", "");
+    try writer.writeAll("'\n");
+    try writer.print("complete -c {s} -f -n 'test (count (commandline -opc)) -eq 2; and test (commandline -opc)[2] = --burrito' -a '", .{spec.binary_name});
+    try write_words(writer, spec.maintenance_commands, "", " ");
+    try writer.writeAll("'\n");
+}
+
+fn write_powershell(writer: anytype, spec: Spec) !void {
+    try writer.print("Register-ArgumentCompleter -Native -CommandName '{s}', '{s}.exe' -ScriptBlock {{\n", .{ spec.binary_name, spec.binary_name });
+    try writer.writeAll("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    try writer.writeAll("    $elements = $commandAst.CommandElements\n");
+    try writer.writeAll("    $position = if ($wordToComplete) { $elements.Count - 1 } else { $elements.Count }\n");
+    try writer.writeAll("    $candidates = @()\n");
+    try writer.writeAll("    if ($position -eq 1) {\n");
+    try writer.writeAll("        $candidates = @('--burrito'");
+    try write_words(writer, spec.app_words, ", '", "'");
+    try writer.writeAll(")\n");
+    try writer.writeAll("    } elseif ($position -eq 2 -and $elements[1].ToString() -eq '--burrito') {\n");
+    try writer.writeAll("        $candidates = @(");
+    for (spec.maintenance_commands, 0..) |command, i| {
+        if (i > 0) {
+            try writer.writeAll(", ");
+        }
+        try writer.print("'{s}'", .{command});
+    }
+    try writer.writeAll(")\n    }\n");
+    try writer.writeAll("    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n");
+    try writer.writeAll("        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n");
+    try writer.writeAll("    }\n}\n");
+}
+
+// Writes each word wrapped in `prefix` and `suffix`, trailing separators are trimmed off by the shells
+fn write_words(writer: anytype, words: []const []const u8, prefix: []const u8, suffix: []const u8) !void {
+    for (words) |word| {
+        try writer.print("{s}{s}{s}", .{ prefix, word, suffix });
+    }
+}
+
+// Shell function names can't contain dashes or dots, binary names often do
+fn write_identifier(writer: anytype, name: []const u8) !void {
+    for (name) |char| {
+        try writer.writeByte(if (std.ascii.isAlphanumeric(char)) char else '_');
+    }
+}
@@ -0,0 +1,144 @@
+/////
+// Optional components are parts of the release left out of the payload (see the `optional_components` option), e.g.
+// large ML models only some users need, or the documentation and locale packs (see the `optional_packs` option).
+// Each is packed into its own compressed FOILZ archive when the wrapper is built, which is published at the
+// component's URL or shipped next to the binary. The wrapper only knows the URL and the archive's hash, and fetches
+// a component into the install directory with `--burrito fetch <component>`, or on launch if it's marked so.
+/////
+
+const std = @import("std");
+
+const foilz = @import("archiver.zig");
+const logger = @import("logger.zig");
+const progress = @import("progress.zig");
+const quarantine = @import("quarantine.zig");
+const update = @import("update.zig");
+const wrapper = @import("wrapper.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const mem = std.mem;
+const Sha256 = std.crypto.hash.sha2.Sha256;
+
+// Installed components are recorded here, one file per component holding the hash of the archive it came from
+const MARKER_DIR = "_components";
+
+pub const Fetch = enum {
+    // Only with `--burrito fetch`, e.g. run by the application when it needs the component
+    on_demand,
+    // Before the VM starts, if it's missing
+    on_launch,
+};
+
+// One entry of src/components.json, written by build.zig
+pub const Component = struct {
+    name: []const u8,
+    // Empty for packs installed from a local file only, with `--burrito fetch <component> --from <file>`
+    url: []const u8 = "",
+    fetch: Fetch = .on_demand,
+    // SHA-256 of the compressed archive
+    sha256: []const u8,
+    // The size of the uncompressed archive, and the files and bytes it holds
+    size: u64,
+    files: u64,
+    bytes: u64,
+};
+
+pub fn get_all(allocator: mem.Allocator) ![]const Component {
+    const parsed = try std.json.parseFromSlice([]const Component, allocator, wrapper.COMPONENTS_JSON, .{ .ignore_unknown_fields = true });
+    return parsed.value;
+}
+
+pub fn find(allocator: mem.Allocator, name: []const u8) !?Component {
+    for (try get_all(allocator)) |component| {
+        if (mem.eql(u8, component.name, name)) return component;
+    }
+    return null;
+}
+
+fn get_marker_path(allocator: mem.Allocator, install_dir: []const u8, component: *const Component) ![]const u8 {
+    return try fs.path.join(allocator, &[_][]const u8{ install_dir, MARKER_DIR, component.name });
+}
+
+// Whether this exact version of the component is installed, a rebuilt component counts as missing
+pub fn is_installed(allocator: mem.Allocator, install_dir: []const u8, component: *const Component) bool {
+    const marker_path = get_marker_path(allocator, install_dir, component) catch return false;
+    const installed_hash = fs.cwd().readFileAlloc(allocator, marker_path, 256) catch return false;
+    return mem.eql(u8, mem.trim(u8, installed_hash, " \r\n"), component.sha256);
+}
+
+// Downloads, or reads from the `from` file, verifies and unpacks `component` into the install directory
+pub fn install(allocator: mem.Allocator, install_dir: []const u8, meta: *const MetaStruct, component: *const Component, from: ?[]const u8) !void {
+    // Compression never adds much to what the archive holds, anything far larger is not the component
+    const max_size: usize = @intCast(component.size + 1024 * 1024);
+
+    const archive = if (from) |path| blk: {
+        logger.info("Installing {s} from {s}...", .{ component.name, path });
+        progress.phase_started("fetch");
+        break :blk try fs.cwd().readFileAlloc(allocator, path, max_size);
+    } else blk: {
+        if (component.url.len == 0) {
+            logger.err("{s} has no download URL, install it from its file with `--burrito fetch {s} --from <file>`", .{ component.name, component.name });
+            return error.NoComponentUrl;
+        }
+        logger.info("Downloading {s} from {s}...", .{ component.name, component.url });
+        progress.phase_started("fetch");
+        break :blk try update.download(allocator, component.url, max_size);
+    };
+    defer allocator.free(archive);
+
+    var digest: [Sha256.digest_length]u8 = undefined;
+    Sha256.hash(archive, &digest, .{});
+    const hex = try std.fmt.allocPrint(allocator, "{s}", .{std.fmt.fmtSliceHexLower(&digest)});
+    if (!mem.eql(u8, hex, component.sha256)) {
+        logger.err("The archive of {s} does not match the component this binary was built with", .{component.name});
+        progress.failed("fetch", error.ComponentMismatch);
+        return error.ComponentMismatch;
+    }
+
+    try foilz.unpack_payload(allocator, &[_][]const u8{archive}, install_dir, .{
+        .trusted_modes = meta.trusted_modes,
+        .max_size = component.size,
+        .counter = .{ .phase = "fetch", .total_files = component.files, .total_bytes = component.bytes },
+    });
+    if (meta.clear_quarantine) {
+        quarantine.clear(allocator, install_dir);
+    }
+
+    // Last, a component without it is fetched again
+    const marker_path = try get_marker_path(allocator, install_dir, component);
+    try fs.cwd().makePath(fs.path.dirname(marker_path).?);
+    try fs.cwd().writeFile(marker_path, component.sha256);
+
+    progress.phase_finished("fetch");
+    logger.info("Installed {s} ({} files)", .{ component.name, component.files });
+}
+
+// Fetches the missing components marked `fetch: :on_launch`, the application still starts if that fails
+pub fn install_on_launch(allocator: mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) void {
+    const all = get_all(allocator) catch return;
+    for (all) |*component| {
+        if (component.fetch != .on_launch or is_installed(allocator, install_dir, component)) continue;
+        install(allocator, install_dir, meta, component, null) catch |err| {
+            logger.warn("Could not fetch {s}, starting without it: {!}", .{ component.name, err });
+        };
+    }
+}
+
+// Tells the VM which components are installed, and how to fetch the others
+pub fn apply_env(allocator: mem.Allocator, env_map: *std.process.EnvMap, install_dir: []const u8) !void {
+    const all = try get_all(allocator);
+    if (all.len == 0) {
+        return;
+    }
+
+    var installed = std.ArrayList(u8).init(allocator);
+    for (all) |*component| {
+        if (!is_installed(allocator, install_dir, component)) continue;
+        if (installed.items.len > 0) try installed.append(',');
+        try installed.appendSlice(component.name);
+    }
+
+    try env_map.put("BURRITO_COMPONENTS", installed.items);
+    try env_map.put("BURRITO_BIN_PATH", try fs.selfExePathAlloc(allocator));
+}
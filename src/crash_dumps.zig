@@ -0,0 +1,100 @@
+/////
+// Keeps `erl_crash.dump` files from slowly filling up the disk of long running deployments.
+// The VM is pointed at a `crash_dumps` directory inside the install dir, and on every launch we apply a retention
+// policy to it: optionally compress old dumps, keep only the newest N, and cap the total size.
+/////
+
+const std = @import("std");
+
+const fs = std.fs;
+const log = std.log;
+
+const gzip = @import("gzip.zig");
+
+pub const Policy = struct {
+    // How many dumps to keep, 0 keeps all of them
+    keep: u32 = 5,
+    // Maximum combined size of the kept dumps in bytes
+    max_bytes: ?u64 = null,
+    // Gzip dumps left over from previous runs
+    compress: bool = false,
+};
+
+const Dump = struct {
+    name: []const u8,
+    size: u64,
+};
+
+pub fn get_dump_dir(allocator: std.mem.Allocator, install_dir: []const u8) ![]const u8 {
+    return try fs.path.join(allocator, &[_][]const u8{ install_dir, "crash_dumps" });
+}
+
+// A fresh, timestamped, file name for the next crash dump
+pub fn get_next_dump_path(allocator: std.mem.Allocator, dump_dir: []const u8) ![]const u8 {
+    const name = try std.fmt.allocPrint(allocator, "erl_crash_{}.dump", .{std.time.milliTimestamp()});
+    return try fs.path.join(allocator, &[_][]const u8{ dump_dir, name });
+}
+
+pub fn apply_policy(allocator: std.mem.Allocator, dump_dir: []const u8, policy: Policy) !void {
+    var dir = try fs.openIterableDirAbsolute(dump_dir, .{});
+    defer dir.close();
+
+    var dumps = std.ArrayList(Dump).init(allocator);
+
+    var itr = dir.iterate();
+    while (try itr.next()) |entry| {
+        if (entry.kind != .file or !std.mem.startsWith(u8, entry.name, "erl_crash_")) {
+            continue;
+        }
+
+        var name = try allocator.dupe(u8, entry.name);
+
+        if (policy.compress and std.mem.endsWith(u8, name, ".dump")) {
+            name = compress_dump(allocator, dump_dir, name) catch |err| blk: {
+                log.warn("Failed to compress crash dump {s}: {!}", .{ name, err });
+                break :blk name;
+            };
+        }
+
+        const stat = try dir.dir.statFile(name);
+        try dumps.append(.{ .name = name, .size = stat.size });
+    }
+
+    // Names embed a timestamp, so sorting them puts the newest dumps first
+    std.mem.sort(Dump, dumps.items, {}, newer_first);
+
+    var total_bytes: u64 = 0;
+    for (dumps.items, 0..) |dump, index| {
+        total_bytes += dump.size;
+
+        const over_count = policy.keep > 0 and index >= policy.keep;
+        const over_size = if (policy.max_bytes) |max| total_bytes > max else false;
+
+        if (over_count or over_size) {
+            log.debug("Removing old crash dump: {s}", .{dump.name});
+            dir.dir.deleteFile(dump.name) catch {};
+        }
+    }
+}
+
+fn compress_dump(allocator: std.mem.Allocator, dump_dir: []const u8, name: []const u8) ![]const u8 {
+    const compressed_name = try std.fmt.allocPrint(allocator, "{s}.gz", .{name});
+    const src_path = try fs.path.join(allocator, &[_][]const u8{ dump_dir, name });
+    const dest_path = try fs.path.join(allocator, &[_][]const u8{ dump_dir, compressed_name });
+
+    try gzip.compress_file(allocator, src_path, dest_path);
+    try fs.deleteFileAbsolute(src_path);
+
+    return compressed_name;
+}
+
+fn newer_first(_: void, a: Dump, b: Dump) bool {
+    // Compare the timestamps numerically, names can differ in length
+    return dump_timestamp(a.name) > dump_timestamp(b.name);
+}
+
+fn dump_timestamp(name: []const u8) i64 {
+    const start = "erl_crash_".len;
+    const end = std.mem.indexOfScalarPos(u8, name, start, '.') orelse name.len;
+    return std.fmt.parseInt(i64, name[start..end], 10) catch 0;
+}
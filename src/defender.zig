@@ -0,0 +1,40 @@
+/////
+// On Windows, the first run is dominated by Microsoft Defender scanning each of the thousands of small files we extract.
+// When an install was slow we suggest excluding the install root from realtime scanning, and
+// `--burrito defender-exclusion` adds the exclusion for the user (after a UAC prompt).
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const elevate = @import("elevate.zig");
+const logger = @import("logger.zig");
+
+// Installs slower than this are worth a hint
+const SLOW_INSTALL_MS = 10 * std.time.ms_per_s;
+
+// The PowerShell command that excludes `base_install_dir` from realtime scanning
+pub fn get_exclusion_command(allocator: std.mem.Allocator, base_install_dir: []const u8) ![]const u8 {
+    // Single quotes are escaped by doubling them in PowerShell strings
+    const quoted = try std.mem.replaceOwned(u8, allocator, base_install_dir, "'", "''");
+    return try std.fmt.allocPrint(allocator, "Add-MpPreference -ExclusionPath '{s}'", .{quoted});
+}
+
+// Prints a hint about the Defender exclusion after a slow install, does nothing on other platforms
+pub fn hint_if_slow(allocator: std.mem.Allocator, base_install_dir: []const u8, elapsed_ms: i64) void {
+    if (builtin.os.tag != .windows or elapsed_ms < SLOW_INSTALL_MS) {
+        return;
+    }
+
+    const command = get_exclusion_command(allocator, base_install_dir) catch return;
+    logger.info("The install took {} seconds, most likely because Microsoft Defender scanned every extracted file.", .{@divTrunc(elapsed_ms, std.time.ms_per_s)});
+    logger.info("Future installs can skip that by excluding {s} from realtime scanning, either by running", .{base_install_dir});
+    logger.info("`--burrito defender-exclusion`, or this command in an administrator PowerShell: {s}", .{command});
+}
+
+// Adds the exclusion through an elevated PowerShell
+pub fn add_exclusion(allocator: std.mem.Allocator, base_install_dir: []const u8) !void {
+    const command = try get_exclusion_command(allocator, base_install_dir);
+    const parameters = try std.fmt.allocPrint(allocator, "-NoProfile -NonInteractive -Command \"{s}\"", .{command});
+    try elevate.run_elevated(allocator, "powershell.exe", parameters);
+}
@@ -0,0 +1,181 @@
+/////
+// A small binary delta format, used to update a binary by only downloading what changed.
+// A delta is gzip compressed, and after decompression looks like:
+//
+//   Magic Header: 'BZDELTA1'
+//   u64 Target Length
+//   Operations, each starting with a one byte tag:
+//     COPY   (0): u64 offset, u64 length   -- copy bytes from the source binary
+//     INSERT (1): u64 length, bytes        -- bytes that are new in the target binary
+//     END    (2)
+//
+// Matches are found with an rsync style rolling checksum over fixed size blocks of the source.
+/////
+
+const std = @import("std");
+
+const gzip = @import("gzip.zig");
+
+const MAGIC = "BZDELTA1";
+const BLOCK_SIZE = 2048;
+
+// Deltas are at most as large as the binary they describe, plus some overhead. Also caps the binary itself
+const MAX_DELTA_SIZE = 1024 * 1024 * 1024;
+
+const Op = enum(u8) {
+    copy = 0,
+    insert = 1,
+    end = 2,
+};
+
+// Rebuilds the target binary from `source` and a gzip compressed delta
+pub fn apply(allocator: std.mem.Allocator, source: []const u8, compressed_delta: []const u8) ![]u8 {
+    var compressed_stream = std.io.fixedBufferStream(compressed_delta);
+    var decompressor = try std.compress.gzip.decompress(allocator, compressed_stream.reader());
+    defer decompressor.deinit();
+    const delta = try decompressor.reader().readAllAlloc(allocator, MAX_DELTA_SIZE);
+    defer allocator.free(delta);
+
+    var stream = std.io.fixedBufferStream(delta);
+    var reader = stream.reader();
+
+    var magic: [MAGIC.len]u8 = undefined;
+    try reader.readNoEof(&magic);
+    if (!std.mem.eql(u8, &magic, MAGIC)) {
+        return error.BadDeltaHeader;
+    }
+
+    const target_len = try reader.readIntLittle(u64);
+    if (target_len > MAX_DELTA_SIZE) {
+        return error.BadDeltaLength;
+    }
+    var target = try std.ArrayList(u8).initCapacity(allocator, @intCast(target_len));
+    errdefer target.deinit();
+
+    while (true) {
+        const op = std.meta.intToEnum(Op, try reader.readByte()) catch return error.BadDeltaOperation;
+        switch (op) {
+            .copy => {
+                const offset = std.math.cast(usize, try reader.readIntLittle(u64)) orelse return error.BadDeltaOperation;
+                const len = std.math.cast(usize, try reader.readIntLittle(u64)) orelse return error.BadDeltaOperation;
+                if (offset > source.len or len > source.len - offset or len > target_len - target.items.len) {
+                    return error.BadDeltaOperation;
+                }
+                try target.appendSlice(source[offset .. offset + len]);
+            },
+            .insert => {
+                const len = std.math.cast(usize, try reader.readIntLittle(u64)) orelse return error.BadDeltaOperation;
+                if (len > delta.len - stream.pos or len > target_len - target.items.len) {
+                    return error.BadDeltaOperation;
+                }
+                try target.appendSlice(delta[stream.pos .. stream.pos + len]);
+                stream.pos += len;
+            },
+            .end => break,
+        }
+    }
+
+    if (target.items.len != target_len) {
+        return error.BadDeltaLength;
+    }
+
+    return target.toOwnedSlice();
+}
+
+// Writes a gzip compressed delta that turns `source` into `target`
+pub fn create(allocator: std.mem.Allocator, source: []const u8, target: []const u8, writer: anytype) !void {
+    var delta = std.ArrayList(u8).init(allocator);
+    defer delta.deinit();
+    var out = delta.writer();
+
+    try out.writeAll(MAGIC);
+    try out.writeIntLittle(u64, target.len);
+
+    // Index every block of the source by its weak checksum, the first block wins on collisions
+    var blocks = std.AutoHashMap(u32, usize).init(allocator);
+    defer blocks.deinit();
+
+    var offset: usize = 0;
+    while (offset + BLOCK_SIZE <= source.len) : (offset += BLOCK_SIZE) {
+        const result = try blocks.getOrPut(Checksum.init(source[offset .. offset + BLOCK_SIZE]).digest());
+        if (!result.found_existing) {
+            result.value_ptr.* = offset;
+        }
+    }
+
+    var literal_start: usize = 0;
+    var position: usize = 0;
+    var checksum: ?Checksum = null;
+
+    while (position + BLOCK_SIZE <= target.len) {
+        const window = target[position .. position + BLOCK_SIZE];
+        if (checksum == null) {
+            checksum = Checksum.init(window);
+        }
+
+        if (blocks.get(checksum.?.digest())) |source_offset| {
+            if (std.mem.eql(u8, source[source_offset .. source_offset + BLOCK_SIZE], window)) {
+                // Grow the match as far as it goes
+                var len: usize = BLOCK_SIZE;
+                while (position + len < target.len and source_offset + len < source.len and target[position + len] == source[source_offset + len]) {
+                    len += 1;
+                }
+
+                try write_insert(out, target[literal_start..position]);
+                try out.writeByte(@intFromEnum(Op.copy));
+                try out.writeIntLittle(u64, source_offset);
+                try out.writeIntLittle(u64, len);
+
+                position += len;
+                literal_start = position;
+                checksum = null;
+                continue;
+            }
+        }
+
+        // No match here, slide the window by one byte
+        if (position + BLOCK_SIZE < target.len) {
+            checksum.?.roll(target[position], target[position + BLOCK_SIZE]);
+        }
+        position += 1;
+    }
+
+    try write_insert(out, target[literal_start..]);
+    try out.writeByte(@intFromEnum(Op.end));
+
+    try gzip.compress(allocator, delta.items, writer);
+}
+
+fn write_insert(out: anytype, bytes: []const u8) !void {
+    if (bytes.len == 0) {
+        return;
+    }
+    try out.writeByte(@intFromEnum(Op.insert));
+    try out.writeIntLittle(u64, bytes.len);
+    try out.writeAll(bytes);
+}
+
+// The rsync weak checksum, cheap to slide along one byte at a time
+const Checksum = struct {
+    a: u32,
+    b: u32,
+
+    fn init(block: []const u8) Checksum {
+        var a: u32 = 0;
+        var b: u32 = 0;
+        for (block, 0..) |byte, i| {
+            a +%= byte;
+            b +%= @as(u32, @intCast(block.len - i)) *% byte;
+        }
+        return .{ .a = a, .b = b };
+    }
+
+    fn roll(self: *Checksum, out_byte: u8, in_byte: u8) void {
+        self.a = self.a -% out_byte +% in_byte;
+        self.b = self.b -% @as(u32, BLOCK_SIZE) *% out_byte +% self.a;
+    }
+
+    fn digest(self: Checksum) u32 {
+        return (self.a & 0xffff) | (self.b << 16);
+    }
+};
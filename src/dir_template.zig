@@ -0,0 +1,137 @@
+/////
+// The name of an install directory comes from a template set at build time with the `install_dir` option, e.g.
+// `{name}_erts-{erts_version}_{version}` (the default) gives `myapp_erts-15.2.1_1.4.0`. Templates are checked when the
+// wrapper is built (see build.zig), so rendering one can't fail at runtime.
+/////
+
+const std = @import("std");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const mem = std.mem;
+
+pub const DEFAULT_TEMPLATE = "{name}_erts-{erts_version}_{version}";
+pub const DEFAULT_SUFFIX = ".burrito";
+
+pub const placeholders = [_][]const u8{ "name", "version", "erts_version", "otp_version", "channel", "target" };
+
+fn get_value(meta: *const MetaStruct, placeholder: []const u8) []const u8 {
+    if (mem.eql(u8, placeholder, "name")) return meta.app_name;
+    if (mem.eql(u8, placeholder, "version")) return meta.app_version;
+    if (mem.eql(u8, placeholder, "erts_version")) return meta.erts_version;
+    if (mem.eql(u8, placeholder, "otp_version")) return meta.otp_version orelse "unknown";
+    if (mem.eql(u8, placeholder, "channel")) return meta.update_channel;
+    if (mem.eql(u8, placeholder, "target")) return meta.target orelse "unknown";
+    unreachable;
+}
+
+const ParseError = error{ UnclosedPlaceholder, UnopenedPlaceholder };
+
+// Splits a template into its literal parts and placeholders
+const Part = union(enum) {
+    literal: []const u8,
+    placeholder: []const u8,
+};
+
+const PartIterator = struct {
+    template: []const u8,
+    pos: usize = 0,
+
+    fn next(self: *PartIterator) ParseError!?Part {
+        if (self.pos >= self.template.len) {
+            return null;
+        }
+
+        const rest = self.template[self.pos..];
+        if (rest[0] == '{') {
+            const end = mem.indexOfScalar(u8, rest, '}') orelse return error.UnclosedPlaceholder;
+            self.pos += end + 1;
+            return .{ .placeholder = rest[1..end] };
+        }
+
+        const end = mem.indexOfAny(u8, rest, "{}") orelse rest.len;
+        if (end < rest.len and rest[end] == '}') {
+            return error.UnopenedPlaceholder;
+        }
+        self.pos += end;
+        return .{ .literal = rest[0..end] };
+    }
+};
+
+// Rejects templates that could give an unusable or ambiguous directory name
+pub fn validate(template: []const u8) !void {
+    if (template.len == 0) {
+        return error.EmptyTemplate;
+    }
+    // Each install is a single directory in the base directory, which `--burrito clean` and rollbacks rely on
+    if (mem.indexOfAny(u8, template, "/\\") != null or mem.eql(u8, template, ".") or mem.eql(u8, template, "..")) {
+        return error.PathSeparatorInTemplate;
+    }
+
+    var has_version = false;
+    var parts = PartIterator{ .template = template };
+    while (try parts.next()) |part| {
+        const placeholder = switch (part) {
+            .placeholder => |placeholder| placeholder,
+            .literal => continue,
+        };
+        for (placeholders) |known| {
+            if (mem.eql(u8, known, placeholder)) break;
+        } else {
+            return error.UnknownPlaceholder;
+        }
+        has_version = has_version or mem.eql(u8, placeholder, "version");
+    }
+
+    // Versions installed side by side need their own directories
+    if (!has_version) {
+        return error.MissingVersionPlaceholder;
+    }
+}
+
+pub fn validate_suffix(suffix: []const u8) !void {
+    if (suffix.len == 0 or mem.indexOfAny(u8, suffix, "/\\") != null or mem.eql(u8, suffix, ".") or mem.eql(u8, suffix, "..")) {
+        return error.InvalidInstallSuffix;
+    }
+}
+
+pub fn render(allocator: mem.Allocator, template: []const u8, meta: *const MetaStruct) ![]const u8 {
+    var name = std.ArrayList(u8).init(allocator);
+    var parts = PartIterator{ .template = template };
+    while (try parts.next()) |part| {
+        try name.appendSlice(switch (part) {
+            .literal => |literal| literal,
+            .placeholder => |placeholder| get_value(meta, placeholder),
+        });
+    }
+    return name.toOwnedSlice();
+}
+
+// Whether `dir_name` could have been rendered for this app, whatever its version, channel or target
+pub fn matches(template: []const u8, meta: *const MetaStruct, dir_name: []const u8) bool {
+    var parts = PartIterator{ .template = template };
+    return matches_from(&parts, meta, dir_name) catch false;
+}
+
+fn matches_from(parts: *PartIterator, meta: *const MetaStruct, text: []const u8) ParseError!bool {
+    const part = (try parts.next()) orelse return text.len == 0;
+    switch (part) {
+        .literal => |literal| {
+            return mem.startsWith(u8, text, literal) and try matches_from(parts, meta, text[literal.len..]);
+        },
+        .placeholder => |placeholder| {
+            if (mem.eql(u8, placeholder, "name")) {
+                return mem.startsWith(u8, text, meta.app_name) and try matches_from(parts, meta, text[meta.app_name.len..]);
+            }
+
+            // Any non-empty value, trying each possible end for the next part
+            const resume_pos = parts.pos;
+            var end: usize = 1;
+            while (end <= text.len) : (end += 1) {
+                parts.pos = resume_pos;
+                if (try matches_from(parts, meta, text[end..])) return true;
+            }
+            return false;
+        },
+    }
+}
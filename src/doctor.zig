@@ -0,0 +1,314 @@
+/////
+// Checks the host for the most common reasons a Burrito binary fails to install or start,
+// and prints a pass/warn/fail line for each one along with a hint on how to fix it.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+const rosetta = @import("rosetta.zig");
+const shared_libs = @import("shared_libs.zig");
+
+const fs = std.fs;
+
+const DEFAULT_EPMD_PORT = 4369;
+
+const install_dir_env = blk: {
+    var upper_name: [build_options.RELEASE_NAME.len]u8 = undefined;
+    for (build_options.RELEASE_NAME, 0..) |char, i| {
+        upper_name[i] = std.ascii.toUpper(char);
+    }
+    break :blk upper_name ++ "_INSTALL_DIR";
+};
+
+// The oldest glibc the precompiled ERTS builds are known to work with
+const MIN_GLIBC = std.SemanticVersion{ .major = 2, .minor = 17, .patch = 0 };
+
+// Shared libraries the VM, or the NIFs commonly shipped with it, load at runtime
+const linux_shared_libs = [_][]const u8{ "libm.so.6", "libgcc_s.so.1", "libstdc++.so.6" };
+
+extern "c" fn gnu_get_libc_version() [*:0]const u8;
+
+// The 64-bit glibc and musl layout of `struct statvfs`
+const StatVfs = extern struct {
+    f_bsize: c_ulong,
+    f_frsize: c_ulong,
+    f_blocks: u64,
+    f_bfree: u64,
+    f_bavail: u64,
+    f_files: u64,
+    f_ffree: u64,
+    f_favail: u64,
+    f_fsid: c_ulong,
+    f_flag: c_ulong,
+    f_namemax: c_ulong,
+    __f_spare: [6]c_int,
+};
+
+extern "c" fn statvfs(path: [*:0]const u8, buf: *StatVfs) c_int;
+
+const Status = enum {
+    pass,
+    warn,
+    fail,
+    skip,
+
+    fn label(self: Status) []const u8 {
+        return switch (self) {
+            .pass => "PASS",
+            .warn => "WARN",
+            .fail => "FAIL",
+            .skip => "SKIP",
+        };
+    }
+};
+
+const Result = struct {
+    status: Status,
+    detail: []const u8,
+    hint: ?[]const u8 = null,
+};
+
+const Check = struct {
+    name: []const u8,
+    run: *const fn (allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) Result,
+};
+
+const checks = [_]Check{
+    .{ .name = "libc", .run = check_libc },
+    .{ .name = "architecture", .run = check_architecture },
+    .{ .name = "shared libraries", .run = check_shared_libs },
+    .{ .name = "install location", .run = check_install_location },
+    .{ .name = "disk space", .run = check_disk_space },
+    .{ .name = "locale", .run = check_locale },
+    .{ .name = "epmd", .run = check_epmd },
+};
+
+// Runs every check and prints the results, returns the number of failed checks
+pub fn run(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) !usize {
+    return report(allocator, install_dir, meta, std.io.getStdOut().writer());
+}
+
+// Runs every check and writes the results to `stdout`, returns the number of failed checks
+pub fn report(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct, stdout: anytype) !usize {
+    var failures: usize = 0;
+
+    try stdout.print("Checking this machine for {s} v{s} ({s})\n\n", .{ meta.app_name, meta.app_version, meta.target orelse @tagName(builtin.os.tag) });
+
+    for (checks) |check| {
+        const result = check.run(allocator, install_dir, meta);
+        try stdout.print("[{s}] {s}: {s}\n", .{ result.status.label(), check.name, result.detail });
+        if (result.hint) |hint| {
+            try stdout.print("       hint: {s}\n", .{hint});
+        }
+
+        if (result.status == .fail) {
+            failures += 1;
+        }
+    }
+
+    try stdout.print("\n{} check(s) failed\n", .{failures});
+    return failures;
+}
+
+fn check_libc(allocator: std.mem.Allocator, _: []const u8, meta: *const MetaStruct) Result {
+    if (builtin.os.tag != .linux) {
+        return .{ .status = .skip, .detail = "only relevant on Linux" };
+    } else if (meta.static_erts) {
+        return .{ .status = .skip, .detail = "the bundled ERTS is statically linked" };
+    } else if (builtin.abi.isMusl()) {
+        return .{ .status = .pass, .detail = "built against musl" };
+    } else {
+        const version_str = std.mem.span(gnu_get_libc_version());
+        const detail = std.fmt.allocPrint(allocator, "glibc {s}", .{version_str}) catch version_str;
+
+        // glibc versions are usually only "major.minor"
+        const full_version = std.fmt.allocPrint(allocator, "{s}.0", .{version_str}) catch version_str;
+        const version = std.SemanticVersion.parse(full_version) catch std.SemanticVersion.parse(version_str) catch {
+            return .{ .status = .warn, .detail = detail, .hint = "could not parse the glibc version" };
+        };
+
+        if (version.order(MIN_GLIBC) == .lt) {
+            return .{ .status = .fail, .detail = detail, .hint = "glibc 2.17 or newer is required, or build a musl target instead" };
+        }
+
+        return .{ .status = .pass, .detail = detail };
+    }
+}
+
+fn check_architecture(_: std.mem.Allocator, _: []const u8, _: *const MetaStruct) Result {
+    if (rosetta.is_translated()) {
+        return .{
+            .status = .warn,
+            .detail = "x86_64 build running under Rosetta 2 on Apple Silicon",
+            .hint = "use the aarch64 build of this application to run natively",
+        };
+    }
+    return .{ .status = .pass, .detail = @tagName(builtin.cpu.arch) };
+}
+
+fn check_shared_libs(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) Result {
+    if (builtin.os.tag != .linux) {
+        return .{ .status = .skip, .detail = "only relevant on Linux" };
+    } else if (meta.static_erts) {
+        return .{ .status = .skip, .detail = "the bundled ERTS is statically linked" };
+    }
+
+    // Once installed, the binaries themselves tell us what they need
+    const metadata_path = fs.path.join(allocator, &[_][]const u8{ install_dir, "_metadata.json" }) catch install_dir;
+    if (fs.accessAbsolute(metadata_path, .{})) {
+        const missing = shared_libs.scan(allocator, install_dir, meta) catch |err| {
+            const detail = std.fmt.allocPrint(allocator, "could not scan the install: {!}", .{err}) catch "could not scan the install";
+            return .{ .status = .warn, .detail = detail };
+        };
+        if (missing.len == 0) {
+            return .{ .status = .pass, .detail = "all found" };
+        }
+
+        var names = std.ArrayList(u8).init(allocator);
+        var hints = std.ArrayList(u8).init(allocator);
+        for (missing, 0..) |lib, i| {
+            if (i > 0) {
+                names.appendSlice(", ") catch {};
+            }
+            names.writer().print("{s} (needed by {s})", .{ lib.library, lib.needed_by }) catch {};
+            if (lib.packages) |packages| {
+                if (hints.items.len > 0) {
+                    hints.appendSlice("; ") catch {};
+                }
+                hints.writer().print("{s}: {s}", .{ lib.library, packages }) catch {};
+            }
+        }
+
+        const detail = std.fmt.allocPrint(allocator, "missing {s}", .{names.items}) catch "missing libraries";
+        const hint = if (hints.items.len > 0) hints.items else "install the packages providing these libraries";
+        return .{ .status = .fail, .detail = detail, .hint = hint };
+    } else |_| {}
+
+    // Not installed yet, check for the libraries every glibc build needs
+    if (builtin.abi.isMusl()) {
+        return .{ .status = .skip, .detail = "not installed yet" };
+    }
+
+    var missing = std.ArrayList(u8).init(allocator);
+    for (linux_shared_libs) |lib_name| {
+        var lib = std.DynLib.open(lib_name) catch {
+            if (missing.items.len > 0) {
+                missing.appendSlice(", ") catch {};
+            }
+            missing.appendSlice(lib_name) catch {};
+            continue;
+        };
+        lib.close();
+    }
+
+    if (missing.items.len > 0) {
+        const detail = std.fmt.allocPrint(allocator, "missing {s}", .{missing.items}) catch "missing libraries";
+        return .{ .status = .fail, .detail = detail, .hint = "install your distribution's libgcc and libstdc++ packages" };
+    }
+
+    return .{ .status = .pass, .detail = "all found" };
+}
+
+fn check_install_location(allocator: std.mem.Allocator, install_dir: []const u8, _: *const MetaStruct) Result {
+    const base_dir = fs.path.dirname(install_dir) orelse install_dir;
+    const hint = "point the install somewhere writable and executable with " ++ install_dir_env;
+
+    fs.cwd().makePath(base_dir) catch {
+        const detail = std.fmt.allocPrint(allocator, "cannot create {s}", .{base_dir}) catch base_dir;
+        return .{ .status = .fail, .detail = detail, .hint = hint };
+    };
+
+    const probe_name = if (builtin.os.tag == .windows) ".burrito_doctor.bat" else ".burrito_doctor.sh";
+    const probe_path = fs.path.join(allocator, &[_][]const u8{ base_dir, probe_name }) catch return .{ .status = .skip, .detail = "out of memory" };
+    defer fs.deleteFileAbsolute(probe_path) catch {};
+
+    const probe_script = if (builtin.os.tag == .windows) "@exit 0\r\n" else "#!/bin/sh\nexit 0\n";
+    const probe = fs.createFileAbsolute(probe_path, .{ .truncate = true, .mode = 0o755 }) catch {
+        const detail = std.fmt.allocPrint(allocator, "{s} is not writable", .{base_dir}) catch base_dir;
+        return .{ .status = .fail, .detail = detail, .hint = hint };
+    };
+    probe.writeAll(probe_script) catch {};
+    probe.close();
+
+    // Actually running something is the only reliable way to spot `noexec` mounts and similar policies
+    const result = std.ChildProcess.exec(.{ .allocator = allocator, .argv = &[_][]const u8{probe_path} }) catch {
+        const detail = std.fmt.allocPrint(allocator, "{s} is writable, but programs in it cannot be executed", .{base_dir}) catch base_dir;
+        return .{ .status = .fail, .detail = detail, .hint = hint };
+    };
+
+    if (result.term != .Exited or result.term.Exited != 0) {
+        const detail = std.fmt.allocPrint(allocator, "{s} is writable, but programs in it cannot be executed", .{base_dir}) catch base_dir;
+        return .{ .status = .fail, .detail = detail, .hint = hint };
+    }
+
+    const detail = std.fmt.allocPrint(allocator, "{s} is writable and executable", .{base_dir}) catch base_dir;
+    return .{ .status = .pass, .detail = detail };
+}
+
+fn check_disk_space(allocator: std.mem.Allocator, install_dir: []const u8, _: *const MetaStruct) Result {
+    if (builtin.os.tag != .linux or @sizeOf(usize) != 8) {
+        return .{ .status = .skip, .detail = "not supported on this platform" };
+    } else {
+        const base_dir = fs.path.dirname(install_dir) orelse install_dir;
+        const base_dir_z = allocator.dupeZ(u8, base_dir) catch return .{ .status = .skip, .detail = "out of memory" };
+
+        var stats: StatVfs = undefined;
+        if (statvfs(base_dir_z, &stats) != 0) {
+            return .{ .status = .skip, .detail = "could not read the file system stats" };
+        }
+
+        const available = stats.f_bavail * stats.f_frsize;
+        const needed = build_options.UNCOMPRESSED_SIZE;
+        const detail = std.fmt.allocPrint(allocator, "{} MiB available, {} MiB needed", .{ available / (1024 * 1024), needed / (1024 * 1024) + 1 }) catch "";
+
+        if (available < needed) {
+            return .{ .status = .fail, .detail = detail, .hint = "free up some space, or install elsewhere with " ++ install_dir_env };
+        }
+
+        return .{ .status = .pass, .detail = detail };
+    }
+}
+
+fn check_locale(allocator: std.mem.Allocator, _: []const u8, _: *const MetaStruct) Result {
+    if (builtin.os.tag == .windows) {
+        return .{ .status = .skip, .detail = "not relevant on Windows" };
+    }
+
+    // Same precedence the C library uses
+    for ([_][]const u8{ "LC_ALL", "LC_CTYPE", "LANG" }) |name| {
+        const value = std.process.getEnvVarOwned(allocator, name) catch continue;
+        if (value.len == 0) {
+            continue;
+        }
+
+        const detail = std.fmt.allocPrint(allocator, "{s}={s}", .{ name, value }) catch value;
+        if (std.ascii.indexOfIgnoreCase(value, "utf-8") != null or std.ascii.indexOfIgnoreCase(value, "utf8") != null) {
+            return .{ .status = .pass, .detail = detail };
+        }
+
+        return .{ .status = .warn, .detail = detail, .hint = "the VM falls back to latin1 file names without a UTF-8 locale, try `export LANG=C.UTF-8`" };
+    }
+
+    return .{ .status = .warn, .detail = "no locale set", .hint = "the VM falls back to latin1 file names without a UTF-8 locale, try `export LANG=C.UTF-8`" };
+}
+
+fn check_epmd(allocator: std.mem.Allocator, _: []const u8, _: *const MetaStruct) Result {
+    var port: u16 = DEFAULT_EPMD_PORT;
+    if (std.process.getEnvVarOwned(allocator, "ERL_EPMD_PORT")) |value| {
+        port = std.fmt.parseInt(u16, value, 10) catch DEFAULT_EPMD_PORT;
+    } else |_| {}
+
+    const address = std.net.Address.initIp4(.{ 127, 0, 0, 1 }, port);
+    if (std.net.tcpConnectToAddress(address)) |stream| {
+        stream.close();
+        const detail = std.fmt.allocPrint(allocator, "running on port {}", .{port}) catch "running";
+        return .{ .status = .pass, .detail = detail };
+    } else |_| {}
+
+    // Not a problem on its own, the VM starts epmd when distribution is enabled
+    const detail = std.fmt.allocPrint(allocator, "not running on port {}", .{port}) catch "not running";
+    return .{ .status = .warn, .detail = detail, .hint = "only needed for distributed Erlang, the VM starts it on demand unless a firewall blocks the port" };
+}
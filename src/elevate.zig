@@ -0,0 +1,103 @@
+/////
+// When the install directory needs admin rights on Windows (e.g. an install dir override under Program Files),
+// we start an elevated copy of ourselves through the UAC prompt to do the extraction, then launch the app as usual.
+// The elevated copy is started with `--burrito-elevated-install=<dir>`, installs into that directory and exits.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const fs = std.fs;
+const log = std.log;
+const windows = std.os.windows;
+
+const SEE_MASK_NOCLOSEPROCESS: windows.ULONG = 0x00000040;
+const SW_HIDE: c_int = 0;
+const ERROR_CANCELLED: windows.DWORD = 1223;
+
+const SHELLEXECUTEINFOW = extern struct {
+    cbSize: windows.DWORD,
+    fMask: windows.ULONG,
+    hwnd: ?windows.HWND,
+    lpVerb: ?windows.LPCWSTR,
+    lpFile: ?windows.LPCWSTR,
+    lpParameters: ?windows.LPCWSTR,
+    lpDirectory: ?windows.LPCWSTR,
+    nShow: c_int,
+    hInstApp: ?windows.HINSTANCE,
+    lpIDList: ?*anyopaque,
+    lpClass: ?windows.LPCWSTR,
+    hkeyClass: ?windows.HKEY,
+    dwHotKey: windows.DWORD,
+    hIconOrMonitor: ?windows.HANDLE,
+    hProcess: ?windows.HANDLE,
+};
+
+extern "shell32" fn ShellExecuteExW(pExecInfo: *SHELLEXECUTEINFOW) callconv(windows.WINAPI) windows.BOOL;
+
+pub const ELEVATED_INSTALL_FLAG = "--burrito-elevated-install";
+
+// Whether installing into `install_dir` needs admin rights we don't have, always false outside of Windows
+pub fn needs_elevation(allocator: std.mem.Allocator, install_dir: []const u8) bool {
+    if (builtin.os.tag != .windows) {
+        return false;
+    }
+
+    fs.cwd().makePath(install_dir) catch |err| return err == error.AccessDenied;
+
+    // The directory may already exist without us being allowed to write into it
+    const probe_path = fs.path.join(allocator, &[_][]const u8{ install_dir, ".burrito_write_probe" }) catch return false;
+    const probe = fs.createFileAbsolute(probe_path, .{}) catch |err| return err == error.AccessDenied;
+    probe.close();
+    fs.deleteFileAbsolute(probe_path) catch {};
+
+    return false;
+}
+
+// Runs the install step in an elevated copy of ourselves, and waits for it to finish
+pub fn run_elevated_install(allocator: std.mem.Allocator, install_dir: []const u8) !void {
+    const exe_path = try fs.selfExePathAlloc(allocator);
+    const parameters = try std.fmt.allocPrint(allocator, "{s}=\"{s}\"", .{ ELEVATED_INSTALL_FLAG, install_dir });
+
+    run_elevated(allocator, exe_path, parameters) catch |err| {
+        switch (err) {
+            error.ElevationCancelled => log.err("Administrator rights were declined, the application can't be installed into {s}", .{install_dir}),
+            error.ElevatedProcessFailed => log.err("The elevated install into {s} failed", .{install_dir}),
+            else => {},
+        }
+        return err;
+    };
+}
+
+// Runs `file` with administrator rights through the UAC prompt, and waits for it to finish
+pub fn run_elevated(allocator: std.mem.Allocator, file: []const u8, parameters: []const u8) !void {
+    if (builtin.os.tag != .windows) {
+        return error.Unsupported;
+    } else {
+        var info = std.mem.zeroes(SHELLEXECUTEINFOW);
+        info.cbSize = @sizeOf(SHELLEXECUTEINFOW);
+        info.fMask = SEE_MASK_NOCLOSEPROCESS;
+        info.lpVerb = try std.unicode.utf8ToUtf16LeWithNull(allocator, "runas");
+        info.lpFile = try std.unicode.utf8ToUtf16LeWithNull(allocator, file);
+        info.lpParameters = try std.unicode.utf8ToUtf16LeWithNull(allocator, parameters);
+        info.nShow = SW_HIDE;
+
+        if (ShellExecuteExW(&info) == 0) {
+            const err = windows.kernel32.GetLastError();
+            if (@intFromEnum(err) == ERROR_CANCELLED) {
+                return error.ElevationCancelled;
+            }
+            return windows.unexpectedError(err);
+        }
+
+        const process = info.hProcess orelse return error.ElevatedProcessFailed;
+        defer windows.CloseHandle(process);
+
+        try windows.WaitForSingleObject(process, windows.INFINITE);
+
+        var exit_code: windows.DWORD = undefined;
+        if (windows.kernel32.GetExitCodeProcess(process, &exit_code) == 0 or exit_code != 0) {
+            return error.ElevatedProcessFailed;
+        }
+    }
+}
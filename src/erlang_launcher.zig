@@ -4,6 +4,18 @@ const builtin = @import("builtin");
 const fs = std.fs;
 const log = std.log;
 const metadata = @import("metadata.zig");
+const bench = @import("bench.zig");
+const exit_codes = @import("exit_codes.zig");
+const cgroup = @import("cgroup.zig");
+const keychain = @import("keychain.zig");
+const libc = @import("libc.zig");
+const logger = @import("logger.zig");
+const priority = @import("priority.zig");
+const profile = @import("profile.zig");
+const rlimit = @import("rlimit.zig");
+const shutil = @import("shutil.zig");
+const supervisor = @import("supervisor.zig");
+const trace = @import("trace.zig");
 const win_asni = @cImport(@cInclude("win_ansi_fix.h"));
 
 const MetaStruct = metadata.MetaStruct;
@@ -11,18 +23,74 @@ const EnvMap = std.process.EnvMap;
 
 const MAX_READ_SIZE = 256;
 
-fn get_erl_exe_name() []const u8 {
+pub const LaunchMode = enum {
+    // Replace the wrapper with the VM, attached to the current terminal
+    foreground,
+    // Start the VM under `run_erl` so it can be attached to later using `to_erl`
+    daemon,
+};
+
+// A config file given at launch, loaded after the release's sys.config so its settings win
+var extra_config: ?[]const u8 = null;
+
+// Checks that `path` is a config file erl can load and keeps its absolute path for the launch
+pub fn set_extra_config(allocator: std.mem.Allocator, path: []const u8) !void {
+    // erl looks for `<name>.config`, whatever `<name>` already ends with
+    if (!std.mem.eql(u8, fs.path.extension(path), ".config")) {
+        return error.NotAConfigFile;
+    }
+    const absolute_path = try fs.cwd().realpathAlloc(allocator, path);
+    const stat = try fs.cwd().statFile(absolute_path);
+    if (stat.kind != .file) {
+        return error.NotAConfigFile;
+    }
+    extra_config = absolute_path;
+}
+
+// Extra arguments for each mode, these always end with `-extra` so app arguments can follow
+const foreground_args = &[_][]const u8{"-extra"};
+const daemon_args = &[_][]const u8{ "-user", "Elixir.IEx.CLI", "-extra", "--no-halt" };
+
+fn get_erl_exe_name(allocator: std.mem.Allocator, meta: *const MetaStruct) []const u8 {
     if (builtin.os.tag == .windows) {
+        // `werl.exe` gives a GUI console with proper encoding and scrollback,
+        // `erl.exe` is the right choice when stdin/stdout are piped
+        const console = shutil.get_release_env(allocator, "WINDOWS_CONSOLE") orelse meta.windows_console;
+        if (std.ascii.eqlIgnoreCase(console, "werl")) {
+            return "werl.exe";
+        }
         return "erl.exe";
     } else {
         return "erlexec";
     }
 }
 
-pub fn launch(install_dir: []const u8, env_map: *EnvMap, meta: *const MetaStruct, args_trimmed: []const []const u8) !void {
-    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
-    var allocator = arena.allocator();
+// The directory `run_erl` creates its named pipes in, `to_erl` needs the same path to attach
+pub fn get_run_erl_pipe_dir(allocator: std.mem.Allocator, install_dir: []const u8) ![]const u8 {
+    const pipe_dir = try fs.path.join(allocator, &[_][]const u8{ try profile.get_run_erl_dir(allocator, install_dir), "pipe" });
+    // `run_erl` and `to_erl` both require a trailing slash on the pipe directory
+    return try std.fmt.allocPrint(allocator, "{s}/", .{pipe_dir});
+}
+
+// Environment variables the launcher sets for the VM, on top of the environment it's given
+pub const launcher_env_names = [_][]const u8{ "ROOTDIR", "BINDIR", "RELEASE_ROOT", "RELEASE_SYS_CONFIG", "RELEASE_NODE", "RELEASE_DISTRIBUTION" };
+
+// Everything needed to start the VM, without having started it yet
+pub const Plan = struct {
+    argv: []const []const u8,
+    env_map: EnvMap,
+    erts_bin_path: []const u8,
+    // Also referenced by `argv`, see `wipe_secrets()`
+    cookie: []u8,
+
+    // Overwrites the cookie once it's no longer needed, so it doesn't linger in our memory (and core dumps or swap)
+    pub fn wipe_secrets(self: *const Plan) void {
+        std.crypto.utils.secureZero(u8, self.cookie);
+    }
+};
 
+// Works out the final argument vector and environment for the VM
+pub fn plan(allocator: std.mem.Allocator, install_dir: []const u8, env_map: *const EnvMap, meta: *const MetaStruct, args_trimmed: []const []const u8, mode: LaunchMode) !Plan {
     // Computer directories we care about
     const release_cookie_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, "releases", "COOKIE" });
     const release_lib_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, "lib" });
@@ -34,17 +102,17 @@ pub fn launch(install_dir: []const u8, env_map: *EnvMap, meta: *const MetaStruct
 
     const erts_version_name = try std.fmt.allocPrint(allocator, "erts-{s}", .{meta.erts_version});
     const erts_bin_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, erts_version_name, "bin" });
-    const erl_bin_path = try fs.path.join(allocator, &[_][]const u8{ erts_bin_path, get_erl_exe_name() });
+    const erl_bin_path = try fs.path.join(allocator, &[_][]const u8{ erts_bin_path, get_erl_exe_name(allocator, meta) });
 
-    // Read the Erlang COOKIE file for the release
-    const release_cookie_file = try fs.openFileAbsolute(release_cookie_path, .{ .mode = .read_write });
-    const release_cookie_content = try release_cookie_file.readToEndAlloc(allocator, MAX_READ_SIZE);
+    const release_cookie_content = try get_cookie(allocator, release_cookie_path, meta);
 
     // Set all the required release arguments
 
     const erlang_cli = &[_][]const u8{
         erl_bin_path[0..],
-        "-elixir ansi_enabled true",
+        "-progname",
+        meta.app_name,
+        if (logger.color_enabled()) "-elixir ansi_enabled true" else "-elixir ansi_enabled false",
         "-noshell",
         "-s elixir start_cli",
         "-mode embedded",
@@ -59,25 +127,113 @@ pub fn launch(install_dir: []const u8, env_map: *EnvMap, meta: *const MetaStruct
         install_vm_args_path,
         "-config",
         config_sys_path,
-        "-extra",
     };
 
+    // Match the number of schedulers to our cgroup CPU quota, otherwise the VM starts one per host core. Dirty CPU
+    // schedulers follow the quota too. Dirty IO schedulers (`+SDio`) are left at the VM's default of 10, they mostly
+    // wait on blocking IO rather than use CPU, so fewer of them would only queue file operations
+    var scheduler_args: []const []const u8 = &.{};
+    if (builtin.os.tag == .linux and !flag_overridden(allocator, env_map, install_vm_args_path, "+S")) {
+        if (cgroup.cpu_limit(allocator)) |limit| {
+            const cpu_count = std.Thread.getCpuCount() catch limit;
+            if (limit < cpu_count) {
+                log.debug("Limiting schedulers to {} to match the cgroup CPU quota", .{limit});
+                const schedulers = try std.fmt.allocPrint(allocator, "{}:{}", .{ limit, limit });
+                scheduler_args = try allocator.dupe([]const u8, &[_][]const u8{ "+S", schedulers, "+SDcpu", schedulers });
+            }
+        }
+    }
+
+    const config_args: []const []const u8 = if (extra_config) |path| try allocator.dupe([]const u8, &[_][]const u8{ "-config", path }) else &.{};
+
+    const mode_args = switch (mode) {
+        .foreground => foreground_args,
+        .daemon => daemon_args,
+    };
+
+    const node_name = try get_node_name(allocator, env_map, meta, install_vm_args_path);
+    const node_args: []const []const u8 = if (node_name) |node| try allocator.dupe([]const u8, &[_][]const u8{ node.flag(), node.name }) else &.{};
+
+    const final_args = try std.mem.concat(allocator, []const u8, &.{ erlang_cli, config_args, scheduler_args, node_args, mode_args, args_trimmed });
+
+    var erl_env_map = EnvMap.init(allocator);
+
+    var env_map_it = env_map.iterator();
+    while (env_map_it.next()) |entry| {
+        const key = entry.key_ptr.*;
+        const val = entry.value_ptr.*;
+        try erl_env_map.put(key, val);
+    }
+
+    if (builtin.os.tag != .windows) {
+        try erl_env_map.put("ROOTDIR", install_dir[0..]);
+        try erl_env_map.put("BINDIR", erts_bin_path[0..]);
+    }
+    try erl_env_map.put("RELEASE_ROOT", install_dir);
+    try erl_env_map.put("RELEASE_SYS_CONFIG", config_sys_path_no_ext);
+    // Same as a release started by its own scripts, e.g. for `:rpc` tooling that reads them
+    if (node_name) |node| {
+        try erl_env_map.put("RELEASE_NODE", node.name);
+        try erl_env_map.put("RELEASE_DISTRIBUTION", @tagName(node.kind));
+    }
+
+    return Plan{
+        .argv = final_args,
+        .env_map = erl_env_map,
+        .erts_bin_path = erts_bin_path,
+        .cookie = release_cookie_content,
+    };
+}
+
+pub fn launch(install_dir: []const u8, env_map: *EnvMap, meta: *const MetaStruct, args_trimmed: []const []const u8, mode: LaunchMode) !void {
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    var allocator = arena.allocator();
+
+    const spawn_timer = trace.start(.spawn_vm);
+    const plan_span = trace.begin("plan launch");
+    var launch_plan = try plan(allocator, install_dir, env_map, meta, args_trimmed, mode);
+    plan_span.end();
+    const final_args = launch_plan.argv;
+
     if (builtin.os.tag == .windows) {
         // Fix up Windows 10+ consoles having ANSI escape support, but only if we set some flags
-        win_asni.enable_virtual_term();
-        const final_args = try std.mem.concat(allocator, []const u8, &.{ erlang_cli, args_trimmed });
+        if (logger.color_enabled()) {
+            win_asni.enable_virtual_term();
+        }
 
-        try env_map.put("RELEASE_ROOT", install_dir);
-        try env_map.put("RELEASE_SYS_CONFIG", config_sys_path_no_ext);
+        if (mode == .daemon) {
+            log.err("Running as a daemon is not supported on Windows!", .{});
+            std.process.exit(1);
+        }
 
         var win_child_proc = std.ChildProcess.init(final_args, allocator);
-        win_child_proc.env_map = env_map;
+        win_child_proc.env_map = &launch_plan.env_map;
         win_child_proc.stdout_behavior = .Inherit;
         win_child_proc.stdin_behavior = .Inherit;
 
-        log.debug("CLI List: {s}", .{final_args});
+        log.debug("CLI List: {s}", .{redact_args(allocator, final_args)});
+
+        if (bench.is_child()) {
+            bench.finish_child();
+        }
+
+        win_child_proc.spawn() catch |err| {
+            logger.set_error_context("starting", final_args[0]);
+            return err;
+        };
+        launch_plan.wipe_secrets();
+        spawn_timer.stop();
+        trace.log_summary();
+
+        if (shutil.get_release_env(allocator, "PRIORITY_CLASS") orelse meta.windows_priority) |class_name| {
+            if (priority.parse_windows_priority_class(class_name)) |class| {
+                priority.set_windows_priority_class(win_child_proc.id, class);
+            } else {
+                log.warn("Ignoring unknown priority class: {s}", .{class_name});
+            }
+        }
 
-        const win_term = try win_child_proc.spawnAndWait();
+        const win_term = try win_child_proc.wait();
         switch (win_term) {
             .Exited => |code| {
                 std.process.exit(code);
@@ -85,25 +241,226 @@ pub fn launch(install_dir: []const u8, env_map: *EnvMap, meta: *const MetaStruct
             else => std.process.exit(1),
         }
     } else {
-        const final_args = try std.mem.concat(allocator, []const u8, &.{ erlang_cli, args_trimmed });
+        log.debug("CLI List: {s}", .{redact_args(allocator, final_args)});
 
-        log.debug("CLI List: {s}", .{final_args});
-
-        var erl_env_map = EnvMap.init(allocator);
+        var erl_env_map = launch_plan.env_map;
         defer erl_env_map.deinit();
 
-        var env_map_it = env_map.iterator();
-        while (env_map_it.next()) |entry| {
-            const key = entry.key_ptr.*;
-            const val = entry.value_ptr.*;
-            try erl_env_map.put(key, val);
+        // Catch a release built for the other libc before the kernel gives a useless error for it,
+        // a fully static ERTS doesn't go through a dynamic loader at all
+        if (builtin.os.tag == .linux and !meta.static_erts) {
+            libc.check_erts(allocator, launch_plan.erts_bin_path, meta) catch |err| switch (err) {
+                error.LibcMismatch => exit_codes.exit_with(err),
+                else => log.debug("Could not check the ERTS dynamic loader: {!}", .{err}),
+            };
         }
 
-        try erl_env_map.put("ROOTDIR", install_dir[0..]);
-        try erl_env_map.put("BINDIR", erts_bin_path[0..]);
-        try erl_env_map.put("RELEASE_ROOT", install_dir);
-        try erl_env_map.put("RELEASE_SYS_CONFIG", config_sys_path_no_ext);
+        // The VM inherits our nice value and resource limits through execve()/fork()
+        if (get_nice(allocator, meta)) |nice| {
+            priority.set_nice(nice);
+        }
+
+        rlimit.raise_nofile(get_max_open_files(allocator, meta));
+
+        if (bench.is_child()) {
+            bench.finish_child();
+        }
+
+        // Past this point we are the VM, or only wait for it
+        spawn_timer.stop();
+        trace.log_summary();
+
+        if (mode == .daemon) {
+            return launch_daemon(allocator, install_dir, launch_plan.erts_bin_path, final_args, &erl_env_map);
+        }
 
-        return std.process.execve(allocator, final_args, &erl_env_map);
+        // Some features need us to stay around as the parent of the VM, instead of replacing ourselves with it
+        const supervisor_options = supervisor.Options{
+            .use_pty = meta.pty or shutil.release_env_enabled(allocator, "PTY"),
+            .shutdown_timeout_ms = get_shutdown_timeout(allocator, meta),
+            .title = try std.fmt.allocPrint(allocator, "{s} {s}", .{ meta.app_name, meta.app_version }),
+            .secret = launch_plan.cookie,
+        };
+
+        if (supervisor_options.use_pty or supervisor_options.shutdown_timeout_ms > 0) {
+            log.debug("Launching erlang as a supervised child process", .{});
+            try supervisor.supervise(allocator, final_args, &erl_env_map, supervisor_options);
+        }
+
+        const exec_err = std.process.execve(allocator, final_args, &erl_env_map);
+        logger.set_error_context("starting", final_args[0]);
+        return exec_err;
+    }
+}
+
+// Reads the distribution cookie, from the release's COOKIE file or from the OS credential store (see keychain.zig)
+fn get_cookie(allocator: std.mem.Allocator, release_cookie_path: []const u8, meta: *const MetaStruct) ![]u8 {
+    if (std.mem.eql(u8, meta.cookie_source, "keychain")) {
+        const cookie = keychain.get_cookie(allocator, meta) catch |err| {
+            logger.err("Could not read the distribution cookie from the credential store: {!}", .{err});
+            return err;
+        };
+        return cookie orelse {
+            logger.err("There is no distribution cookie for {s} in the credential store", .{meta.app_name});
+            keychain.print_store_hint(allocator, meta);
+            return error.CookieNotFound;
+        };
+    }
+
+    // Read the Erlang COOKIE file for the release
+    const release_cookie_file = try fs.openFileAbsolute(release_cookie_path, .{ .mode = .read_write });
+    defer release_cookie_file.close();
+    return try release_cookie_file.readToEndAlloc(allocator, MAX_READ_SIZE);
+}
+
+// True if the release's `vm.args` or the `ERL_*FLAGS` environment already pass `flag` to the VM
+fn flag_overridden(allocator: std.mem.Allocator, env_map: *const EnvMap, vm_args_path: []const u8, flag: []const u8) bool {
+    for ([_][]const u8{ "ERL_FLAGS", "ERL_AFLAGS", "ERL_ZFLAGS" }) |name| {
+        if (env_map.get(name)) |value| {
+            if (has_token(value, flag)) {
+                return true;
+            }
+        }
+    }
+
+    const vm_args_file = fs.openFileAbsolute(vm_args_path, .{}) catch return false;
+    defer vm_args_file.close();
+    const vm_args = vm_args_file.readToEndAlloc(allocator, 1024 * 1024) catch return false;
+
+    // Comments count for nothing, the default vm.args mentions `-name` and `-sname` in one
+    var lines = std.mem.splitScalar(u8, vm_args, '\n');
+    while (lines.next()) |line| {
+        if (std.mem.startsWith(u8, std.mem.trimLeft(u8, line, " \t"), "#")) continue;
+        if (has_token(line, flag)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Whether `flag` is one of the whitespace separated arguments in `text`, so `-name` doesn't match `-sname`
+fn has_token(text: []const u8, flag: []const u8) bool {
+    var tokens = std.mem.tokenizeAny(u8, text, " \t\r\n");
+    while (tokens.next()) |token| {
+        if (std.mem.eql(u8, token, flag)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+pub const NodeName = struct {
+    kind: enum { sname, name },
+    name: []const u8,
+
+    fn flag(self: NodeName) []const u8 {
+        return switch (self.kind) {
+            .sname => "-sname",
+            .name => "-name",
+        };
+    }
+};
+
+// The node name the VM is started with, from `<APP>_NODE_NAME` or made up for this run with `unique_node_name`.
+// Null leaves distribution to the release.
+fn get_node_name(allocator: std.mem.Allocator, env_map: *const EnvMap, meta: *const MetaStruct, vm_args_path: []const u8) !?NodeName {
+    const explicit_name = shutil.get_release_env(allocator, "NODE_NAME");
+    if (explicit_name == null and !meta.unique_node_name) {
+        return null;
+    }
+
+    // Passing a second name would keep the VM from starting distribution at all
+    if (flag_overridden(allocator, env_map, vm_args_path, "-sname") or flag_overridden(allocator, env_map, vm_args_path, "-name")) {
+        logger.warn("The node name is already set in vm.args or ERL_FLAGS, remove it there to name nodes per run", .{});
+        return null;
+    }
+
+    if (explicit_name) |name| {
+        // A fully qualified host needs long names, like `RELEASE_DISTRIBUTION=name`
+        const host = if (std.mem.indexOfScalar(u8, name, '@')) |at| name[at + 1 ..] else "";
+        return NodeName{ .kind = if (std.mem.indexOfScalar(u8, host, '.') != null) .name else .sname, .name = name };
+    }
+
+    // The pid keeps concurrent runs apart, the random part covers pids being reused across hosts sharing an EPMD
+    var random_bytes: [2]u8 = undefined;
+    std.crypto.random.bytes(&random_bytes);
+    const name = try std.fmt.allocPrint(allocator, "{s}_{}_{s}", .{ meta.app_name, get_pid(), std.fmt.fmtSliceHexLower(&random_bytes) });
+    log.debug("Starting the VM as node {s}", .{name});
+    return NodeName{ .kind = .sname, .name = name };
+}
+
+// A copy of `args` with the distribution cookie hidden, for logging them. Debug messages end up in support bundles
+// and crash logs.
+pub fn redact_args(allocator: std.mem.Allocator, args: []const []const u8) []const []const u8 {
+    const redacted = allocator.dupe([]const u8, args) catch return &.{};
+    for (redacted, 0..) |*arg, i| {
+        if (i > 0 and std.mem.eql(u8, args[i - 1], "-setcookie")) {
+            arg.* = "<hidden>";
+        }
+    }
+    return redacted;
+}
+
+fn get_pid() i64 {
+    return switch (builtin.os.tag) {
+        .windows => std.os.windows.kernel32.GetCurrentProcessId(),
+        .linux => std.os.linux.getpid(),
+        else => std.c.getpid(),
+    };
+}
+
+fn get_max_open_files(allocator: std.mem.Allocator, meta: *const MetaStruct) ?u64 {
+    if (shutil.get_release_env(allocator, "MAX_OPEN_FILES")) |value| {
+        return std.fmt.parseInt(u64, value, 10) catch blk: {
+            log.warn("Ignoring invalid open file limit: {s}", .{value});
+            break :blk meta.max_open_files;
+        };
     }
+    return meta.max_open_files;
+}
+
+fn get_nice(allocator: std.mem.Allocator, meta: *const MetaStruct) ?i32 {
+    if (shutil.get_release_env(allocator, "NICE")) |value| {
+        return std.fmt.parseInt(i32, value, 10) catch blk: {
+            log.warn("Ignoring invalid nice value: {s}", .{value});
+            break :blk meta.nice;
+        };
+    }
+    return meta.nice;
+}
+
+fn get_shutdown_timeout(allocator: std.mem.Allocator, meta: *const MetaStruct) u64 {
+    if (shutil.get_release_env(allocator, "SHUTDOWN_TIMEOUT")) |value| {
+        return std.fmt.parseInt(u64, value, 10) catch blk: {
+            log.warn("Ignoring invalid shutdown timeout: {s}", .{value});
+            break :blk meta.shutdown_timeout;
+        };
+    }
+    return meta.shutdown_timeout;
+}
+
+fn launch_daemon(allocator: std.mem.Allocator, install_dir: []const u8, erts_bin_path: []const u8, erl_args: []const []const u8, erl_env_map: *const EnvMap) !void {
+    const run_erl_path = try fs.path.join(allocator, &[_][]const u8{ erts_bin_path, "run_erl" });
+    const pipe_dir = try get_run_erl_pipe_dir(allocator, install_dir);
+    const log_dir = try fs.path.join(allocator, &[_][]const u8{ try profile.get_run_erl_dir(allocator, install_dir), "log" });
+
+    try std.fs.cwd().makePath(pipe_dir);
+    try std.fs.cwd().makePath(log_dir);
+
+    // `run_erl` takes the command to run as a single string that is passed to `sh -c`
+    var command = std.ArrayList(u8).init(allocator);
+    try command.appendSlice("exec");
+    for (erl_args) |arg| {
+        try command.append(' ');
+        try command.appendSlice(try shutil.shell_quote(allocator, arg));
+    }
+
+    const log_dir_slash = try std.fmt.allocPrint(allocator, "{s}/", .{log_dir});
+    const run_erl_args = &[_][]const u8{ run_erl_path, "-daemon", pipe_dir, log_dir_slash, command.items };
+
+    log.debug("run_erl CLI List: {s} {s}", .{ run_erl_args[0 .. run_erl_args.len - 1], redact_args(allocator, erl_args) });
+
+    const exec_err = std.process.execve(allocator, run_erl_args, erl_env_map);
+    logger.set_error_context("starting", run_erl_path);
+    return exec_err;
 }
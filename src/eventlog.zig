@@ -0,0 +1,85 @@
+/////
+// Reports wrapper warnings and errors to the Windows Event Log (Application log), under an event source named after the release.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const windows = std.os.windows;
+const HANDLE = windows.HANDLE;
+const HKEY = windows.HKEY;
+const LPCWSTR = windows.LPCWSTR;
+const BOOL = windows.BOOL;
+const WORD = windows.WORD;
+const DWORD = windows.DWORD;
+
+extern "advapi32" fn RegisterEventSourceW(lpUNCServerName: ?LPCWSTR, lpSourceName: LPCWSTR) callconv(windows.WINAPI) ?HANDLE;
+extern "advapi32" fn ReportEventW(hEventLog: HANDLE, wType: WORD, wCategory: WORD, dwEventID: DWORD, lpUserSid: ?*anyopaque, wNumStrings: WORD, dwDataSize: DWORD, lpStrings: ?[*]const LPCWSTR, lpRawData: ?*anyopaque) callconv(windows.WINAPI) BOOL;
+extern "advapi32" fn RegCreateKeyExW(hKey: HKEY, lpSubKey: LPCWSTR, Reserved: DWORD, lpClass: ?LPCWSTR, dwOptions: DWORD, samDesired: DWORD, lpSecurityAttributes: ?*anyopaque, phkResult: *HKEY, lpdwDisposition: ?*DWORD) callconv(windows.WINAPI) windows.LSTATUS;
+extern "advapi32" fn RegSetValueExW(hKey: HKEY, lpValueName: LPCWSTR, Reserved: DWORD, dwType: DWORD, lpData: [*]const u8, cbData: DWORD) callconv(windows.WINAPI) windows.LSTATUS;
+extern "advapi32" fn RegCloseKey(hKey: HKEY) callconv(windows.WINAPI) windows.LSTATUS;
+
+pub const EventType = enum(WORD) {
+    err = 0x0001,
+    warning = 0x0002,
+    information = 0x0004,
+};
+
+// Generic "%1" message file shipped with .NET, lets Event Viewer show our message text as-is
+const MESSAGE_FILE = "%SystemRoot%\\Microsoft.NET\\Framework\\v4.0.30319\\EventLogMessages.dll";
+const SOURCE_KEY_PREFIX = "SYSTEM\\CurrentControlSet\\Services\\EventLog\\Application\\";
+
+const HKEY_LOCAL_MACHINE: HKEY = @ptrFromInt(0x80000002);
+const KEY_SET_VALUE: DWORD = 0x0002;
+const REG_EXPAND_SZ: DWORD = 2;
+const REG_DWORD: DWORD = 4;
+
+const EVENT_ID = 1000;
+
+var event_source: ?HANDLE = null;
+
+// Registers the event source in the registry, this requires admin rights so failures are ignored.
+// Without the registration Event Viewer still shows our messages, but prefixed by a "description not found" notice.
+fn register_source(allocator: std.mem.Allocator, source_name: []const u8) void {
+    const key_path = std.fmt.allocPrint(allocator, SOURCE_KEY_PREFIX ++ "{s}", .{source_name}) catch return;
+    const key_path_w = std.unicode.utf8ToUtf16LeWithNull(allocator, key_path) catch return;
+
+    var key: HKEY = undefined;
+    if (RegCreateKeyExW(HKEY_LOCAL_MACHINE, key_path_w, 0, null, 0, KEY_SET_VALUE, null, &key, null) != 0) {
+        return;
+    }
+    defer _ = RegCloseKey(key);
+
+    const message_file_w = std.unicode.utf8ToUtf16LeWithNull(allocator, MESSAGE_FILE) catch return;
+    const message_file_bytes = std.mem.sliceAsBytes(message_file_w[0 .. message_file_w.len + 1]);
+    const value_name_w = std.unicode.utf8ToUtf16LeWithNull(allocator, "EventMessageFile") catch return;
+    _ = RegSetValueExW(key, value_name_w, 0, REG_EXPAND_SZ, message_file_bytes.ptr, @intCast(message_file_bytes.len));
+
+    const types_supported: DWORD = 0x0007;
+    const types_name_w = std.unicode.utf8ToUtf16LeWithNull(allocator, "TypesSupported") catch return;
+    _ = RegSetValueExW(key, types_name_w, 0, REG_DWORD, std.mem.asBytes(&types_supported), @sizeOf(DWORD));
+}
+
+fn get_source(allocator: std.mem.Allocator, source_name: []const u8) ?HANDLE {
+    if (event_source) |handle| {
+        return handle;
+    }
+
+    register_source(allocator, source_name);
+
+    const source_name_w = std.unicode.utf8ToUtf16LeWithNull(allocator, source_name) catch return null;
+    event_source = RegisterEventSourceW(null, source_name_w);
+    return event_source;
+}
+
+// Writes a single entry into the Application event log, does nothing on other platforms
+pub fn report(allocator: std.mem.Allocator, event_type: EventType, source_name: []const u8, message: []const u8) void {
+    if (builtin.os.tag != .windows) {
+        return;
+    } else {
+        const handle = get_source(allocator, source_name) orelse return;
+        const message_w = std.unicode.utf8ToUtf16LeWithNull(allocator, message) catch return;
+        const strings = [_]LPCWSTR{message_w};
+        _ = ReportEventW(handle, @intFromEnum(event_type), 0, EVENT_ID, null, 1, 0, &strings, null);
+    }
+}
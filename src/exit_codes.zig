@@ -0,0 +1,99 @@
+/////
+// The exit codes of the wrapper itself, one per class of failure, so scripts can tell a corrupted download from a
+// full disk or a crashed app. They are part of the public interface (see the README) and never change meaning.
+//
+// Once the VM is running, its exit code is passed through as it is, and a VM killed by a signal exits with
+// 128 + the signal number, like in a shell. Codes 70 to 79 are reserved for the wrapper, apps can avoid them to
+// stay distinguishable.
+/////
+
+const std = @import("std");
+
+pub const ExitCode = enum(u8) {
+    // Anything not covered below
+    failure = 1,
+    // The payload is damaged or doesn't belong to this binary, downloading it again fixes it
+    payload_corrupted = 70,
+    // The install directory can't be created or written to
+    install_dir_unavailable = 71,
+    // The disk, or the user's quota on it, is full
+    disk_full = 72,
+    // The machine ran out of memory while installing or preparing the launch
+    out_of_memory = 73,
+    // Installed files changed since they were installed, and the release refuses to launch them
+    tampered = 74,
+    // The VM could not be started, e.g. a missing or incompatible ERTS
+    launch_failed = 75,
+    // Elevation to install into a protected location was cancelled or failed
+    elevation_failed = 76,
+    // A bug in the wrapper itself, see panic_handler.zig
+    panic = 77,
+
+    pub fn code(self: ExitCode) u8 {
+        return @intFromEnum(self);
+    }
+};
+
+pub fn for_error(err: anyerror) ExitCode {
+    return switch (err) {
+        error.PayloadCorrupted,
+        error.PayloadMismatch,
+        error.EmptyPayload,
+        error.BadHeader,
+        error.BadRecord,
+        error.ParseError,
+        error.EndOfStream,
+        error.CaseCollision,
+        error.PathTraversal,
+        => .payload_corrupted,
+
+        error.InstallDirUnavailable,
+        error.AccessDenied,
+        error.PermissionDenied,
+        error.ReadOnlyFileSystem,
+        => .install_dir_unavailable,
+
+        error.NoSpaceLeft,
+        error.DiskQuota,
+        error.FileTooBig,
+        => .disk_full,
+
+        error.OutOfMemory,
+        error.SystemResources,
+        => .out_of_memory,
+
+        error.Tampered => .tampered,
+
+        error.LibcMismatch,
+        error.InvalidExe,
+        error.CookieNotFound,
+        => .launch_failed,
+
+        error.ElevationCancelled,
+        error.ElevatedProcessFailed,
+        => .elevation_failed,
+
+        else => .failure,
+    };
+}
+
+// Turns a class back into an error, for failures reported by a child process through its exit code
+pub fn to_error(code: u8) anyerror {
+    const exit_code = std.meta.intToEnum(ExitCode, code) catch return error.Unexpected;
+    return switch (exit_code) {
+        .failure => error.Unexpected,
+        .payload_corrupted => error.PayloadCorrupted,
+        .install_dir_unavailable => error.AccessDenied,
+        .disk_full => error.NoSpaceLeft,
+        .out_of_memory => error.OutOfMemory,
+        .tampered => error.Tampered,
+        .launch_failed => error.InvalidExe,
+        .elevation_failed => error.ElevatedProcessFailed,
+        .panic => error.Unexpected,
+    };
+}
+
+// Ends the wrapper with the exit code for `err`
+pub fn exit_with(err: anyerror) noreturn {
+    std.process.exit(for_error(err).code());
+}
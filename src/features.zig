@@ -0,0 +1,27 @@
+/////
+// Optional parts of the wrapper a release can build without (the `exclude_features` option), for embedded users who
+// only need the payload installed and launched. Checks are comptime, so an excluded feature's code isn't compiled
+// into the binary at all.
+/////
+
+const build_options = @import("build_options");
+
+pub const Feature = enum {
+    // The `--burrito` commands
+    maintenance,
+    // `--burrito update` and `--burrito make-delta`
+    update,
+    // The install report sent to `telemetry_url`
+    telemetry,
+    // Progress events on `<APP>_PROGRESS_FD`
+    progress,
+};
+
+pub inline fn enabled(comptime feature: Feature) bool {
+    return switch (feature) {
+        .maintenance => build_options.FEATURE_MAINTENANCE,
+        .update => build_options.FEATURE_UPDATE,
+        .telemetry => build_options.FEATURE_TELEMETRY,
+        .progress => build_options.FEATURE_PROGRESS,
+    };
+}
@@ -0,0 +1,60 @@
+/////
+// Flags meant for the wrapper itself rather than the application, e.g. `my-binary --burrito-verbose serve`.
+// They are only recognized before the first application argument, and are removed before the VM sees the rest.
+/////
+
+const std = @import("std");
+
+const logger = @import("logger.zig");
+
+const PREFIX = "--burrito-";
+
+pub const Flags = struct {
+    verbosity: ?logger.Verbosity = null,
+    // Same as the `<APP>_LOG_TARGET` override, e.g. `--burrito-log-target=json`
+    log_target: ?[]const u8 = null,
+    // Same as setting `NO_COLOR`
+    no_color: bool = false,
+    // Report failures as a JSON object on stderr, same as `<APP>_JSON_ERRORS=1`
+    json_errors: bool = false,
+    // Same as `<APP>_PROFILE`, see profile.zig
+    profile: ?[]const u8 = null,
+    // A `.config` file loaded on top of the release's sys.config, same as `<APP>_CONFIG`
+    config: ?[]const u8 = null,
+    // Set on the elevated copy of the wrapper that only installs into this directory (see elevate.zig)
+    elevated_install: ?[]const u8 = null,
+};
+
+// Parses the leading wrapper flags, returns them along with the remaining arguments
+pub fn parse(args: [][]u8, flags: *Flags) [][]u8 {
+    var index: usize = 0;
+    while (index < args.len and std.mem.startsWith(u8, args[index], PREFIX)) : (index += 1) {
+        // Flags with a value are passed as `--burrito-<name>=<value>`
+        const flag = args[index][PREFIX.len..];
+        const separator = std.mem.indexOfScalar(u8, flag, '=');
+        const name = if (separator) |position| flag[0..position] else flag;
+        const value = if (separator) |position| flag[position + 1 ..] else null;
+
+        if (std.mem.eql(u8, name, "quiet")) {
+            flags.verbosity = .quiet;
+        } else if (std.mem.eql(u8, name, "verbose")) {
+            flags.verbosity = .verbose;
+        } else if (std.mem.eql(u8, name, "log-target") and value != null) {
+            flags.log_target = value;
+        } else if (std.mem.eql(u8, name, "no-color")) {
+            flags.no_color = true;
+        } else if (std.mem.eql(u8, name, "json-errors")) {
+            flags.json_errors = true;
+        } else if (std.mem.eql(u8, name, "profile") and value != null) {
+            flags.profile = value;
+        } else if (std.mem.eql(u8, name, "config") and value != null) {
+            flags.config = value;
+        } else if (std.mem.eql(u8, name, "elevated-install") and value != null) {
+            flags.elevated_install = value;
+        } else {
+            logger.warn("Ignoring unknown wrapper flag: {s}", .{args[index]});
+        }
+    }
+
+    return args[index..];
+}
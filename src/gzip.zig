@@ -0,0 +1,38 @@
+/////
+// Writes gzip (RFC 1952) streams, the std library only ships a gzip decompressor.
+/////
+
+const std = @import("std");
+
+const deflate = std.compress.deflate;
+
+const MAX_READ_SIZE = 1000000000;
+
+// Gzip compresses `data` into `writer`
+pub fn compress(allocator: std.mem.Allocator, data: []const u8, writer: anytype) !void {
+    // ID1, ID2, CM (deflate), FLG, MTIME (unset), XFL, OS (unknown)
+    try writer.writeAll(&[_]u8{ 0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff });
+
+    var compressor = try deflate.compressor(allocator, writer, .{});
+    defer compressor.deinit();
+    _ = try compressor.write(data);
+    try compressor.close();
+
+    try writer.writeIntLittle(u32, std.hash.Crc32.hash(data));
+    try writer.writeIntLittle(u32, @truncate(data.len));
+}
+
+// Gzip compresses the file at `src_path` into `dest_path`
+pub fn compress_file(allocator: std.mem.Allocator, src_path: []const u8, dest_path: []const u8) !void {
+    const src_file = try std.fs.openFileAbsolute(src_path, .{});
+    defer src_file.close();
+    const data = try src_file.readToEndAlloc(allocator, MAX_READ_SIZE);
+    defer allocator.free(data);
+
+    const dest_file = try std.fs.createFileAbsolute(dest_path, .{ .truncate = true });
+    defer dest_file.close();
+
+    var buffered = std.io.bufferedWriter(dest_file.writer());
+    try compress(allocator, data, buffered.writer());
+    try buffered.flush();
+}
@@ -0,0 +1,254 @@
+/////
+// Translations of the messages end users see when something goes wrong, in the language of their locale.
+// The language comes from `<APP>_LANG`, then `LC_ALL`, `LC_MESSAGES` and `LANG` (the user's locale on Windows),
+// falling back to English. Releases can add languages or reword messages with the `translations` option, those
+// take precedence over the built-in texts.
+//
+// Texts are templates, `{0}`, `{1}`... are replaced with the arguments given to `format()`.
+// Debug output and maintenance commands are for developers and stay in English.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const exit_codes = @import("exit_codes.zig");
+const metadata = @import("metadata.zig");
+const shutil = @import("shutil.zig");
+
+const MetaStruct = metadata.MetaStruct;
+
+var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+var allocator = arena.allocator();
+
+pub const Key = enum {
+    // What a failure means for the user, one per exit code class (see exit_codes.zig)
+    payload_corrupted,
+    install_dir_unavailable,
+    disk_full,
+    out_of_memory,
+    tampered,
+    launch_failed,
+    elevation_failed,
+    // {0} is the install directory
+    elevation_requested,
+    install_dir_default_failed,
+    // {0} is the installed version
+    reinstalling,
+    support_bundle_hint,
+    // {0} is the path of the bundle
+    support_bundle_written,
+    // {0} is the release name
+    panic,
+    // {0} is the path of the crash log
+    panic_log,
+    // {0} is the `support_url`
+    panic_support,
+};
+
+const Texts = std.enums.EnumFieldStruct(Key, []const u8, null);
+const PartialTexts = std.enums.EnumFieldStruct(Key, ?[]const u8, @as(?[]const u8, null));
+
+const english = Texts{
+    .payload_corrupted = "The application's files are damaged or incomplete. Please download it again.",
+    .install_dir_unavailable = "The folder the application installs into can't be created or written to.",
+    .disk_full = "There is not enough free disk space to install the application.",
+    .out_of_memory = "There is not enough free memory to start the application.",
+    .tampered = "Some of the application's files were changed after it was installed. Run it with `--burrito repair` to restore them.",
+    .launch_failed = "The application's runtime could not be started on this computer.",
+    .elevation_failed = "Administrator rights are needed to install the application here.",
+    .elevation_requested = "Installing into {0} requires administrator rights, requesting elevation...",
+    .install_dir_default_failed = "We could not install this application to the default directory, this may be due to a permission error.",
+    .reinstalling = "The application changed since v{0} was installed, reinstalling",
+    .support_bundle_hint = "Run `--burrito support-bundle` to collect the details of this failure into a zip file for a bug report",
+    .support_bundle_written = "Wrote {0}, please attach it to your bug report",
+    .panic = "{0} ran into an unexpected problem and has to close.",
+    .panic_log = "The details were written to {0}.",
+    .panic_support = "Please report this at {0}.",
+};
+
+const builtin_languages = [_]struct { language: []const u8, texts: PartialTexts }{
+    .{ .language = "de", .texts = .{
+        .payload_corrupted = "Die Dateien der Anwendung sind beschädigt oder unvollständig. Bitte laden Sie sie erneut herunter.",
+        .install_dir_unavailable = "Der Ordner, in den die Anwendung installiert wird, kann nicht angelegt oder beschrieben werden.",
+        .disk_full = "Es ist nicht genug freier Speicherplatz vorhanden, um die Anwendung zu installieren.",
+        .out_of_memory = "Es ist nicht genug freier Arbeitsspeicher vorhanden, um die Anwendung zu starten.",
+        .tampered = "Einige Dateien der Anwendung wurden nach der Installation verändert. Starten Sie sie mit `--burrito repair`, um sie wiederherzustellen.",
+        .launch_failed = "Die Laufzeitumgebung der Anwendung konnte auf diesem Computer nicht gestartet werden.",
+        .elevation_failed = "Für die Installation an diesem Ort werden Administratorrechte benötigt.",
+        .elevation_requested = "Für die Installation nach {0} werden Administratorrechte benötigt, sie werden angefordert...",
+        .install_dir_default_failed = "Die Anwendung konnte nicht im Standardordner installiert werden, möglicherweise fehlen die Berechtigungen.",
+        .reinstalling = "Die Anwendung hat sich seit der Installation von v{0} geändert und wird neu installiert",
+        .support_bundle_hint = "Mit `--burrito support-bundle` werden die Details dieses Fehlers für einen Fehlerbericht in eine ZIP-Datei geschrieben",
+        .support_bundle_written = "{0} wurde geschrieben, bitte hängen Sie die Datei an Ihren Fehlerbericht an",
+        .panic = "{0} ist auf ein unerwartetes Problem gestoßen und muss beendet werden.",
+        .panic_log = "Die Details wurden in {0} gespeichert.",
+        .panic_support = "Bitte melden Sie den Fehler unter {0}.",
+    } },
+    .{ .language = "fr", .texts = .{
+        .payload_corrupted = "Les fichiers de l'application sont endommagés ou incomplets. Veuillez la télécharger à nouveau.",
+        .install_dir_unavailable = "Le dossier d'installation de l'application ne peut pas être créé ou n'est pas accessible en écriture.",
+        .disk_full = "Il n'y a pas assez d'espace disque libre pour installer l'application.",
+        .out_of_memory = "Il n'y a pas assez de mémoire libre pour démarrer l'application.",
+        .tampered = "Certains fichiers de l'application ont été modifiés après son installation. Lancez-la avec `--burrito repair` pour les restaurer.",
+        .launch_failed = "L'environnement d'exécution de l'application n'a pas pu démarrer sur cet ordinateur.",
+        .elevation_failed = "Des droits d'administrateur sont nécessaires pour installer l'application à cet emplacement.",
+        .elevation_requested = "L'installation dans {0} nécessite des droits d'administrateur, demande d'élévation en cours...",
+        .install_dir_default_failed = "L'application n'a pas pu être installée dans le dossier par défaut, probablement à cause d'un problème de permissions.",
+        .reinstalling = "L'application a changé depuis l'installation de la v{0}, réinstallation en cours",
+        .support_bundle_hint = "Lancez `--burrito support-bundle` pour rassembler les détails de cette erreur dans un fichier zip à joindre à un rapport de bug",
+        .support_bundle_written = "{0} a été écrit, veuillez le joindre à votre rapport de bug",
+        .panic = "{0} a rencontré un problème inattendu et doit se fermer.",
+        .panic_log = "Les détails ont été enregistrés dans {0}.",
+        .panic_support = "Veuillez signaler ce problème sur {0}.",
+    } },
+    .{ .language = "es", .texts = .{
+        .payload_corrupted = "Los archivos de la aplicación están dañados o incompletos. Vuelva a descargarla.",
+        .install_dir_unavailable = "No se puede crear la carpeta de instalación de la aplicación o no se puede escribir en ella.",
+        .disk_full = "No hay suficiente espacio libre en el disco para instalar la aplicación.",
+        .out_of_memory = "No hay suficiente memoria libre para iniciar la aplicación.",
+        .tampered = "Algunos archivos de la aplicación se modificaron después de instalarla. Ejecútela con `--burrito repair` para restaurarlos.",
+        .launch_failed = "No se pudo iniciar el entorno de ejecución de la aplicación en este equipo.",
+        .elevation_failed = "Se necesitan permisos de administrador para instalar la aplicación en esta ubicación.",
+        .elevation_requested = "Instalar en {0} requiere permisos de administrador, solicitando elevación...",
+        .install_dir_default_failed = "No se pudo instalar la aplicación en la carpeta predeterminada, puede deberse a un problema de permisos.",
+        .reinstalling = "La aplicación cambió desde que se instaló la v{0}, reinstalando",
+        .support_bundle_hint = "Ejecute `--burrito support-bundle` para reunir los detalles de este error en un archivo zip para un informe de errores",
+        .support_bundle_written = "Se escribió {0}, adjúntelo a su informe de errores",
+        .panic = "{0} encontró un problema inesperado y debe cerrarse.",
+        .panic_log = "Los detalles se guardaron en {0}.",
+        .panic_support = "Informe de este problema en {0}.",
+    } },
+};
+
+// Windows `GetUserDefaultLocaleName()`, e.g. `de-DE`
+const LOCALE_NAME_MAX_LENGTH = 85;
+extern "kernel32" fn GetUserDefaultLocaleName(name: [*]u16, length: c_int) callconv(std.os.windows.WINAPI) c_int;
+
+var release_translations: []const metadata.Translation = &.{};
+// e.g. `pt_BR`, or only `pt`
+var locale: ?[]const u8 = null;
+var locale_checked = false;
+
+// Picks up the release's own translations once the metadata is parsed, built-in texts are used until then
+pub fn init(meta: *const MetaStruct) void {
+    release_translations = meta.translations;
+}
+
+pub fn text(key: Key) []const u8 {
+    const full_locale = get_locale() orelse return english_text(key);
+    const language = full_locale[0 .. std.mem.indexOfScalar(u8, full_locale, '_') orelse full_locale.len];
+
+    // Most specific first, the release's texts before ours
+    return find_release_text(full_locale, key) orelse
+        find_release_text(language, key) orelse
+        find_builtin_text(language, key) orelse
+        find_release_text("en", key) orelse
+        english_text(key);
+}
+
+// The text for `key` with `{0}`, `{1}`... replaced by `args`
+pub fn format(key: Key, args: []const []const u8) []const u8 {
+    const template = text(key);
+    var result = std.ArrayList(u8).init(allocator);
+
+    var i: usize = 0;
+    while (i < template.len) {
+        if (template[i] == '{') {
+            if (std.mem.indexOfScalarPos(u8, template, i, '}')) |end| {
+                if (std.fmt.parseInt(usize, template[i + 1 .. end], 10)) |index| {
+                    if (index < args.len) {
+                        result.appendSlice(args[index]) catch return template;
+                        i = end + 1;
+                        continue;
+                    }
+                } else |_| {}
+            }
+        }
+        result.append(template[i]) catch return template;
+        i += 1;
+    }
+
+    return result.items;
+}
+
+// What a failure means for the user, null for failures without a class of their own
+pub fn explain_failure(err: anyerror) ?[]const u8 {
+    return switch (exit_codes.for_error(err)) {
+        .failure, .panic => null,
+        inline else => |class| text(@field(Key, @tagName(class))),
+    };
+}
+
+fn english_text(key: Key) []const u8 {
+    return switch (key) {
+        inline else => |k| @field(english, @tagName(k)),
+    };
+}
+
+fn find_builtin_text(language: []const u8, key: Key) ?[]const u8 {
+    for (builtin_languages) |builtin_language| {
+        if (std.mem.eql(u8, builtin_language.language, language)) {
+            return switch (key) {
+                inline else => |k| @field(builtin_language.texts, @tagName(k)),
+            };
+        }
+    }
+    return null;
+}
+
+fn find_release_text(wanted_locale: []const u8, key: Key) ?[]const u8 {
+    for (release_translations) |translation| {
+        if (std.mem.eql(u8, translation.locale, wanted_locale) and std.mem.eql(u8, translation.key, @tagName(key))) {
+            return translation.text;
+        }
+    }
+    return null;
+}
+
+fn get_locale() ?[]const u8 {
+    if (locale_checked) {
+        return locale;
+    }
+    locale_checked = true;
+
+    const value = shutil.get_release_env(allocator, "LANG") orelse
+        get_env_locale("LC_ALL") orelse
+        get_env_locale("LC_MESSAGES") orelse
+        get_env_locale("LANG") orelse
+        get_system_locale() orelse
+        return null;
+
+    locale = normalize(value);
+    return locale;
+}
+
+fn get_env_locale(comptime name: []const u8) ?[]const u8 {
+    const value = std.process.getEnvVarOwned(allocator, name) catch return null;
+    return if (value.len > 0) value else null;
+}
+
+fn get_system_locale() ?[]const u8 {
+    if (builtin.os.tag != .windows) {
+        return null;
+    }
+
+    var buffer: [LOCALE_NAME_MAX_LENGTH]u16 = undefined;
+    const length = GetUserDefaultLocaleName(&buffer, buffer.len);
+    if (length <= 1) {
+        return null;
+    }
+    // The length includes the terminating null
+    return std.unicode.utf16leToUtf8Alloc(allocator, buffer[0..@intCast(length - 1)]) catch null;
+}
+
+// `de_DE.UTF-8@euro` and `de-DE` both become `de_DE`, the `C` and `POSIX` locales mean English
+fn normalize(value: []const u8) ?[]const u8 {
+    const end = std.mem.indexOfAny(u8, value, ".@") orelse value.len;
+    const name = allocator.dupe(u8, value[0..end]) catch return null;
+    std.mem.replaceScalar(u8, name, '-', '_');
+
+    if (name.len == 0 or std.mem.eql(u8, name, "C") or std.mem.eql(u8, name, "POSIX")) {
+        return null;
+    }
+    return name;
+}
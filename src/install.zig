@@ -1,9 +1,17 @@
 const std = @import("std");
+const build_options = @import("build_options");
+const dir_template = @import("dir_template.zig");
 const metadata = @import("metadata.zig");
 const MetaStruct = metadata.MetaStruct;
 
 const MAX_READ_SIZE = 1000000000;
 
+// Length of the hash used as the directory name with `short_install_dir`
+const SHORT_NAME_LEN = 12;
+const NAME_MARKER_EXT = ".name";
+// Holds the SHA-256 of the compressed payload an install was made from
+const PAYLOAD_HASH_NAME = "_payload.sha256";
+
 pub const Install = struct {
     metadata_file_path: []const u8 = undefined,
     base_install_dir_path: []const u8 = undefined,
@@ -42,3 +50,71 @@ pub fn load_install_from_path(allocator: std.mem.Allocator, full_install_path: [
         .version = parsed_version,
     };
 }
+
+// The full install directory name, e.g. `myapp_erts-15.2.1_1.4.0` (see dir_template.zig)
+pub fn get_full_dir_name(allocator: std.mem.Allocator, meta: *const MetaStruct) ![]const u8 {
+    return try dir_template.render(allocator, build_options.INSTALL_DIR_TEMPLATE, meta);
+}
+
+// Whether the install directory at `path` is one of this release's, of any version
+pub fn is_own_dir(allocator: std.mem.Allocator, meta: *const MetaStruct, path: []const u8) bool {
+    return dir_template.matches(build_options.INSTALL_DIR_TEMPLATE, meta, get_full_name(allocator, path));
+}
+
+// The directory name for an install, with `short_install_dir` a short hash of the full name that keeps deep OTP paths
+// under the length limits of old Windows tooling
+pub fn get_dir_name(allocator: std.mem.Allocator, meta: *const MetaStruct) ![]const u8 {
+    const full_name = try get_full_dir_name(allocator, meta);
+    if (!meta.short_install_dir) {
+        return full_name;
+    }
+
+    var digest: [std.crypto.hash.sha2.Sha256.digest_length]u8 = undefined;
+    std.crypto.hash.sha2.Sha256.hash(full_name, &digest, .{});
+    const hex = try std.fmt.allocPrint(allocator, "{s}", .{std.fmt.fmtSliceHexLower(&digest)});
+    return hex[0..SHORT_NAME_LEN];
+}
+
+fn get_name_marker_path(allocator: std.mem.Allocator, install_dir: []const u8) ![]const u8 {
+    return try std.fmt.allocPrint(allocator, "{s}" ++ NAME_MARKER_EXT, .{install_dir});
+}
+
+// Records the full name of a short install directory in `<dir>.name`, next to it
+pub fn write_name_marker(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) !void {
+    const file = try std.fs.createFileAbsolute(try get_name_marker_path(allocator, install_dir), .{ .truncate = true });
+    defer file.close();
+    try file.writeAll(try get_full_dir_name(allocator, meta));
+}
+
+// The full name of an install directory, from its marker if it has a short name
+pub fn get_full_name(allocator: std.mem.Allocator, install_dir: []const u8) []const u8 {
+    const marker_path = get_name_marker_path(allocator, install_dir) catch return std.fs.path.basename(install_dir);
+    const file = std.fs.openFileAbsolute(marker_path, .{}) catch return std.fs.path.basename(install_dir);
+    defer file.close();
+
+    const content = file.readToEndAlloc(allocator, std.fs.MAX_PATH_BYTES) catch return std.fs.path.basename(install_dir);
+    return std.mem.trim(u8, content, " \r\n");
+}
+
+// Deletes an install directory, along with its name marker if it has one
+pub fn delete(allocator: std.mem.Allocator, install_dir: []const u8) !void {
+    try std.fs.deleteTreeAbsolute(install_dir);
+    const marker_path = try get_name_marker_path(allocator, install_dir);
+    std.fs.deleteFileAbsolute(marker_path) catch {};
+}
+
+// Records which payload the install was made from, see `is_payload_current()`
+pub fn write_payload_hash(allocator: std.mem.Allocator, install_dir: []const u8, payload_hash: []const u8) !void {
+    const path = try std.fs.path.join(allocator, &[_][]const u8{ install_dir, PAYLOAD_HASH_NAME });
+    const file = try std.fs.createFileAbsolute(path, .{ .truncate = true });
+    defer file.close();
+    try file.writeAll(payload_hash);
+}
+
+// A rebuild without a version bump keeps the same install directory, only the payload hash tells the installs apart.
+// Installs made before the hash was recorded count as outdated, and are replaced once.
+pub fn is_payload_current(allocator: std.mem.Allocator, install_dir: []const u8, payload_hash: []const u8) bool {
+    const path = std.fs.path.join(allocator, &[_][]const u8{ install_dir, PAYLOAD_HASH_NAME }) catch return false;
+    const content = std.fs.cwd().readFileAlloc(allocator, path, 1024) catch return false;
+    return std.mem.eql(u8, std.mem.trim(u8, content, " \r\n"), payload_hash);
+}
@@ -0,0 +1,79 @@
+/////
+// Sends wrapper log messages to the systemd journal using its native datagram protocol,
+// so they show up with structured fields under `journalctl -u <unit>`.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const os = std.os;
+
+const SOCKET_PATH = "/run/systemd/journal/socket";
+
+var socket: ?os.socket_t = null;
+
+// Extra fields attached to every message
+pub const Fields = struct {
+    release_name: []const u8,
+    release_version: []const u8,
+    phase: []const u8,
+};
+
+// True if systemd connected our stderr to the journal. `JOURNAL_STREAM` is inherited by everything a service
+// starts, e.g. terminals in a systemd user session, so it only counts if it names our actual stderr: it holds
+// the `<device>:<inode>` of the stream systemd set up.
+pub fn is_journal_stream() bool {
+    if (builtin.os.tag != .linux) {
+        return false;
+    } else {
+        const value = std.os.getenv("JOURNAL_STREAM") orelse return false;
+        const separator = std.mem.indexOfScalar(u8, value, ':') orelse return false;
+        const device = std.fmt.parseInt(u64, value[0..separator], 10) catch return false;
+        const inode = std.fmt.parseInt(u64, value[separator + 1 ..], 10) catch return false;
+
+        const stat = os.fstat(os.STDERR_FILENO) catch return false;
+        return stat.dev == device and stat.ino == inode;
+    }
+}
+
+fn connect() ?os.socket_t {
+    if (socket) |fd| {
+        return fd;
+    }
+
+    const address = std.net.Address.initUnix(SOCKET_PATH) catch return null;
+    const fd = os.socket(os.AF.UNIX, os.SOCK.DGRAM | os.SOCK.CLOEXEC, 0) catch return null;
+    os.connect(fd, &address.any, address.getOsSockLen()) catch {
+        os.close(fd);
+        return null;
+    };
+
+    socket = fd;
+    return fd;
+}
+
+// Sends a single entry to the journal, `priority` uses the syslog severity levels
+pub fn send(allocator: std.mem.Allocator, priority: u8, fields: Fields, message: []const u8) void {
+    if (builtin.os.tag != .linux) {
+        return;
+    } else {
+        const fd = connect() orelse return;
+
+        var entry = std.ArrayList(u8).init(allocator);
+        defer entry.deinit();
+        var writer = entry.writer();
+
+        // MESSAGE uses the length-prefixed form so newlines in the message are kept intact
+        writer.writeAll("MESSAGE\n") catch return;
+        writer.writeIntLittle(u64, message.len) catch return;
+        writer.print("{s}\n", .{message}) catch return;
+
+        writer.print("PRIORITY={}\n", .{priority}) catch return;
+        writer.print("SYSLOG_IDENTIFIER={s}\n", .{fields.release_name}) catch return;
+        writer.print("RELEASE_NAME={s}\n", .{fields.release_name}) catch return;
+        writer.print("RELEASE_VERSION={s}\n", .{fields.release_version}) catch return;
+        writer.print("BURRITO_PHASE={s}\n", .{fields.phase}) catch return;
+
+        _ = os.send(fd, entry.items, 0) catch {};
+    }
+}
@@ -0,0 +1,125 @@
+/////
+// Reads the distribution cookie from the OS credential store instead of the plaintext `releases/COOKIE` file:
+// the Keychain on MacOS, the Credential Manager on Windows and the Secret Service (libsecret) on Linux.
+// The secret is stored under a service name (`burrito.<app>` by default) with `cookie` as the account.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const logger = @import("logger.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const log = std.log;
+const windows = std.os.windows;
+
+const CREDENTIALW = extern struct {
+    Flags: windows.DWORD,
+    Type: windows.DWORD,
+    TargetName: ?windows.LPWSTR,
+    Comment: ?windows.LPWSTR,
+    LastWritten: windows.FILETIME,
+    CredentialBlobSize: windows.DWORD,
+    CredentialBlob: ?[*]u8,
+    Persist: windows.DWORD,
+    AttributeCount: windows.DWORD,
+    Attributes: ?*anyopaque,
+    TargetAlias: ?windows.LPWSTR,
+    UserName: ?windows.LPWSTR,
+};
+
+extern "advapi32" fn CredReadW(TargetName: windows.LPCWSTR, Type: windows.DWORD, Flags: windows.DWORD, Credential: **CREDENTIALW) callconv(windows.WINAPI) windows.BOOL;
+extern "advapi32" fn CredFree(Buffer: *anyopaque) callconv(windows.WINAPI) void;
+
+const CRED_TYPE_GENERIC: windows.DWORD = 1;
+const ACCOUNT = "cookie";
+
+pub fn get_service(allocator: std.mem.Allocator, meta: *const MetaStruct) ![]const u8 {
+    return meta.cookie_keychain_service orelse try std.fmt.allocPrint(allocator, "burrito.{s}", .{meta.app_name});
+}
+
+// Returns the cookie, or null if there is no entry for it
+pub fn get_cookie(allocator: std.mem.Allocator, meta: *const MetaStruct) !?[]u8 {
+    const service = try get_service(allocator, meta);
+
+    const cookie = switch (builtin.os.tag) {
+        .windows => try read_credential(allocator, service),
+        .macos => try run_lookup(allocator, &[_][]const u8{ "security", "find-generic-password", "-s", service, "-a", ACCOUNT, "-w" }),
+        .linux, .freebsd => try run_lookup(allocator, &[_][]const u8{ "secret-tool", "lookup", "service", service, "account", ACCOUNT }),
+        else => return error.Unsupported,
+    };
+
+    if (cookie) |value| {
+        defer std.crypto.utils.secureZero(u8, value);
+        const trimmed = std.mem.trim(u8, value, " \r\n");
+        if (trimmed.len > 0) {
+            return try allocator.dupe(u8, trimmed);
+        }
+    }
+    return null;
+}
+
+// Tells the user how to store the cookie, for when it's missing
+pub fn print_store_hint(allocator: std.mem.Allocator, meta: *const MetaStruct) void {
+    const service = get_service(allocator, meta) catch return;
+
+    switch (builtin.os.tag) {
+        .windows => logger.info("Store it with: cmdkey /generic:{s} /user:{s} /pass:<cookie>", .{ service, ACCOUNT }),
+        .macos => logger.info("Store it with: security add-generic-password -s {s} -a {s} -w <cookie>", .{ service, ACCOUNT }),
+        else => logger.info("Store it with: secret-tool store --label=\"{s}\" service {s} account {s}", .{ service, service, ACCOUNT }),
+    }
+}
+
+// Runs a credential store CLI, a non-zero exit means there's no such entry
+fn run_lookup(allocator: std.mem.Allocator, argv: []const []const u8) !?[]u8 {
+    const result = std.ChildProcess.exec(.{ .allocator = allocator, .argv = argv }) catch |err| {
+        log.debug("Could not run {s}: {!}", .{ argv[0], err });
+        return err;
+    };
+
+    return switch (result.term) {
+        .Exited => |code| if (code == 0) result.stdout else null,
+        else => null,
+    };
+}
+
+fn read_credential(allocator: std.mem.Allocator, service: []const u8) !?[]u8 {
+    if (builtin.os.tag != .windows) {
+        return null;
+    } else {
+        const target_w = try std.unicode.utf8ToUtf16LeWithNull(allocator, service);
+
+        var credential: *CREDENTIALW = undefined;
+        if (CredReadW(target_w, CRED_TYPE_GENERIC, 0, &credential) == 0) {
+            return null;
+        }
+        defer CredFree(credential);
+
+        const blob = credential.CredentialBlob orelse return null;
+        const bytes = blob[0..credential.CredentialBlobSize];
+        defer std.crypto.utils.secureZero(u8, bytes);
+
+        // `cmdkey` and the Credential Manager UI store the password as UTF-16, a cookie is plain ASCII
+        if (bytes.len % 2 == 0 and is_ascii_utf16(bytes)) {
+            const wide = try allocator.alloc(u16, bytes.len / 2);
+            defer std.crypto.utils.secureZero(u16, wide);
+            for (wide, 0..) |*char, i| {
+                char.* = std.mem.readIntLittle(u16, bytes[i * 2 ..][0..2]);
+            }
+            if (std.unicode.utf16leToUtf8Alloc(allocator, wide)) |utf8| {
+                return utf8;
+            } else |_| {}
+        }
+        return try allocator.dupe(u8, bytes);
+    }
+}
+
+fn is_ascii_utf16(bytes: []const u8) bool {
+    var i: usize = 1;
+    while (i < bytes.len) : (i += 2) {
+        if (bytes[i] != 0) {
+            return false;
+        }
+    }
+    return true;
+}
@@ -0,0 +1,99 @@
+/////
+// Detects which C library the host uses, so a release built for the other one fails with a clear explanation
+// instead of the kernel's baffling "No such file or directory" when the ERTS dynamic loader is missing.
+// Only relevant on Linux.
+/////
+
+const std = @import("std");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const elf = std.elf;
+const fs = std.fs;
+const log = std.log;
+
+pub const Libc = enum {
+    gnu,
+    musl,
+};
+
+// Returns the dynamic loader (PT_INTERP) an ELF executable asks for, or null for static executables
+pub fn read_interpreter(allocator: std.mem.Allocator, path: []const u8) !?[]const u8 {
+    const file = try fs.openFileAbsolute(path, .{});
+    defer file.close();
+
+    const header = try elf.Header.read(file);
+    var phdrs = header.program_header_iterator(file);
+    while (try phdrs.next()) |phdr| {
+        if (phdr.p_type != elf.PT_INTERP) {
+            continue;
+        }
+
+        const interpreter = try allocator.alloc(u8, @intCast(phdr.p_filesz));
+        _ = try file.preadAll(interpreter, phdr.p_offset);
+        return std.mem.trimRight(u8, interpreter, "\x00");
+    }
+
+    return null;
+}
+
+fn libc_from_interpreter(interpreter: []const u8) ?Libc {
+    if (std.mem.indexOf(u8, interpreter, "ld-musl") != null) {
+        return .musl;
+    }
+    if (std.mem.indexOf(u8, interpreter, "ld-linux") != null) {
+        return .gnu;
+    }
+    return null;
+}
+
+// Looks at what the system shell links against, every distribution ships one
+pub fn detect_host(allocator: std.mem.Allocator) ?Libc {
+    const interpreter = (read_interpreter(allocator, "/bin/sh") catch return null) orelse return null;
+    return libc_from_interpreter(interpreter);
+}
+
+// The libc a release was built for, from its target triple (e.g. `x86_64-linux-musl`, `arm-linux-gnueabihf`)
+pub fn from_target(target: []const u8) ?Libc {
+    if (std.mem.indexOf(u8, target, "-linux-musl") != null) {
+        return .musl;
+    }
+    if (std.mem.indexOf(u8, target, "-linux-gnu") != null) {
+        return .gnu;
+    }
+    return null;
+}
+
+// Checks that the loader `erlexec` needs is present, logs an explanation and returns an error if it isn't
+pub fn check_erts(allocator: std.mem.Allocator, erts_bin_path: []const u8, meta: *const MetaStruct) !void {
+    const erlexec_path = try fs.path.join(allocator, &[_][]const u8{ erts_bin_path, "erlexec" });
+    const interpreter = (read_interpreter(allocator, erlexec_path) catch return) orelse return;
+
+    fs.accessAbsolute(interpreter, .{}) catch {
+        const built_for = if (meta.target) |target| from_target(target) else libc_from_interpreter(interpreter);
+        const host = detect_host(allocator);
+
+        log.err("This application was built for {s}-based Linux, but the dynamic loader it needs ({s}) is missing.", .{ libc_name(built_for), interpreter });
+        if (host) |host_libc| {
+            log.err("This machine uses {s}. Rebuild the release with a target using `libc: :{s}`.", .{ libc_name(host_libc), @tagName(host_libc) });
+        }
+        if (built_for == .gnu and host == .musl) {
+            log.err("On Alpine Linux, installing the `gcompat` package may also work.", .{});
+        }
+        return error.LibcMismatch;
+    };
+
+    // The loader exists, but a glibc release on a musl host only works through a compatibility layer
+    const built_for = libc_from_interpreter(interpreter);
+    const host = detect_host(allocator);
+    if (built_for != null and host != null and built_for != host) {
+        log.warn("This application was built for {s}-based Linux, but this machine uses {s}, expect problems.", .{ libc_name(built_for), libc_name(host) });
+    }
+}
+
+fn libc_name(libc: ?Libc) []const u8 {
+    return if (libc) |value| switch (value) {
+        .gnu => "glibc",
+        .musl => "musl",
+    } else "an unknown libc";
+}
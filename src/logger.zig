@@ -1,41 +1,377 @@
 const std = @import("std");
+const build_options = @import("build_options");
+
+const eventlog = @import("eventlog.zig");
+const exit_codes = @import("exit_codes.zig");
+const journald = @import("journald.zig");
+const syslog = @import("syslog.zig");
 
 var log = std.log;
 var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
 var allocator = arena.allocator();
+// The payload is extracted by several threads, and neither the arena nor the history is thread-safe. Every use of
+// either holds this, the functions that expect it held say so.
+var mutex = std.Thread.Mutex{};
+
+pub const Target = enum {
+    // Human readable output on stdout/stderr
+    console,
+    // Messages go to the local syslog daemon instead of the console
+    syslog,
+    // Messages go to the systemd journal with structured fields
+    journald,
+    // Messages are printed to the console, warnings and errors are also reported to the Windows Event Log
+    eventlog,
+    // One JSON object per line on stderr, for log aggregators, along with structured events (see `event()`)
+    json,
+};
+
+var target: Target = .console;
+
+pub const Verbosity = enum {
+    // Only warnings and errors
+    quiet,
+    // Progress messages, warnings and errors
+    normal,
+    // Everything, including debug messages
+    verbose,
+};
+
+// Development builds are chatty by default
+var verbosity: Verbosity = if (build_options.IS_PROD) .normal else .verbose;
+
+// Whether we, and the VM after us, may print ANSI colors (see `shutil.wants_color()`)
+var color = true;
+
+// Attached to structured log entries
+var release_version: []const u8 = "";
+var phase: []const u8 = "startup";
+var phase_started: i64 = 0;
+
+// With `--burrito-json-errors`, a failure is also reported as one JSON object on stderr, for tools wrapping the binary
+var json_errors = false;
+var failure_reported = false;
+var last_error_message: ?[]const u8 = null;
+
+// Zig errors carry no data, so the site that fails records what it was doing and on which path here (see
+// `set_error_context()`), for the final error message and the JSON failure report
+pub const ErrorContext = struct {
+    // What failed, e.g. "writing" or "creating the directory"
+    operation: []const u8,
+    path: ?[]const u8 = null,
+};
+var error_context: ?ErrorContext = null;
+
+// Everything logged during this run, so a support bundle can include it. Debug messages only when they're printed,
+// as some carry details (paths, arguments) that shouldn't end up in a bundle unasked
+const MAX_HISTORY_SIZE = 1024 * 1024;
+var history = std.ArrayList(u8).init(allocator);
+
+// Selects where wrapper log messages are written to, `name` comes from the metadata or an env override
+pub fn set_target(name: []const u8) void {
+    target = std.meta.stringToEnum(Target, name) orelse blk: {
+        warn("Unknown log target `{s}`, using the console", .{name});
+        break :blk .console;
+    };
+
+    // When systemd is capturing our output anyway, send it to the journal with proper fields instead
+    if (target == .console and journald.is_journal_stream()) {
+        target = .journald;
+    }
+}
+
+// For skipping work that only feeds debug messages
+pub fn is_verbose() bool {
+    return verbosity == .verbose;
+}
+
+pub fn set_verbosity(new_verbosity: Verbosity) void {
+    verbosity = new_verbosity;
+}
+
+// Parses a verbosity name from an env override, ignoring unknown values
+pub fn set_verbosity_name(name: []const u8) void {
+    verbosity = std.meta.stringToEnum(Verbosity, name) orelse blk: {
+        warn("Unknown verbosity `{s}`, expected quiet, normal or verbose", .{name});
+        break :blk verbosity;
+    };
+}
+
+pub fn set_color(enabled: bool) void {
+    color = enabled;
+}
+
+pub fn color_enabled() bool {
+    return color;
+}
+
+pub fn set_release_version(version: []const u8) void {
+    release_version = version;
+}
+
+// Marks which part of the wrapper's lifecycle we're in (startup, install, launch, maintenance)
+pub fn set_phase(new_phase: []const u8) void {
+    phase = new_phase;
+    phase_started = std.time.milliTimestamp();
+}
+
+pub fn set_json_errors(enabled: bool) void {
+    json_errors = enabled;
+}
+
+pub fn json_errors_enabled() bool {
+    return json_errors;
+}
+
+// Records the operation and path a failure happened at. Only the first one is kept: with several extraction threads,
+// the later failures are usually the other threads giving up.
+pub fn set_error_context(operation: []const u8, path: ?[]const u8) void {
+    mutex.lock();
+    defer mutex.unlock();
+    if (error_context != null) return;
+
+    const owned_path = if (path) |p| allocator.dupe(u8, p) catch return else null;
+    error_context = .{ .operation = operation, .path = owned_path };
+}
+
+pub fn get_error_context() ?ErrorContext {
+    mutex.lock();
+    defer mutex.unlock();
+    return error_context;
+}
+
+// The last thing printed before the wrapper exits with an error, with where it happened if we know
+pub fn report_final_error(failure: anyerror) void {
+    const context = get_error_context() orelse {
+        err("Failed: {s}", .{@errorName(failure)});
+        return;
+    };
+    if (context.path) |path| {
+        err("{s} {s} failed: {s}", .{ capitalized(context.operation), path, @errorName(failure) });
+    } else {
+        err("{s} failed: {s}", .{ capitalized(context.operation), @errorName(failure) });
+    }
+}
+
+fn capitalized(text: []const u8) []const u8 {
+    if (text.len == 0 or !std.ascii.isLower(text[0])) return text;
+    mutex.lock();
+    defer mutex.unlock();
+    const copy = allocator.dupe(u8, text) catch return text;
+    copy[0] = std.ascii.toUpper(copy[0]);
+    return copy;
+}
+
+// Writes the JSON failure report, once per run: the first caller has the most specific context.
+// `code` is the snake_cased error name, e.g. `payload_corrupted`, and is kept stable across releases.
+pub fn report_failure(failure: anyerror, context: anytype) void {
+    if (!json_errors or failure_reported) return;
+    failure_reported = true;
+
+    const failure_context = get_error_context();
+    const report = .{
+        .@"error" = .{
+            .code = get_error_code(failure),
+            .name = @errorName(failure),
+            .exit_code = exit_codes.for_error(failure).code(),
+            .message = last_error_message orelse @errorName(failure),
+            .operation = if (failure_context) |c| c.operation else null,
+            .path = if (failure_context) |c| c.path else null,
+            .release = build_options.RELEASE_NAME,
+            .version = release_version,
+            .phase = phase,
+            .context = context,
+        },
+    };
+
+    const stderr_lock = std.debug.getStderrMutex();
+    stderr_lock.lock();
+    defer stderr_lock.unlock();
+    var stderr = std.io.getStdErr().writer();
+    std.json.stringify(report, .{}, stderr) catch return;
+    stderr.writeByte('\n') catch {};
+}
+
+fn get_error_code(failure: anyerror) []const u8 {
+    const name = @errorName(failure);
+    mutex.lock();
+    defer mutex.unlock();
+    var code = std.ArrayList(u8).init(allocator);
+    for (name, 0..) |char, i| {
+        if (std.ascii.isUpper(char) and i > 0) {
+            code.append('_') catch return name;
+        }
+        code.append(std.ascii.toLower(char)) catch return name;
+    }
+    return code.items;
+}
+
+// Emits a structured event with arbitrary fields (paths, durations, error codes) when logging JSON, ignored otherwise
+pub fn event(comptime name: []const u8, fields: anytype) void {
+    if (target != .json) return;
+    write_json("info", name, fields);
+}
+
+fn write_json(level: []const u8, message: []const u8, fields: anytype) void {
+    const now = std.time.milliTimestamp();
+    const entry = .{
+        .ts = now,
+        .level = level,
+        .release = build_options.RELEASE_NAME,
+        .version = release_version,
+        .phase = phase,
+        .phase_ms = if (phase_started == 0) 0 else now - phase_started,
+        .message = message,
+        .fields = fields,
+    };
+
+    const stderr_lock = std.debug.getStderrMutex();
+    stderr_lock.lock();
+    defer stderr_lock.unlock();
+    var stderr = std.io.getStdErr().writer();
+    std.json.stringify(entry, .{}, stderr) catch return;
+    stderr.writeByte('\n') catch {};
+}
+
+pub fn get_history() []const u8 {
+    return history.items;
+}
+
+// Expects `mutex` to be held
+fn remember(comptime label: []const u8, message: []const u8) void {
+    if (history.items.len >= MAX_HISTORY_SIZE) return;
+    history.writer().print("[{s}] [{s}] {s}\n", .{ label, phase, message }) catch {};
+}
 
 pub fn query(comptime message: []const u8, args: anytype) void {
     var stdout = std.io.getStdOut().writer();
+    mutex.lock();
+    defer mutex.unlock();
     var out_string = std.fmt.allocPrint(allocator, message, args) catch { return; };
     stdout.print("[?] {s}", .{out_string}) catch {};
 }
 
 pub fn log_stderr(comptime message: []const u8, args: anytype) void {
     var stderr = std.io.getStdErr().writer();
+    mutex.lock();
+    defer mutex.unlock();
     var out_string = std.fmt.allocPrint(allocator, message, args) catch { return; };
+    remember("l", out_string);
+    if (verbosity == .quiet) return;
+    if (forward(.info, out_string)) return;
     stderr.print("[l] {s}\n", .{out_string}) catch {};
 }
 
 pub fn info(comptime message: []const u8, args: anytype) void {
     var stdout = std.io.getStdOut().writer();
+    mutex.lock();
+    defer mutex.unlock();
     var out_string = std.fmt.allocPrint(allocator, message, args) catch { return; };
+    remember("i", out_string);
+    if (verbosity == .quiet) return;
+    if (forward(.info, out_string)) return;
     stdout.print("[i] {s}\n", .{out_string}) catch {};
 }
 
 pub fn warn(comptime message: []const u8, args: anytype) void {
     var stderr = std.io.getStdErr().writer();
+    mutex.lock();
+    defer mutex.unlock();
     var out_string = std.fmt.allocPrint(allocator, message, args) catch { return; };
+    remember("w", out_string);
+    if (forward(.warning, out_string)) return;
     stderr.print("[w] {s}\n", .{out_string}) catch {};
 }
 
 pub fn err(comptime message: []const u8, args: anytype) void {
     var stderr = std.io.getStdErr().writer();
+    mutex.lock();
+    defer mutex.unlock();
     var out_string = std.fmt.allocPrint(allocator, message, args) catch { return; };
+    remember("!", out_string);
+    last_error_message = out_string;
+    if (forward(.err, out_string)) return;
     stderr.print("[!] {s}\n", .{out_string}) catch {};
 }
 
 pub fn crit(comptime message: []const u8, args: anytype) void {
     var stderr = std.io.getStdErr().writer();
+    mutex.lock();
+    defer mutex.unlock();
     var out_string = std.fmt.allocPrint(allocator, message, args) catch { return; };
+    remember("!!", out_string);
+    last_error_message = out_string;
+    if (forward(.critical, out_string)) return;
     stderr.print("[!!] {s}\n", .{out_string}) catch {};
 }
+
+// Hooked into `std.log` from the wrapper, so messages logged by the std library and our own modules
+// are routed through the same target as the functions above
+pub fn std_log_fn(
+    comptime message_level: std.log.Level,
+    comptime scope: @TypeOf(.EnumLiteral),
+    comptime format: []const u8,
+    args: anytype,
+) void {
+    mutex.lock();
+    defer mutex.unlock();
+
+    const enabled = switch (message_level) {
+        .err, .warn => true,
+        .info => verbosity != .quiet,
+        .debug => verbosity == .verbose,
+    };
+    if (message_level == .debug and !enabled) return;
+
+    if (std.fmt.allocPrint(allocator, format, args)) |history_string| {
+        remember(message_level.asText(), history_string);
+    } else |_| {}
+
+    if (!enabled) return;
+
+    if (target != .console) {
+        const out_string = std.fmt.allocPrint(allocator, format, args) catch { return; };
+        const severity: syslog.Severity = switch (message_level) {
+            .err => .err,
+            .warn => .warning,
+            .info => .info,
+            .debug => .debug,
+        };
+        if (forward(severity, out_string)) return;
+    }
+
+    std.log.defaultLog(message_level, scope, format, args);
+}
+
+// Sends a message to a non-console target, returns true if the message was handled. Expects `mutex` to be held
+fn forward(severity: syslog.Severity, message: []const u8) bool {
+    switch (target) {
+        .console => return false,
+        .syslog => {
+            syslog.send(severity, build_options.RELEASE_NAME, message);
+            return true;
+        },
+        .journald => {
+            const fields = journald.Fields{
+                .release_name = build_options.RELEASE_NAME,
+                .release_version = release_version,
+                .phase = phase,
+            };
+            journald.send(allocator, @intFromEnum(severity), fields, message);
+            return true;
+        },
+        .eventlog => {
+            switch (severity) {
+                .critical, .err => eventlog.report(allocator, .err, build_options.RELEASE_NAME, message),
+                .warning => eventlog.report(allocator, .warning, build_options.RELEASE_NAME, message),
+                .info, .debug => {},
+            }
+            // The Event Log is in addition to the console, not instead of it
+            return false;
+        },
+        .json => {
+            write_json(@tagName(severity), message, .{});
+            return true;
+        },
+    }
+}
@@ -0,0 +1,104 @@
+/////
+// Hardened Linux systems use SELinux or AppArmor policies that can deny executing anything from home directories,
+// which fails the launch with EACCES even though the ERTS executables have their +x bits.
+// A release can name an install location the policy allows, label the extracted files for SELinux, and when the
+// launch is denied anyway we say which policy is the likely cause.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const logger = @import("logger.zig");
+
+const fs = std.fs;
+const log = std.log;
+
+const INSTALL_DIR_ENV = blk: {
+    var name: [build_options.RELEASE_NAME.len]u8 = undefined;
+    _ = std.ascii.upperString(&name, build_options.RELEASE_NAME);
+    break :blk name ++ "_INSTALL_DIR";
+};
+
+pub const Policy = enum {
+    selinux,
+    apparmor,
+};
+
+// Returns the mandatory access control system that is enforcing, if any
+pub fn get_active() ?Policy {
+    if (builtin.os.tag != .linux) {
+        return null;
+    }
+
+    if (read_flag("/sys/fs/selinux/enforce", '1')) {
+        return .selinux;
+    }
+    // AppArmor is enabled on most Ubuntu systems, it only matters if we run under a profile
+    if (read_flag("/sys/module/apparmor/parameters/enabled", 'Y') and is_apparmor_confined()) {
+        return .apparmor;
+    }
+    return null;
+}
+
+fn is_apparmor_confined() bool {
+    const file = fs.openFileAbsolute("/proc/self/attr/current", .{}) catch return false;
+    defer file.close();
+
+    var buffer: [64]u8 = undefined;
+    const read = file.read(&buffer) catch return false;
+    const label_text = std.mem.trim(u8, buffer[0..read], " \n\x00");
+    return label_text.len > 0 and !std.mem.startsWith(u8, label_text, "unconfined");
+}
+
+fn read_flag(path: []const u8, expected: u8) bool {
+    const file = fs.openFileAbsolute(path, .{}) catch return false;
+    defer file.close();
+
+    var buffer: [1]u8 = undefined;
+    const read = file.read(&buffer) catch return false;
+    return read == 1 and buffer[0] == expected;
+}
+
+// Sets the SELinux type of every extracted file (e.g. `bin_t`), so a confined user is allowed to execute them
+pub fn label(allocator: std.mem.Allocator, install_dir: []const u8, selinux_type: []const u8) void {
+    if (get_active() != .selinux) {
+        return;
+    }
+
+    const result = std.ChildProcess.exec(.{
+        .allocator = allocator,
+        .argv = &[_][]const u8{ "chcon", "-R", "-t", selinux_type, install_dir },
+    }) catch |err| {
+        logger.warn("Could not label {s} as {s} for SELinux: {!}", .{ install_dir, selinux_type, err });
+        return;
+    };
+
+    switch (result.term) {
+        .Exited => |code| if (code != 0) {
+            logger.warn("Could not label {s} as {s} for SELinux: {s}", .{ install_dir, selinux_type, std.mem.trim(u8, result.stderr, "\n") });
+        } else {
+            log.debug("Labeled {s} as {s}", .{ install_dir, selinux_type });
+        },
+        else => {},
+    }
+}
+
+// Explains an exec denied by policy, the kernel only gives us EACCES
+pub fn explain_launch_error(err: anyerror, install_dir: []const u8) void {
+    if (builtin.os.tag != .linux) {
+        return;
+    }
+
+    switch (err) {
+        error.AccessDenied, error.PermissionDenied => {
+            const policy = get_active() orelse return;
+            logger.err("{s} is enforcing on this system and likely denied executing the Erlang runtime from {s}.", .{ @tagName(policy), install_dir });
+            switch (policy) {
+                .selinux => logger.err("Check `ausearch -m avc -ts recent`, and point `{s}` at a location the policy allows, or label the install with `chcon -R -t bin_t {s}`.", .{ INSTALL_DIR_ENV, install_dir }),
+                .apparmor => logger.err("Check `dmesg | grep apparmor`, and point `{s}` at a location the profile allows executing from.", .{INSTALL_DIR_ENV}),
+            }
+        },
+        else => {},
+    }
+}
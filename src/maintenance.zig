@@ -1,28 +1,159 @@
 const std = @import("std");
+const build_options = @import("build_options");
 
 const logger = @import("logger.zig");
 const metadata = @import("metadata.zig");
 const install = @import("install.zig");
 const wrapper = @import("wrapper.zig");
+const launcher = @import("erlang_launcher.zig");
+const shutil = @import("shutil.zig");
+const relocate = @import("relocate.zig");
+const rollback = @import("rollback.zig");
+const gzip = @import("gzip.zig");
+const tar = @import("tar.zig");
+const foilz = @import("archiver.zig");
+const doctor = @import("doctor.zig");
+const features = @import("features.zig");
+const update = @import("update.zig");
+const completions = @import("completions.zig");
+const defender = @import("defender.zig");
+const delta = @import("delta.zig");
+const uninstall_entry = @import("uninstall_entry.zig");
+const support_bundle = @import("support_bundle.zig");
+const trace = @import("trace.zig");
+const bench = @import("bench.zig");
+const components = @import("components.zig");
+const exit_codes = @import("exit_codes.zig");
 
 const MetaStruct = metadata.MetaStruct;
+const Sha256 = std.crypto.hash.sha2.Sha256;
+
+// Maintenance commands can be invoked with either prefix, e.g. `my-binary --burrito help`
+const namespaces = [_][]const u8{ "--burrito", "maintenance" };
+
+pub const Context = struct {
+    allocator: std.mem.Allocator,
+    // Arguments following the command name
+    args: [][]u8,
+    install_dir: []const u8,
+    meta: *const MetaStruct,
+};
+
+const Command = struct {
+    name: []const u8,
+    // An alternative spelling, e.g. `--version` for `version`
+    alias: ?[]const u8 = null,
+    usage: []const u8 = "",
+    help: []const u8,
+    // Null for commands the wrapper handles itself as part of the normal launch flow
+    run: ?*const fn (ctx: *const Context) anyerror!void,
+    // Left out of the help and completions, for commands meant for the release's developers
+    hidden: bool = false,
+};
+
+const commands = [_]Command{
+    .{ .name = "help", .help = "Show this list of maintenance commands", .run = print_help },
+    .{ .name = "uninstall", .help = "Uninstall the unpacked payload from this machine", .run = do_uninstall },
+    .{ .name = "clean", .usage = "[--yes]", .help = "Uninstall every version of this application from this machine", .run = do_clean },
+    .{ .name = "directory", .help = "Print the install directory of the unpacked payload", .run = print_install_dir },
+    .{ .name = "version", .alias = "--version", .usage = "[--short]", .help = "Print the version of the application and what it was built with", .run = print_version },
+    .{ .name = "relocate", .usage = "<path>", .help = "Move the installed payload under a new root directory", .run = do_relocate },
+    .{ .name = "defender-exclusion", .help = "Exclude the install root from Microsoft Defender realtime scanning (Windows)", .run = do_defender_exclusion },
+    .{ .name = "open", .help = "Open the install directory in the system file manager", .run = do_open },
+    .{ .name = "which", .help = "Print the resolved install directory and whether it exists", .run = print_which },
+    .{ .name = "checksums", .usage = "[--json]", .help = "Print SHA-256 hashes of the payload and of this binary", .run = print_checksums },
+    .{ .name = "licenses", .usage = "[--extract <dir>]", .help = "Print, or extract, the license files of the application and its dependencies", .run = do_licenses },
+    .{ .name = "sbom", .usage = "[--extract <file>]", .help = "Print, or extract, the software bill of materials packed into this binary", .run = do_sbom },
+    .{ .name = "env", .usage = "[args...]", .help = "Print the arguments and environment the VM would be launched with", .run = print_env },
+    .{ .name = "timings", .help = "Time a fresh install and launch preparation, and print where the time goes", .run = print_timings },
+    .{ .name = "meta", .usage = "[--json]", .help = "Print the metadata and payload details of this binary", .run = print_metadata },
+    .{ .name = "support-bundle", .usage = "[dir]", .help = "Collect logs, metadata and doctor output into a zip file for a bug report", .run = do_support_bundle },
+    .{ .name = "repair", .help = "Restore missing or modified files of the install, leaving unchanged ones alone", .run = do_repair },
+    .{ .name = "doctor", .help = "Check this machine for common problems that stop the application from running", .run = do_doctor },
+    .{ .name = "completions", .usage = "<shell>", .help = "Print a completion script for bash, zsh, fish or powershell", .run = print_completions },
+    .{ .name = "rollback", .usage = "[--undo]", .help = "Switch back to the previously installed version", .run = do_rollback },
+    .{ .name = "components", .usage = "[--json]", .help = "List the optional components of the application and whether they're installed", .run = print_components },
+    .{ .name = "fetch", .usage = "<component...> | --all | <component> --from <file>", .help = "Download and install optional components, or install one from its file", .run = do_fetch },
+} ++ update_commands ++ [_]Command{
+    .{ .name = "extract", .usage = "<dir> [--force]", .help = "Unpack the release into a directory without launching it", .run = do_extract },
+    .{ .name = "export", .usage = "[dir]", .help = "Write the release out as a standard Mix release tarball", .run = do_export },
+    .{ .name = "daemon", .usage = "[args...]", .help = "Start the application in the background under run_erl", .run = null },
+    .{ .name = "attach", .help = "Attach to the console of an application started with `daemon`", .run = do_attach },
+    .{ .name = "bench-startup", .usage = "[--runs <n>] [--budget <ms>] [--json]", .help = "Measure the startup overhead of an installed application", .run = do_bench_startup, .hidden = true },
+};
+
+// Left out of builds without the `update` feature, along with everything they use
+const update_commands = if (features.enabled(.update)) [_]Command{
+    .{ .name = "update", .usage = "[--check] [--json] [--channel <name>] [--allow-downgrade]", .help = "Download, verify and install the latest version of this binary", .run = do_update },
+    .{ .name = "make-delta", .usage = "<old> <new> <out>", .help = "Write a delta update that turns the old binary into the new one", .run = do_make_delta },
+} else [_]Command{};
+
+const command_names = blk: {
+    var names: []const []const u8 = &.{};
+    for (commands) |command| {
+        if (!command.hidden) {
+            names = names ++ &[_][]const u8{command.name};
+        }
+    }
+    break :blk names;
+};
+
+// True if `arg` starts a maintenance command instead of being an argument for the application
+pub fn is_namespace(arg: []const u8) bool {
+    for (namespaces) |namespace| {
+        if (std.mem.eql(u8, arg, namespace)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+pub fn do_maint(args: [][]u8, install_dir: []const u8, meta: *const MetaStruct) !void {
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer arena.deinit();
+
+    const ctx = Context{
+        .allocator = arena.allocator(),
+        .args = if (args.len > 0) args[1..] else args,
+        .install_dir = install_dir,
+        .meta = meta,
+    };
 
-pub fn do_maint(args: [][]u8, install_dir: []const u8) !void {
     if (args.len < 1) {
         logger.warn("No sub-command provided!", .{});
-    } else {
-        if (std.mem.eql(u8, args[0], "uninstall")) {
-            try do_uninstall(install_dir);
-        }
+        try print_help(&ctx);
+        return;
+    }
 
-        if (std.mem.eql(u8, args[0], "directory")) {
-            try print_install_dir(install_dir);
+    for (commands) |command| {
+        const alias_match = if (command.alias) |alias| std.mem.eql(u8, args[0], alias) else false;
+        if (std.mem.eql(u8, args[0], command.name) or alias_match) {
+            if (command.run) |run| {
+                try run(&ctx);
+            }
+            return;
         }
+    }
+
+    logger.warn("Unknown maintenance command: {s}", .{args[0]});
+    try print_help(&ctx);
+    exit_failure();
+}
+
+fn print_help(_: *const Context) !void {
+    var stdout = std.io.getStdOut().writer();
+    stdout.print("Maintenance commands for {s}:\n\n", .{build_options.RELEASE_NAME}) catch {};
 
-        if (std.mem.eql(u8, args[0], "meta")) {
-            try print_metadata();
+    for (commands) |command| {
+        if (command.hidden) {
+            continue;
         }
+        var invocation_buf: [64]u8 = undefined;
+        const invocation = std.fmt.bufPrint(&invocation_buf, "{s} {s}", .{ command.name, command.usage }) catch command.name;
+        stdout.print("  {s} --burrito {s: <22} {s}\n", .{ build_options.RELEASE_NAME, invocation, command.help }) catch {};
     }
+
+    stdout.print("\nThe `maintenance` prefix can be used in place of `--burrito`.\n", .{}) catch {};
 }
 
 fn confirm() !bool {
@@ -41,7 +172,9 @@ fn confirm() !bool {
     }
 }
 
-fn do_uninstall(install_dir: []const u8) !void {
+fn do_uninstall(ctx: *const Context) !void {
+    const install_dir = ctx.install_dir;
+
     logger.warn("This will uninstall the application runtime for this Burrito binary!", .{});
     if ((try confirm()) == false) {
         logger.warn("Uninstall was aborted!", .{});
@@ -50,53 +183,875 @@ fn do_uninstall(install_dir: []const u8) !void {
     }
 
     logger.info("Deleting directory: {s}", .{install_dir});
-    try std.fs.deleteTreeAbsolute(install_dir);
+    try install.delete(ctx.allocator, install_dir);
+    uninstall_entry.unregister(ctx.allocator);
     logger.info("Uninstall complete!", .{});
     logger.info("Quitting.", .{});
 }
 
-fn print_metadata() !void {
+fn do_repair(ctx: *const Context) !void {
+    const metadata_path = try std.fs.path.join(ctx.allocator, &[_][]const u8{ ctx.install_dir, "_metadata.json" });
+    std.fs.accessAbsolute(metadata_path, .{}) catch {
+        logger.err("The payload is not installed yet, run the application to install it", .{});
+        exit_failure();
+    };
+
+    logger.info("Repairing the install in {s}...", .{ctx.install_dir});
+    const started = std.time.milliTimestamp();
+    try wrapper.install_payload(ctx.meta, ctx.install_dir, .repair);
+    logger.info("Repair complete! Took {}ms", .{std.time.milliTimestamp() - started});
+}
+
+fn print_components(ctx: *const Context) !void {
+    const all = try components.get_all(ctx.allocator);
+    var stdout = std.io.getStdOut().writer();
+
+    if (has_flag(ctx, "--json")) {
+        var statuses = std.ArrayList(struct { name: []const u8, url: []const u8, fetch: components.Fetch, size: u64, installed: bool }).init(ctx.allocator);
+        for (all) |*component| {
+            try statuses.append(.{
+                .name = component.name,
+                .url = component.url,
+                .fetch = component.fetch,
+                .size = component.bytes,
+                .installed = components.is_installed(ctx.allocator, ctx.install_dir, component),
+            });
+        }
+        try std.json.stringify(statuses.items, .{}, stdout);
+        try stdout.writeByte('\n');
+        return;
+    }
+
+    if (all.len == 0) {
+        try stdout.print("{s} has no optional components\n", .{ctx.meta.app_name});
+        return;
+    }
+    for (all) |*component| {
+        const status = if (components.is_installed(ctx.allocator, ctx.install_dir, component)) "installed" else "not installed";
+        try stdout.print("{s: <24} {s: <14} {} files, {} bytes\n", .{ component.name, status, component.files, component.bytes });
+    }
+}
+
+fn do_fetch(ctx: *const Context) !void {
+    const metadata_path = try std.fs.path.join(ctx.allocator, &[_][]const u8{ ctx.install_dir, "_metadata.json" });
+    std.fs.accessAbsolute(metadata_path, .{}) catch {
+        logger.err("The payload is not installed yet, run the application to install it", .{});
+        exit_failure();
+    };
+
+    const all = try components.get_all(ctx.allocator);
+    const fetch_all = has_flag(ctx, "--all");
+    const from = get_option(ctx, "--from");
+
+    var names = std.ArrayList([]const u8).init(ctx.allocator);
+    var i: usize = 0;
+    while (i < ctx.args.len) : (i += 1) {
+        if (std.mem.eql(u8, ctx.args[i], "--from")) {
+            i += 1;
+        } else if (!std.mem.eql(u8, ctx.args[i], "--all")) {
+            try names.append(ctx.args[i]);
+        }
+    }
+
+    if (!fetch_all and names.items.len == 0) {
+        logger.err("Please name the components to fetch, or pass `--all`, see `--burrito components`", .{});
+        exit_failure();
+    }
+    // A file holds a single component
+    if (from != null and (fetch_all or names.items.len != 1)) {
+        logger.err("Please name the one component to install from {s}", .{from.?});
+        exit_failure();
+    }
+
+    // Every name is checked before anything is downloaded
+    if (!fetch_all) {
+        for (names.items) |name| {
+            if (try components.find(ctx.allocator, name) == null) {
+                logger.err("Unknown component: {s}, see `--burrito components`", .{name});
+                exit_failure();
+            }
+        }
+    }
+
+    for (all) |*component| {
+        const wanted = fetch_all or for (names.items) |name| {
+            if (std.mem.eql(u8, name, component.name)) break true;
+        } else false;
+        if (!wanted) continue;
+
+        if (components.is_installed(ctx.allocator, ctx.install_dir, component)) {
+            logger.info("{s} is already installed", .{component.name});
+            continue;
+        }
+        if (fetch_all and component.url.len == 0) {
+            logger.info("Skipping {s}, it has no download URL", .{component.name});
+            continue;
+        }
+        try components.install(ctx.allocator, ctx.install_dir, ctx.meta, component, from);
+    }
+}
+
+fn do_doctor(ctx: *const Context) !void {
+    const failures = try doctor.run(ctx.allocator, ctx.install_dir, ctx.meta);
+    if (failures > 0) {
+        exit_failure();
+    }
+}
+
+fn print_completions(ctx: *const Context) !void {
+    if (ctx.args.len < 1) {
+        logger.err("Please provide a shell, one of: bash, zsh, fish, powershell", .{});
+        exit_failure();
+    }
+
+    const shell = completions.parse_shell(ctx.args[0]) orelse {
+        logger.err("Unsupported shell: {s}, expected one of: bash, zsh, fish, powershell", .{ctx.args[0]});
+        exit_failure();
+    };
+
+    // Complete for whatever name the binary was installed as
+    const exe_path = try std.fs.selfExePathAlloc(ctx.allocator);
+    var binary_name = std.fs.path.basename(exe_path);
+    if (std.mem.endsWith(u8, binary_name, ".exe")) {
+        binary_name = binary_name[0 .. binary_name.len - 4];
+    }
+
+    const spec = completions.Spec{
+        .binary_name = binary_name,
+        .maintenance_commands = command_names,
+        .app_words = ctx.meta.completions,
+    };
+
+    try completions.write(std.io.getStdOut().writer(), shell, spec);
+}
+
+fn do_update(ctx: *const Context) !void {
+    const channel = get_option(ctx, "--channel");
+
+    const config = try update.get_config(ctx.allocator, ctx.meta, channel) orelse {
+        logger.err("This binary was not built with an update URL, see the `update` release option", .{});
+        exit_failure();
+    };
+
+    if (has_flag(ctx, "--check")) {
+        const result = try update.check(ctx.allocator, config, ctx.meta.app_version);
+        var stdout = std.io.getStdOut().writer();
+
+        if (has_flag(ctx, "--json")) {
+            try std.json.stringify(result, .{}, stdout);
+            try stdout.writeByte('\n');
+        } else if (result.update_available) {
+            try stdout.print("Update available on the {s} channel: v{s} -> v{s}\n", .{ result.channel, result.current_version, result.latest_version });
+        } else {
+            try stdout.print("Up to date on the {s} channel (v{s})\n", .{ result.channel, result.current_version });
+        }
+        return;
+    }
+
+    // Following a channel is sticky, later updates keep using it
+    if (channel) |name| {
+        try update.save_channel(ctx.allocator, name);
+    }
+
+    try update.run(ctx.allocator, config, ctx.meta.app_version, has_flag(ctx, "--allow-downgrade"));
+
+    // A fresh update supersedes any earlier rollback
+    if (std.fs.path.dirname(ctx.install_dir)) |base_install_dir| {
+        rollback.unpin(ctx.allocator, base_install_dir);
+    }
+}
+
+// Returns the value following `name` in the command arguments, e.g. `--channel beta`
+fn get_option(ctx: *const Context, name: []const u8) ?[]const u8 {
+    for (ctx.args, 0..) |arg, i| {
+        if (std.mem.eql(u8, arg, name) and i + 1 < ctx.args.len) {
+            return ctx.args[i + 1];
+        }
+    }
+    return null;
+}
+
+fn do_make_delta(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+
+    if (ctx.args.len < 3) {
+        logger.err("Usage: --burrito make-delta <old binary> <new binary> <output file>", .{});
+        exit_failure();
+    }
+
+    const old_binary = try std.fs.cwd().readFileAlloc(allocator, ctx.args[0], std.math.maxInt(usize));
+    const new_binary = try std.fs.cwd().readFileAlloc(allocator, ctx.args[1], std.math.maxInt(usize));
+
+    const out_file = try std.fs.cwd().createFile(ctx.args[2], .{ .truncate = true });
+    defer out_file.close();
+
+    var buffered = std.io.bufferedWriter(out_file.writer());
+    try delta.create(allocator, old_binary, new_binary, buffered.writer());
+    try buffered.flush();
+
+    var digest: [Sha256.digest_length]u8 = undefined;
+    Sha256.hash(old_binary, &digest, .{});
+
+    logger.info("Wrote {s} ({} bytes), publish it next to the new binary as <url>.from-{s}.delta", .{ ctx.args[2], (try out_file.stat()).size, std.fmt.fmtSliceHexLower(&digest) });
+}
+
+fn do_extract(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+
+    var target: ?[]const u8 = null;
+    for (ctx.args) |arg| {
+        if (!std.mem.startsWith(u8, arg, "--")) {
+            target = arg;
+            break;
+        }
+    }
+
+    if (target == null) {
+        logger.err("Please provide a directory to extract into, e.g. `--burrito extract ./my-release`", .{});
+        exit_failure();
+    }
+
+    // The archive unpacker works on absolute paths only
+    const dest_dir = try std.fs.cwd().realpathAlloc(allocator, ".");
+    const extract_dir = try std.fs.path.resolve(allocator, &[_][]const u8{ dest_dir, target.? });
+
+    try std.fs.cwd().makePath(extract_dir);
+
+    // Don't mix the release in with whatever is already in there
+    if (!has_flag(ctx, "--force") and !(try is_dir_empty(extract_dir))) {
+        logger.err("The directory {s} is not empty, pass `--force` to extract into it anyway", .{extract_dir});
+        exit_failure();
+    }
+
+    logger.info("Extracting {s} v{s} into {s}", .{ ctx.meta.app_name, ctx.meta.app_version, extract_dir });
+
+    const metadata_path = try std.fs.path.join(allocator, &[_][]const u8{ extract_dir, "_metadata.json" });
+    try wrapper.do_payload_install(ctx.meta, extract_dir, metadata_path, false);
+
+    logger.info("Extraction complete!", .{});
+}
+
+fn do_export(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+    const meta = ctx.meta;
+
+    const cwd_path = try std.fs.cwd().realpathAlloc(allocator, ".");
+    const out_dir = try std.fs.path.resolve(allocator, &[_][]const u8{ cwd_path, if (ctx.args.len > 0) ctx.args[0] else "." });
+    try std.fs.cwd().makePath(out_dir);
+
+    // Same name `mix release` gives its tarballs
+    const tarball_name = try std.fmt.allocPrint(allocator, "{s}-{s}.tar.gz", .{ meta.app_name, meta.app_version });
+    const tarball_path = try std.fs.path.join(allocator, &[_][]const u8{ out_dir, tarball_name });
+
+    logger.info("Exporting {s} v{s} to {s}", .{ meta.app_name, meta.app_version, tarball_path });
+
+    // The payload is already laid out like a release, its records go straight into the tarball
+    const archive = try foilz.decompress(allocator, try wrapper.get_payload(), build_options.UNCOMPRESSED_SIZE);
+    var records = try foilz.iterate_records(archive);
+
+    var tar_buffer = std.ArrayList(u8).init(allocator);
+    var tar_writer = tar.writer(tar_buffer.writer());
+    var count: usize = 0;
+
+    while (try records.next()) |record| {
+        const mode: u32 = if (@import("builtin").os.tag == .windows) 0o644 else @truncate(foilz.sanitize_mode(record.mode, meta.trusted_modes));
+        try tar_writer.add_file(record.name, mode, record.data);
+        count += 1;
+    }
+    try tar_writer.finish();
+
+    const tarball = try std.fs.createFileAbsolute(tarball_path, .{ .truncate = true });
+    defer tarball.close();
+
+    var buffered = std.io.bufferedWriter(tarball.writer());
+    try gzip.compress(allocator, tar_buffer.items, buffered.writer());
+    try buffered.flush();
+
+    logger.info("Exported {} files into {s}", .{ count, tarball_path });
+}
+
+fn do_licenses(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+    var stdout = std.io.getStdOut().writer();
+
+    var extract_dir: ?[]const u8 = null;
+    for (ctx.args, 0..) |arg, i| {
+        if (std.mem.eql(u8, arg, "--extract")) {
+            if (i + 1 >= ctx.args.len) {
+                logger.err("Please provide a directory to extract the licenses into", .{});
+                exit_failure();
+            }
+            const cwd_path = try std.fs.cwd().realpathAlloc(allocator, ".");
+            extract_dir = try std.fs.path.resolve(allocator, &[_][]const u8{ cwd_path, ctx.args[i + 1] });
+        }
+    }
+
+    var records = try foilz.iterate_records(wrapper.LICENSES_FOILZ);
+    var count: usize = 0;
+
+    while (try records.next()) |record| {
+        count += 1;
+
+        if (extract_dir) |dir| {
+            const path = try std.fs.path.join(allocator, &[_][]const u8{ dir, record.name });
+            try std.fs.cwd().makePath(std.fs.path.dirname(path).?);
+            const file = try std.fs.createFileAbsolute(path, .{ .truncate = true });
+            defer file.close();
+            try file.writeAll(record.data);
+        } else {
+            try stdout.print("==> {s} <==\n{s}\n", .{ record.name, record.data });
+        }
+    }
+
+    if (count == 0) {
+        logger.info("No license files were packed into this binary", .{});
+    } else if (extract_dir) |dir| {
+        logger.info("Extracted {} license files into {s}", .{ count, dir });
+    }
+}
+
+fn do_sbom(ctx: *const Context) !void {
+    if (wrapper.SBOM.len == 0) {
+        logger.info("No SBOM was packed into this binary", .{});
+        return;
+    }
+
+    const target = get_option(ctx, "--extract") orelse {
+        try std.io.getStdOut().writeAll(wrapper.SBOM);
+        return;
+    };
+
+    // Extracting into a directory keeps the original file name
+    const cwd_path = try std.fs.cwd().realpathAlloc(ctx.allocator, ".");
+    var path = try std.fs.path.resolve(ctx.allocator, &[_][]const u8{ cwd_path, target });
+    if (std.fs.cwd().openDir(path, .{})) |opened| {
+        var dir = opened;
+        dir.close();
+        path = try std.fs.path.join(ctx.allocator, &[_][]const u8{ path, build_options.SBOM_NAME });
+    } else |_| {}
+
+    const file = try std.fs.createFileAbsolute(path, .{ .truncate = true });
+    defer file.close();
+    try file.writeAll(wrapper.SBOM);
+
+    logger.info("Extracted the SBOM into {s}", .{path});
+}
+
+fn do_support_bundle(ctx: *const Context) !void {
+    const cwd_path = try std.fs.cwd().realpathAlloc(ctx.allocator, ".");
+    const dest_dir = try std.fs.path.resolve(ctx.allocator, &[_][]const u8{ cwd_path, if (ctx.args.len > 0) ctx.args[0] else "." });
+    try std.fs.cwd().makePath(dest_dir);
+
+    const path = try support_bundle.write(ctx.allocator, dest_dir, ctx.install_dir, ctx.meta, null);
+    logger.info("Wrote {s}, please attach it to your bug report", .{path});
+}
+
+fn is_dir_empty(path: []const u8) !bool {
+    var dir = try std.fs.openIterableDirAbsolute(path, .{});
+    defer dir.close();
+
+    var itr = dir.iterate();
+    return (try itr.next()) == null;
+}
+
+fn do_clean(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+    const base_install_dir = std.fs.path.dirname(ctx.install_dir) orelse return;
+
+    // The install root is shared with other Burrito apps, only touch directories named after this release
+    var to_delete = std.ArrayList([]const u8).init(allocator);
+
+    var base_dir = std.fs.openIterableDirAbsolute(base_install_dir, .{}) catch |err| switch (err) {
+        error.FileNotFound => {
+            logger.info("Nothing to clean, {s} does not exist", .{base_install_dir});
+            return;
+        },
+        else => return err,
+    };
+    defer base_dir.close();
+
+    var itr = base_dir.iterate();
+    while (try itr.next()) |entry| {
+        if (entry.kind != .directory) {
+            continue;
+        }
+
+        // Short install directories are recognized by the full name in their marker
+        const path = try std.fs.path.join(allocator, &[_][]const u8{ base_install_dir, entry.name });
+        if (install.is_own_dir(allocator, ctx.meta, path)) {
+            try to_delete.append(path);
+        }
+    }
+
+    if (to_delete.items.len == 0) {
+        logger.info("Nothing to clean, no versions of {s} are installed", .{ctx.meta.app_name});
+        return;
+    }
+
+    logger.warn("This will uninstall every version of {s} from this machine:", .{ctx.meta.app_name});
+    for (to_delete.items) |path| {
+        logger.warn("  {s}", .{path});
+    }
+
+    if (!has_flag(ctx, "--yes") and (try confirm()) == false) {
+        logger.warn("Clean was aborted!", .{});
+        return;
+    }
+
+    for (to_delete.items) |path| {
+        logger.info("Deleting directory: {s}", .{path});
+        try install.delete(allocator, path);
+    }
+    uninstall_entry.unregister(allocator);
+
+    logger.info("Removed {} installed version(s)", .{to_delete.items.len});
+}
+
+fn do_attach(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+    const install_dir = ctx.install_dir;
+    const meta = ctx.meta;
+
+    if (@import("builtin").os.tag == .windows) {
+        logger.err("Attaching to a daemon is not supported on Windows!", .{});
+        exit_failure();
+    }
+
+    const erts_version_name = try std.fmt.allocPrint(allocator, "erts-{s}", .{meta.erts_version});
+    const to_erl_path = try std.fs.path.join(allocator, &[_][]const u8{ install_dir, erts_version_name, "bin", "to_erl" });
+    const pipe_dir = try launcher.get_run_erl_pipe_dir(allocator, install_dir);
+
+    std.fs.accessAbsolute(pipe_dir, .{}) catch {
+        logger.err("No running daemon was found, start one with `--burrito daemon`", .{});
+        exit_failure();
+    };
+
+    logger.info("Attaching to daemon, press Ctrl+D to detach", .{});
+
+    var env_map = try std.process.getEnvMap(allocator);
+    return std.process.execve(allocator, &[_][]const u8{ to_erl_path, pipe_dir }, &env_map);
+}
+
+const PayloadInfo = struct {
+    compressed_size: usize,
+    uncompressed_size: u64,
+    codec: []const u8,
+    format_version: u32,
+};
+
+const MetadataDump = struct {
+    metadata: MetaStruct,
+    install_dir: []const u8,
+    payload: PayloadInfo,
+};
+
+// Failed commands, usage errors included, exit with the generic failure code (see exit_codes.zig), so scripts can
+// tell them apart from successful runs
+fn exit_failure() noreturn {
+    std.process.exit(exit_codes.ExitCode.failure.code());
+}
+
+fn has_flag(ctx: *const Context, flag: []const u8) bool {
+    for (ctx.args) |arg| {
+        if (std.mem.eql(u8, arg, flag)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+fn print_metadata(ctx: *const Context) !void {
+    var stdout = std.io.getStdOut().writer();
+
+    const dump = MetadataDump{
+        .metadata = ctx.meta.*,
+        .install_dir = ctx.install_dir,
+        .payload = .{
+            .compressed_size = (try wrapper.get_payload()).len,
+            .uncompressed_size = build_options.UNCOMPRESSED_SIZE,
+            .codec = foilz.CODEC,
+            .format_version = foilz.FORMAT_VERSION,
+        },
+    };
+
+    if (has_flag(ctx, "--json")) {
+        try std.json.stringify(dump, .{ .whitespace = .indent_2 }, stdout);
+        try stdout.writeByte('\n');
+        return;
+    }
+
+    inline for (std.meta.fields(MetaStruct)) |field| {
+        try print_field(stdout, field.name, @field(dump.metadata, field.name));
+    }
+    try print_field(stdout, "install_dir", dump.install_dir);
+    inline for (std.meta.fields(PayloadInfo)) |field| {
+        try print_field(stdout, "payload_" ++ field.name, @field(dump.payload, field.name));
+    }
+}
+
+const Checksums = struct {
+    // Hash of the uncompressed archive, stays the same as long as the packed files do
+    payload_sha256: []const u8,
+    // Hash of the compressed payload as embedded in the binary
+    compressed_payload_sha256: []const u8,
+    binary_sha256: []const u8,
+    codec: []const u8,
+    format_version: u32,
+};
+
+fn print_checksums(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+    var stdout = std.io.getStdOut().writer();
+
+    const archive = try foilz.decompress(allocator, try wrapper.get_payload(), build_options.UNCOMPRESSED_SIZE);
+
+    const exe_path = try std.fs.selfExePathAlloc(allocator);
+    const exe_file = try std.fs.openFileAbsolute(exe_path, .{});
+    defer exe_file.close();
+
+    var binary_hasher = Sha256.init(.{});
+    var buffer: [64 * 1024]u8 = undefined;
+    while (true) {
+        const count = try exe_file.read(&buffer);
+        if (count == 0) break;
+        binary_hasher.update(buffer[0..count]);
+    }
+
+    const checksums = Checksums{
+        .payload_sha256 = try sha256_hex(allocator, archive),
+        .compressed_payload_sha256 = try sha256_hex(allocator, try wrapper.get_payload()),
+        .binary_sha256 = try std.fmt.allocPrint(allocator, "{s}", .{std.fmt.fmtSliceHexLower(&binary_hasher.finalResult())}),
+        .codec = foilz.CODEC,
+        .format_version = foilz.FORMAT_VERSION,
+    };
+
+    if (has_flag(ctx, "--json")) {
+        try std.json.stringify(checksums, .{ .whitespace = .indent_2 }, stdout);
+        try stdout.writeByte('\n');
+        return;
+    }
+
+    inline for (std.meta.fields(Checksums)) |field| {
+        try print_field(stdout, field.name, @field(checksums, field.name));
+    }
+}
+
+fn sha256_hex(allocator: std.mem.Allocator, data: []const u8) ![]const u8 {
+    var digest: [Sha256.digest_length]u8 = undefined;
+    Sha256.hash(data, &digest, .{});
+    return try std.fmt.allocPrint(allocator, "{s}", .{std.fmt.fmtSliceHexLower(&digest)});
+}
+
+fn print_field(writer: anytype, comptime name: []const u8, value: anytype) !void {
+    const T = @TypeOf(value);
+    switch (@typeInfo(T)) {
+        .Optional => {
+            if (value) |inner| {
+                try print_field(writer, name, inner);
+            } else {
+                try writer.print("{s: <24} (not set)\n", .{name});
+            }
+        },
+        // Both strings and lists of strings print nicely with `{s}`
+        .Pointer => try writer.print("{s: <24} {s}\n", .{ name, value }),
+        else => try writer.print("{s: <24} {any}\n", .{ name, value }),
+    }
+}
+
+fn print_version(ctx: *const Context) !void {
+    var stdout = std.io.getStdOut().writer();
+    const meta = ctx.meta;
+
+    // Only the version number, handy for scripts
+    if (has_flag(ctx, "--short")) {
+        try stdout.print("{s}\n", .{meta.app_version});
+        return;
+    }
+
+    try stdout.print("{s} {s}\n", .{ meta.app_name, meta.app_version });
+    try stdout.print("  ERTS:   {s}\n", .{meta.erts_version});
+    try stdout.print("  OTP:    {s}\n", .{meta.otp_version orelse "unknown"});
+    try stdout.print("  Target: {s}\n", .{meta.target orelse "unknown"});
+    try stdout.print("  Built:  {s}\n", .{meta.build_timestamp orelse "unknown"});
+    try stdout.print("  Commit: {s}\n", .{meta.git_sha orelse "unknown"});
+    try stdout.print("  Zig:    {s}\n", .{meta.zig_version});
+}
+
+fn do_relocate(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+
+    if (ctx.args.len < 1) {
+        logger.err("Please provide the new root directory, e.g. `--burrito relocate /mnt/data`", .{});
+        exit_failure();
+    }
+
+    const metadata_path = try std.fs.path.join(allocator, &[_][]const u8{ ctx.install_dir, "_metadata.json" });
+    std.fs.accessAbsolute(metadata_path, .{}) catch {
+        logger.err("Nothing to relocate, the payload is not installed in {s}", .{ctx.install_dir});
+        exit_failure();
+    };
+
+    const cwd_path = try std.fs.cwd().realpathAlloc(allocator, ".");
+    const new_root = try std.fs.path.resolve(allocator, &[_][]const u8{ cwd_path, ctx.args[0] });
+
+    if (shutil.get_release_env(allocator, "INSTALL_DIR") != null) {
+        logger.warn("The install dir environment override is set, and will keep taking precedence over the new location", .{});
+    }
+
+    logger.info("Relocating {s} under {s}", .{ ctx.install_dir, new_root });
+
+    const new_install_dir = relocate.relocate(allocator, ctx.install_dir, new_root) catch |err| {
+        switch (err) {
+            error.SameLocation => logger.err("The payload is already installed under {s}", .{new_root}),
+            error.DestinationExists => logger.err("The destination already contains an install of this version", .{}),
+            else => logger.err("Relocation failed, the original install was left in place: {!}", .{err}),
+        }
+        exit_failure();
+    };
+
+    logger.info("Relocation complete! The payload now lives in {s}", .{new_install_dir});
+}
+
+// Installs into a scratch directory next to the real install, so the numbers come from the same disk as a first run
+fn print_timings(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+
+    const scratch_dir = try std.fmt.allocPrint(allocator, "{s}.timings-{}", .{ ctx.install_dir, std.time.timestamp() });
+    try std.fs.cwd().makePath(scratch_dir);
+    defer std.fs.deleteTreeAbsolute(scratch_dir) catch |err| {
+        logger.warn("Could not remove {s}: {!}", .{ scratch_dir, err });
+    };
+
+    logger.info("Installing into {s} to measure startup, this takes as long as a first run...", .{scratch_dir});
+    const metadata_path = try std.fs.path.join(allocator, &[_][]const u8{ scratch_dir, "_metadata.json" });
+    try wrapper.do_payload_install(ctx.meta, scratch_dir, metadata_path, false);
+
+    // Everything a launch does before starting the VM, without starting it
+    const spawn_timer = trace.start(.spawn_vm);
+    var env_map = try wrapper.build_env_map(scratch_dir, ctx.meta, false);
+    const launch_plan = try launcher.plan(allocator, scratch_dir, &env_map, ctx.meta, ctx.args, .foreground);
+    launch_plan.wipe_secrets();
+    spawn_timer.stop();
+
+    try trace.print_summary(std.io.getStdOut().writer());
+}
+
+fn do_bench_startup(ctx: *const Context) !void {
+    const metadata_path = try std.fs.path.join(ctx.allocator, &[_][]const u8{ ctx.install_dir, "_metadata.json" });
+    std.fs.accessAbsolute(metadata_path, .{}) catch {
+        logger.err("The payload is not installed yet, run the application once first", .{});
+        exit_failure();
+    };
+
+    var options = bench.Options{ .json = has_flag(ctx, "--json") };
+    if (get_option(ctx, "--runs")) |runs| {
+        options.runs = std.fmt.parseInt(usize, runs, 10) catch 0;
+        if (options.runs == 0) {
+            logger.err("The number of runs must be a positive number, got: {s}", .{runs});
+            exit_failure();
+        }
+    }
+    if (get_option(ctx, "--budget")) |budget| {
+        options.budget_ms = std.fmt.parseFloat(f64, budget) catch {
+            logger.err("The budget must be a number of milliseconds, got: {s}", .{budget});
+            exit_failure();
+        };
+    }
+
+    bench.run(ctx.allocator, options) catch |err| switch (err) {
+        error.StartupOverBudget => std.process.exit(1),
+        else => return err,
+    };
+}
+
+fn print_env(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
     var stdout = std.io.getStdOut().writer();
-    stdout.print("{s}", .{wrapper.RELEASE_METADATA_JSON}) catch {};
+
+    const metadata_path = try std.fs.path.join(allocator, &[_][]const u8{ ctx.install_dir, "_metadata.json" });
+    std.fs.accessAbsolute(metadata_path, .{}) catch {
+        logger.err("The payload is not installed yet, run the application once first", .{});
+        exit_failure();
+    };
+
+    var env_map = try wrapper.build_env_map(ctx.install_dir, ctx.meta, false);
+    const launch_plan = try launcher.plan(allocator, ctx.install_dir, &env_map, ctx.meta, ctx.args, .foreground);
+    // Never printed, so never needed
+    launch_plan.wipe_secrets();
+
+    try stdout.print("Arguments:\n", .{});
+    // Don't leak the distribution cookie into terminal scrollback or bug reports
+    for (launcher.redact_args(allocator, launch_plan.argv)) |arg| {
+        try stdout.print("  {s}\n", .{arg});
+    }
+
+    var own_env = try std.process.getEnvMap(allocator);
+
+    var names = std.ArrayList([]const u8).init(allocator);
+    var env_it = launch_plan.env_map.iterator();
+    while (env_it.next()) |entry| {
+        try names.append(entry.key_ptr.*);
+    }
+    std.mem.sort([]const u8, names.items, {}, string_less_than);
+
+    try stdout.print("\nEnvironment:\n", .{});
+    for (names.items) |name| {
+        const value = launch_plan.env_map.get(name).?;
+        try stdout.print("  {s}={s}  [{s}]\n", .{ name, value, env_origin(&own_env, name, value) });
+    }
 }
 
-fn print_install_dir(install_dir: []const u8) !void {
+// Where a value of the launch environment came from
+fn env_origin(own_env: *const std.process.EnvMap, name: []const u8, value: []const u8) []const u8 {
+    for (launcher.launcher_env_names) |launcher_name| {
+        if (std.mem.eql(u8, name, launcher_name)) {
+            return if (own_env.get(name) != null) "launcher, overrides user" else "launcher";
+        }
+    }
+
+    if (own_env.get(name)) |own_value| {
+        return if (std.mem.eql(u8, own_value, value)) "user" else "wrapper, overrides user";
+    }
+
+    return "wrapper";
+}
+
+fn string_less_than(_: void, lhs: []const u8, rhs: []const u8) bool {
+    return std.mem.lessThan(u8, lhs, rhs);
+}
+
+fn do_defender_exclusion(ctx: *const Context) !void {
+    if (@import("builtin").os.tag != .windows) {
+        logger.err("Microsoft Defender exclusions are only supported on Windows!", .{});
+        exit_failure();
+    }
+
+    const base_install_dir = std.fs.path.dirname(ctx.install_dir) orelse return;
+    logger.info("Excluding {s} from realtime scanning, this requires administrator rights", .{base_install_dir});
+
+    defender.add_exclusion(ctx.allocator, base_install_dir) catch |err| {
+        logger.err("Failed to add the exclusion: {!}", .{err});
+        logger.info("You can add it yourself in an administrator PowerShell: {s}", .{try defender.get_exclusion_command(ctx.allocator, base_install_dir)});
+        exit_failure();
+    };
+
+    logger.info("Exclusion added!", .{});
+}
+
+fn do_open(ctx: *const Context) !void {
+    const builtin = @import("builtin");
+
+    std.fs.accessAbsolute(ctx.install_dir, .{}) catch {
+        logger.err("The install directory does not exist yet, run the application once first", .{});
+        exit_failure();
+    };
+
+    const opener = switch (builtin.os.tag) {
+        .windows => "explorer.exe",
+        .macos => "open",
+        else => "xdg-open",
+    };
+
+    var child = std.ChildProcess.init(&[_][]const u8{ opener, ctx.install_dir }, ctx.allocator);
+    child.stdin_behavior = .Ignore;
+
+    _ = child.spawnAndWait() catch |err| {
+        logger.err("Failed to run `{s}`: {!}", .{ opener, err });
+        logger.info("The install directory is: {s}", .{ctx.install_dir});
+        exit_failure();
+    };
+}
+
+fn print_which(ctx: *const Context) !void {
     var stdout = std.io.getStdOut().writer();
-    stdout.print("{s}\n", .{install_dir}) catch {};
+
+    const metadata_path = try std.fs.path.join(ctx.allocator, &[_][]const u8{ ctx.install_dir, "_metadata.json" });
+    const dir_exists = if (std.fs.accessAbsolute(ctx.install_dir, .{})) true else |_| false;
+    const installed = if (std.fs.accessAbsolute(metadata_path, .{})) true else |_| false;
+    const source = if (shutil.get_release_env(ctx.allocator, "INSTALL_DIR") != null)
+        "install dir environment override"
+    else if (relocate.read_marker(ctx.allocator) != null)
+        "relocated with `--burrito relocate`"
+    else
+        "default app data directory";
+
+    try stdout.print("{s}\n", .{ctx.install_dir});
+    try stdout.print("  exists:    {s}\n", .{if (dir_exists) "yes" else "no"});
+    try stdout.print("  installed: {s}\n", .{if (installed) "yes" else "no"});
+    try stdout.print("  source:    {s}\n", .{source});
+}
+
+fn print_install_dir(ctx: *const Context) !void {
+    var stdout = std.io.getStdOut().writer();
+    stdout.print("{s}\n", .{ctx.install_dir}) catch {};
 }
 
-pub fn do_clean_old_versions(install_prefix_path: []const u8, current_install_path: []const u8) !void {
+pub fn do_clean_old_versions(install_prefix_path: []const u8, current_install_path: []const u8, keep_previous: bool) !void {
     std.log.debug("Going to clean up older versions of this application...", .{});
 
     var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
     defer arena.deinit();
     const allocator = arena.allocator();
 
-    const prefix_dir = try std.fs.openIterableDirAbsolute(install_prefix_path, .{ .access_sub_paths = true });
+    const current_install = (try install.load_install_from_path(allocator, current_install_path)) orelse return;
+    const older_installs = try rollback.find_older_installs(allocator, install_prefix_path, &current_install);
 
-    const current_install = try install.load_install_from_path(allocator, current_install_path);
+    // The newest of the older versions stays around for `--burrito rollback`
+    const to_delete = if (keep_previous and older_installs.len > 0) older_installs[1..] else older_installs;
 
-    var itr = prefix_dir.iterate();
-    while (try itr.next()) |dir| {
-        if (dir.kind == .directory) {
-            const possible_app_path = try std.fs.path.join(allocator, &[_][]const u8{ install_prefix_path, dir.name });
-            const other_install = try install.load_install_from_path(allocator, possible_app_path);
+    for (to_delete) |other_install| {
+        try install.delete(allocator, other_install.install_dir_path);
+        logger.log_stderr("Uninstalled older version (v{s})", .{other_install.metadata.app_version});
+    }
+}
 
-            // If can can't figure out if this is an install dir, just ignore it
-            if (other_install == null) {
-                continue;
-            }
+fn do_rollback(ctx: *const Context) !void {
+    const allocator = ctx.allocator;
+    const base_install_dir = std.fs.path.dirname(ctx.install_dir) orelse return;
 
-            // If this isn't the same installed app as us ignore it
-            if (!std.mem.eql(u8, current_install.?.metadata.app_name, other_install.?.metadata.app_name)) {
-                continue;
-            }
+    if (has_flag(ctx, "--undo")) {
+        const active = rollback.read_pin(allocator, base_install_dir) orelse {
+            logger.err("There is no rollback to undo", .{});
+            exit_failure();
+        };
 
-            // Compare the version, if it's older, delete the directory
-            if (std.SemanticVersion.order(current_install.?.version, other_install.?.version) == .gt) {
-                try std.fs.deleteTreeAbsolute(other_install.?.install_dir_path);
-                logger.log_stderr("Uninstalled older version (v{s})", .{other_install.?.metadata.app_version});
+        // Without a swap at rollback time, the kept binary is the one before the last update, not a newer one
+        if (active.swapped_binary) {
+            if (!try rollback.swap_binaries(allocator)) {
+                logger.err("The newer binary is gone, run `--burrito update` to go forward again", .{});
+                exit_failure();
             }
+            logger.info("Restored the newer binary", .{});
         }
+        rollback.unpin(allocator, base_install_dir);
+        logger.info("Rollback undone, v{s} will be launched again", .{active.from_version});
+        return;
+    }
+
+    if (rollback.read_pin(allocator, base_install_dir)) |active| {
+        const version = if (active.install) |pinned| pinned.metadata.app_version else ctx.meta.app_version;
+        logger.err("Already rolled back to v{s}, use `--burrito rollback --undo` to go forward again", .{version});
+        exit_failure();
     }
+
+    const current_install = (try install.load_install_from_path(allocator, ctx.install_dir)) orelse {
+        logger.err("The current version is not installed, there is nothing to roll back from", .{});
+        exit_failure();
+    };
+
+    const older_installs = try rollback.find_older_installs(allocator, base_install_dir, &current_install);
+    const swapped = try rollback.swap_binaries(allocator);
+
+    if (older_installs.len > 0) {
+        try rollback.pin(allocator, base_install_dir, &older_installs[0], swapped, ctx.meta.app_version);
+        logger.info("Rolled back from v{s} to v{s}", .{ ctx.meta.app_version, older_installs[0].metadata.app_version });
+    } else if (swapped) {
+        try rollback.pin(allocator, base_install_dir, null, swapped, ctx.meta.app_version);
+        logger.info("Rolled back to the binary kept by the last update, it will reinstall its own version on the next run", .{});
+    } else {
+        logger.err("No previous version was found, enable the `keep_previous_version` option to keep one around", .{});
+        exit_failure();
+    }
+
+    logger.info("Use `--burrito rollback --undo` to go forward again", .{});
 }
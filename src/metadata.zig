@@ -1,5 +1,13 @@
 const std = @import("std");
 
+// A release's own text for a wrapper message, from the `translations` option (see i18n.zig)
+pub const Translation = struct {
+    // A language (`de`) or a language with a region (`pt_BR`)
+    locale: []const u8,
+    key: []const u8,
+    text: []const u8,
+};
+
 pub const MetaStruct = struct {
     app_name: []const u8 = undefined,
     zig_version: []const u8 = undefined,
@@ -7,10 +15,46 @@ pub const MetaStruct = struct {
     app_version: []const u8 = undefined,
     options: []const u8 = undefined,
     erts_version: []const u8 = undefined,
+    otp_version: ?[]const u8 = null,
+    target: ?[]const u8 = null,
+    static_erts: bool = false,
+    build_timestamp: ?[]const u8 = null,
+    git_sha: ?[]const u8 = null,
+    pty: bool = false,
+    windows_console: []const u8 = "erl",
+    log_target: []const u8 = "console",
+    shutdown_timeout: u64 = 0,
+    nice: ?i32 = null,
+    windows_priority: ?[]const u8 = null,
+    max_open_files: ?u64 = null,
+    crash_dump_keep: u32 = 5,
+    crash_dump_max_bytes: ?u64 = null,
+    crash_dump_compress: bool = false,
+    update_url: ?[]const u8 = null,
+    update_public_key: ?[]const u8 = null,
+    update_channel: []const u8 = "stable",
+    keep_previous_version: bool = false,
+    clear_quarantine: bool = true,
+    short_install_dir: bool = false,
+    trusted_modes: bool = false,
+    cookie_source: []const u8 = "file",
+    tamper_check: []const u8 = "warn",
+    policy_install_dir: ?[]const u8 = null,
+    selinux_type: ?[]const u8 = null,
+    sandbox_extraction: bool = false,
+    telemetry_url: ?[]const u8 = null,
+    cookie_keychain_service: ?[]const u8 = null,
+    windows_uninstall_entry: bool = false,
+    windows_display_name: ?[]const u8 = null,
+    windows_publisher: ?[]const u8 = null,
+    completions: []const []const u8 = &.{},
+    translations: []const Translation = &.{},
+    support_url: ?[]const u8 = null,
+    unique_node_name: bool = false,
 };
 
 pub fn parse(allocator: std.mem.Allocator, string_data: []const u8) ?MetaStruct {
-    const metadata_parsed = std.json.parseFromSlice(MetaStruct, allocator, string_data, .{}) catch |e| {
+    const metadata_parsed = std.json.parseFromSlice(MetaStruct, allocator, string_data, .{ .ignore_unknown_fields = true }) catch |e| {
         std.log.err("Error when parsing metadata: {!}", .{e});
         return null;
     };
@@ -0,0 +1,90 @@
+/////
+// Replaces Zig's panic output, a stack trace that means nothing to end users, with a short message pointing at a
+// crash log and the release's `support_url`. The log gets the panic message, the stack trace and everything the
+// wrapper logged during the run. Development builds still print the usual trace afterwards.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const exit_codes = @import("exit_codes.zig");
+const i18n = @import("i18n.zig");
+const logger = @import("logger.zig");
+const shutil = @import("shutil.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+
+var release_version: []const u8 = "";
+var support_url: ?[]const u8 = null;
+
+// A panic while handling a panic gives up right away
+var panicking = std.atomic.Atomic(bool).init(false);
+
+var log_path_buf: [fs.MAX_PATH_BYTES]u8 = undefined;
+
+pub fn init(meta: *const MetaStruct) void {
+    release_version = meta.app_version;
+    support_url = meta.support_url;
+}
+
+pub fn handle(message: []const u8, error_return_trace: ?*std.builtin.StackTrace, ret_addr: ?usize) noreturn {
+    if (panicking.swap(true, .SeqCst)) {
+        std.os.abort();
+    }
+
+    const log_path = write_log(message, error_return_trace, ret_addr orelse @returnAddress()) catch null;
+
+    // Straight to stderr as one paragraph, the logger may be what panicked
+    const stderr = std.io.getStdErr().writer();
+    stderr.print("\n[!!] {s}", .{i18n.format(.panic, &.{build_options.RELEASE_NAME})}) catch {};
+    if (log_path) |path| {
+        stderr.print(" {s}", .{i18n.format(.panic_log, &.{path})}) catch {};
+    }
+    if (support_url) |url| {
+        stderr.print(" {s}", .{i18n.format(.panic_support, &.{url})}) catch {};
+    }
+    stderr.writeByte('\n') catch {};
+
+    if (!build_options.IS_PROD) {
+        std.builtin.default_panic(message, error_return_trace, ret_addr);
+    }
+    std.process.exit(exit_codes.ExitCode.panic.code());
+}
+
+// Writes `<temp dir>/<app>-crash-<timestamp>.log`, returns its path
+fn write_log(message: []const u8, error_return_trace: ?*std.builtin.StackTrace, ret_addr: usize) ![]const u8 {
+    const allocator = std.heap.page_allocator;
+
+    const temp_dir = shutil.get_temp_dir(allocator);
+    const log_path = try std.fmt.bufPrint(&log_path_buf, "{s}{c}{s}-crash-{}.log", .{ temp_dir, fs.path.sep, build_options.RELEASE_NAME, std.time.timestamp() });
+
+    // The temp dir is usually shared with other users, and the log holds the run's messages
+    const file = try fs.createFileAbsolute(log_path, .{ .exclusive = true, .mode = 0o600 });
+    defer file.close();
+    const writer = file.writer();
+
+    try writer.print("{s} {s} panicked: {s}\n", .{ build_options.RELEASE_NAME, release_version, message });
+    try writer.print("running on: {s} {s}\n\n", .{ @tagName(builtin.os.tag), @tagName(builtin.cpu.arch) });
+
+    // Release builds can be stripped, the log is still worth having without a trace
+    try writer.print("Stack trace:\n", .{});
+    if (std.debug.getSelfDebugInfo()) |debug_info| {
+        std.debug.writeCurrentStackTrace(writer, debug_info, .no_color, ret_addr) catch |err| {
+            try writer.print("unavailable: {!}\n", .{err});
+        };
+        if (error_return_trace) |trace| {
+            try writer.print("\nError return trace:\n", .{});
+            std.debug.writeStackTrace(trace.*, writer, allocator, debug_info, .no_color) catch |err| {
+                try writer.print("unavailable: {!}\n", .{err});
+            };
+        }
+    } else |err| {
+        try writer.print("unavailable: {!}\n", .{err});
+    }
+
+    try writer.print("\nWrapper log:\n{s}", .{logger.get_history()});
+
+    return log_path;
+}
@@ -0,0 +1,45 @@
+/////
+// Lowers (or raises) the scheduling priority of the Erlang VM.
+// On POSIX we set our own nice value right before `execve()`, the VM inherits it.
+// On Windows we set the priority class of the VM child process after spawning it.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const log = std.log;
+const windows = std.os.windows;
+
+const PRIO_PROCESS = 0;
+
+extern "c" fn setpriority(which: c_int, who: c_uint, prio: c_int) c_int;
+extern "kernel32" fn SetPriorityClass(hProcess: windows.HANDLE, dwPriorityClass: windows.DWORD) callconv(windows.WINAPI) windows.BOOL;
+
+pub const WindowsPriorityClass = enum(u32) {
+    idle = 0x00000040,
+    below_normal = 0x00004000,
+    normal = 0x00000020,
+    above_normal = 0x00008000,
+    high = 0x00000080,
+};
+
+// Sets the nice value of the current process, from -20 (highest priority) to 19 (lowest)
+pub fn set_nice(nice: i32) void {
+    if (builtin.os.tag != .windows) {
+        if (setpriority(PRIO_PROCESS, 0, @intCast(std.math.clamp(nice, -20, 19))) != 0) {
+            log.warn("Failed to set the nice value to {}, continuing with the default priority", .{nice});
+        }
+    }
+}
+
+pub fn parse_windows_priority_class(name: []const u8) ?WindowsPriorityClass {
+    return std.meta.stringToEnum(WindowsPriorityClass, name);
+}
+
+pub fn set_windows_priority_class(process: windows.HANDLE, class: WindowsPriorityClass) void {
+    if (builtin.os.tag == .windows) {
+        if (SetPriorityClass(process, @intFromEnum(class)) == 0) {
+            log.warn("Failed to set the priority class to {s}, continuing with the default priority", .{@tagName(class)});
+        }
+    }
+}
@@ -0,0 +1,95 @@
+/////
+// Profiles run several independent instances of the same release side by side, e.g. `dev`, `staging` and `prod`.
+// The payload is installed once and shared, each profile gets its own writable directories, which the VM finds in
+// `BURRITO_DATA_DIR` and `BURRITO_CONFIG_DIR` (along with the name in `BURRITO_PROFILE`):
+//
+//   Linux:   $XDG_DATA_HOME/<app>/<profile>                 $XDG_CONFIG_HOME/<app>/<profile>
+//   MacOS:   ~/Library/Application Support/<app>/<profile>  ~/Library/Preferences/<app>/<profile>
+//   Windows: %LOCALAPPDATA%\<app>\<profile>                 %APPDATA%\<app>\<profile>
+//
+// The profile is picked with `--burrito-profile=<name>` or `<APP>_PROFILE`, and is `default` otherwise.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const log = std.log;
+
+pub const DEFAULT = "default";
+
+// Profile names end up in paths, keep them short and portable
+const MAX_NAME_LEN = 64;
+
+var active: []const u8 = DEFAULT;
+
+pub fn set(name: []const u8) !void {
+    if (!is_valid_name(name)) {
+        return error.InvalidProfileName;
+    }
+    active = name;
+}
+
+pub fn get() []const u8 {
+    return active;
+}
+
+pub fn is_default() bool {
+    return std.mem.eql(u8, active, DEFAULT);
+}
+
+fn is_valid_name(name: []const u8) bool {
+    if (name.len == 0 or name.len > MAX_NAME_LEN or name[0] == '.') return false;
+    for (name) |c| {
+        if (!(std.ascii.isAlphanumeric(c) or c == '_' or c == '-' or c == '.')) return false;
+    }
+    return true;
+}
+
+pub fn get_data_dir(allocator: std.mem.Allocator, meta: *const MetaStruct) ![]const u8 {
+    const app_dir = try fs.getAppDataDir(allocator, meta.app_name);
+    return try fs.path.join(allocator, &[_][]const u8{ app_dir, active });
+}
+
+pub fn get_config_dir(allocator: std.mem.Allocator, meta: *const MetaStruct) ![]const u8 {
+    const base = switch (builtin.os.tag) {
+        .windows => try std.process.getEnvVarOwned(allocator, "APPDATA"),
+        .macos => try fs.path.join(allocator, &[_][]const u8{ try get_home(allocator), "Library", "Preferences" }),
+        else => std.process.getEnvVarOwned(allocator, "XDG_CONFIG_HOME") catch
+            try fs.path.join(allocator, &[_][]const u8{ try get_home(allocator), ".config" }),
+    };
+    return try fs.path.join(allocator, &[_][]const u8{ base, meta.app_name, active });
+}
+
+fn get_home(allocator: std.mem.Allocator) ![]const u8 {
+    return std.process.getEnvVarOwned(allocator, "HOME") catch return error.NoHomeDirectory;
+}
+
+// Exports the profile and its directories to the VM, creating the directories if `create` is set
+pub fn apply_env(allocator: std.mem.Allocator, env_map: *std.process.EnvMap, meta: *const MetaStruct, create: bool) !void {
+    const data_dir = try get_data_dir(allocator, meta);
+    const config_dir = try get_config_dir(allocator, meta);
+
+    if (create) {
+        for ([_][]const u8{ data_dir, config_dir }) |dir| {
+            fs.cwd().makePath(dir) catch |err| {
+                log.warn("Could not create the directory {s} for the {s} profile: {!}", .{ dir, active, err });
+            };
+        }
+    }
+
+    try env_map.put("BURRITO_PROFILE", active);
+    try env_map.put("BURRITO_DATA_DIR", data_dir);
+    try env_map.put("BURRITO_CONFIG_DIR", config_dir);
+}
+
+// Where `run_erl` keeps the pipes and logs of a daemon, so each profile can run one of its own
+pub fn get_run_erl_dir(allocator: std.mem.Allocator, install_dir: []const u8) ![]const u8 {
+    if (is_default()) {
+        return try fs.path.join(allocator, &[_][]const u8{ install_dir, "run_erl" });
+    }
+    const dir_name = try std.fmt.allocPrint(allocator, "run_erl-{s}", .{active});
+    return try fs.path.join(allocator, &[_][]const u8{ install_dir, dir_name });
+}
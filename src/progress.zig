@@ -0,0 +1,110 @@
+/////
+// Machine-readable progress for GUI installers that wrap the binary. If `<APP>_PROGRESS_FD` names a file descriptor
+// (a handle on Windows) inherited from the parent, newline-delimited JSON events are written to it, separately from
+// our human-readable output:
+//
+//   {"event":"phase_started","phase":"extract"}
+//   {"event":"progress","phase":"extract","files":128,"total_files":3412,"bytes":1048576,"total_bytes":52428800}
+//   {"event":"phase_finished","phase":"extract"}
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const features = @import("features.zig");
+const shutil = @import("shutil.zig");
+
+const log = std.log;
+const os = std.os;
+
+var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+var allocator = arena.allocator();
+
+// Enough to move a progress bar smoothly without a write for every small file
+const FILES_PER_EVENT = 64;
+
+var progress_file: ?std.fs.File = null;
+var progress_file_checked = false;
+
+pub fn is_enabled() bool {
+    if (!features.enabled(.progress)) return false;
+    return get_file() != null;
+}
+
+pub fn phase_started(phase: []const u8) void {
+    send(.{ .event = "phase_started", .phase = phase });
+}
+
+pub fn phase_finished(phase: []const u8) void {
+    send(.{ .event = "phase_finished", .phase = phase });
+}
+
+pub fn failed(phase: []const u8, failure: anyerror) void {
+    send(.{ .event = "failed", .phase = phase, .@"error" = @errorName(failure) });
+}
+
+// Counts files and bytes of a phase, and reports them every few files
+pub const Counter = struct {
+    phase: []const u8,
+    total_files: u64,
+    total_bytes: u64,
+    files: u64 = 0,
+    bytes: u64 = 0,
+
+    pub fn add(self: *Counter, bytes: u64) void {
+        self.files += 1;
+        self.bytes += bytes;
+        if (self.files % FILES_PER_EVENT == 0 or self.files == self.total_files) {
+            self.report();
+        }
+    }
+
+    pub fn report(self: *const Counter) void {
+        send(.{
+            .event = "progress",
+            .phase = self.phase,
+            .files = self.files,
+            .total_files = self.total_files,
+            .bytes = self.bytes,
+            .total_bytes = self.total_bytes,
+        });
+    }
+};
+
+fn send(progress_event: anytype) void {
+    if (!features.enabled(.progress)) return;
+
+    const file = get_file() orelse return;
+    const writer = file.writer();
+    std.json.stringify(progress_event, .{}, writer) catch |err| return give_up(err);
+    writer.writeByte('\n') catch |err| return give_up(err);
+}
+
+// The reader went away or the descriptor was never valid, stop trying
+fn give_up(err: anyerror) void {
+    log.debug("Could not write a progress event, no longer reporting progress: {!}", .{err});
+    progress_file = null;
+}
+
+fn get_file() ?std.fs.File {
+    if (progress_file_checked) {
+        return progress_file;
+    }
+    progress_file_checked = true;
+
+    const value = shutil.get_release_env(allocator, "PROGRESS_FD") orelse return null;
+    const number = std.fmt.parseInt(usize, value, 10) catch {
+        log.warn("Ignoring the progress file descriptor `{s}`, it is not a number", .{value});
+        return null;
+    };
+
+    // Never write machine-readable events into our own human-readable output
+    if (builtin.os.tag != .windows and number <= 2) {
+        log.warn("Ignoring the progress file descriptor {}, it must not be stdin, stdout or stderr", .{number});
+        return null;
+    }
+
+    const handle: os.fd_t = if (builtin.os.tag == .windows) @ptrFromInt(number) else @intCast(number);
+    progress_file = std.fs.File{ .handle = handle };
+    return progress_file;
+}
@@ -0,0 +1,171 @@
+/////
+// Allocates a pseudo-terminal for the Erlang VM, and proxies our own stdin/stdout through it.
+// This gives the BEAM a real terminal even when we were launched without one (CI, `ssh` without `-t`, etc.)
+// Only supported on POSIX systems.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const shutil = @import("shutil.zig");
+
+const os = std.os;
+const log = std.log;
+
+const EnvMap = std.process.EnvMap;
+
+const TIOCSCTTY: u32 = switch (builtin.os.tag) {
+    .linux => 0x540E,
+    else => 0x20007461,
+};
+
+const TIOCGWINSZ: u32 = switch (builtin.os.tag) {
+    .linux => 0x5413,
+    else => 0x40087468,
+};
+
+const TIOCSWINSZ: u32 = switch (builtin.os.tag) {
+    .linux => 0x5414,
+    else => 0x80087467,
+};
+
+const WindowSize = extern struct {
+    rows: u16,
+    cols: u16,
+    x_pixels: u16,
+    y_pixels: u16,
+};
+
+extern "c" fn posix_openpt(flags: c_int) c_int;
+extern "c" fn grantpt(fd: c_int) c_int;
+extern "c" fn unlockpt(fd: c_int) c_int;
+extern "c" fn ptsname(fd: c_int) ?[*:0]const u8;
+extern "c" fn setsid() c_int;
+extern "c" fn cfmakeraw(termios_p: *os.termios) void;
+
+const PROXY_BUFFER_SIZE = 4096;
+
+pub const Session = struct {
+    pid: os.pid_t,
+    master: os.fd_t,
+    stdin_open: bool = true,
+    original_termios: ?os.termios = null,
+};
+
+// Opens a new PTY pair, forks, and executes the given command on the slave side
+pub fn spawn(allocator: std.mem.Allocator, argv: []const []const u8, env_map: *const EnvMap) !Session {
+    const master = posix_openpt(os.O.RDWR | os.O.NOCTTY);
+    if (master < 0) {
+        return error.PtyOpenFailed;
+    }
+
+    if (grantpt(master) != 0 or unlockpt(master) != 0) {
+        os.close(master);
+        return error.PtyOpenFailed;
+    }
+
+    const slave_name = ptsname(master) orelse {
+        os.close(master);
+        return error.PtyOpenFailed;
+    };
+
+    log.debug("Allocated PTY: {s}", .{slave_name});
+
+    const pid = try os.fork();
+    if (pid == 0) {
+        // We are the child, make the slave our controlling terminal and become the VM
+        os.close(master);
+        _ = setsid();
+        shutil.exit_with_parent();
+
+        const slave = os.openZ(slave_name, os.O.RDWR, 0) catch os.exit(127);
+        _ = std.c.ioctl(slave, @bitCast(TIOCSCTTY), @as(c_int, 0));
+
+        os.dup2(slave, os.STDIN_FILENO) catch os.exit(127);
+        os.dup2(slave, os.STDOUT_FILENO) catch os.exit(127);
+        os.dup2(slave, os.STDERR_FILENO) catch os.exit(127);
+        if (slave > os.STDERR_FILENO) {
+            os.close(slave);
+        }
+
+        const err = std.process.execve(allocator, argv, env_map);
+        log.err("Failed to launch the Erlang VM inside of a PTY: {!}", .{err});
+        os.exit(127);
+    }
+
+    var session = Session{ .pid = pid, .master = master };
+
+    // If we have a real terminal on our side, put it into raw mode so the VM does all the line editing
+    if (os.isatty(os.STDIN_FILENO)) {
+        if (os.tcgetattr(os.STDIN_FILENO)) |termios| {
+            session.original_termios = termios;
+            var raw = termios;
+            cfmakeraw(&raw);
+            os.tcsetattr(os.STDIN_FILENO, .NOW, raw) catch {};
+        } else |_| {}
+    }
+
+    sync_window_size(&session);
+
+    return session;
+}
+
+// Copies the size of our terminal onto the PTY, the kernel then sends SIGWINCH to the VM for us
+pub fn sync_window_size(session: *Session) void {
+    var size: WindowSize = undefined;
+    if (std.c.ioctl(os.STDOUT_FILENO, @bitCast(TIOCGWINSZ), &size) != 0) {
+        if (std.c.ioctl(os.STDIN_FILENO, @bitCast(TIOCGWINSZ), &size) != 0) {
+            return;
+        }
+    }
+
+    _ = std.c.ioctl(session.master, @bitCast(TIOCSWINSZ), &size);
+}
+
+// Copies any pending bytes between our stdio and the PTY, waiting at most `timeout_ms` for some to arrive.
+// Returns false once the child has closed its side of the PTY.
+pub fn pump(session: *Session, timeout_ms: i32) bool {
+    var fds = [_]os.pollfd{
+        .{ .fd = session.master, .events = os.POLL.IN, .revents = 0 },
+        .{ .fd = if (session.stdin_open) os.STDIN_FILENO else -1, .events = os.POLL.IN, .revents = 0 },
+    };
+
+    var buffer: [PROXY_BUFFER_SIZE]u8 = undefined;
+
+    _ = os.poll(&fds, timeout_ms) catch return false;
+
+    if (fds[0].revents != 0) {
+        const count = os.read(session.master, &buffer) catch 0;
+        if (count == 0) {
+            return false;
+        }
+        write_all(os.STDOUT_FILENO, buffer[0..count]);
+    }
+
+    if (fds[1].revents != 0) {
+        const count = os.read(os.STDIN_FILENO, &buffer) catch 0;
+        if (count == 0) {
+            // Our stdin is gone, stop polling it but keep draining the VM output
+            session.stdin_open = false;
+        } else {
+            write_all(session.master, buffer[0..count]);
+        }
+    }
+
+    return true;
+}
+
+// Restores our terminal and closes the master side of the PTY
+pub fn close(session: *Session) void {
+    if (session.original_termios) |termios| {
+        os.tcsetattr(os.STDIN_FILENO, .NOW, termios) catch {};
+    }
+    os.close(session.master);
+}
+
+fn write_all(fd: os.fd_t, bytes: []const u8) void {
+    var index: usize = 0;
+    while (index < bytes.len) {
+        index += os.write(fd, bytes[index..]) catch return;
+    }
+}
@@ -0,0 +1,61 @@
+/////
+// Files unpacked from a binary downloaded through a browser can inherit the `com.apple.quarantine` attribute,
+// which makes Gatekeeper prompt for, or kill, the ERTS executables. We clear it on the files we extract.
+// MacOS only.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const fs = std.fs;
+const log = std.log;
+
+const QUARANTINE_ATTRIBUTE = "com.apple.quarantine";
+const XATTR_NOFOLLOW = 0x0001;
+
+extern "c" fn removexattr(path: [*:0]const u8, name: [*:0]const u8, options: c_int) c_int;
+
+// Removes the quarantine attribute from every file in `install_dir`
+pub fn clear(allocator: std.mem.Allocator, install_dir: []const u8) void {
+    if (builtin.os.tag != .macos) {
+        return;
+    } else {
+        var dir = fs.openIterableDirAbsolute(install_dir, .{}) catch return;
+        defer dir.close();
+
+        var walker = dir.walk(allocator) catch return;
+        defer walker.deinit();
+
+        var cleared: usize = 0;
+        while (walker.next() catch null) |entry| {
+            if (entry.kind != .file) {
+                continue;
+            }
+
+            const path = fs.path.joinZ(allocator, &[_][]const u8{ install_dir, entry.path }) catch continue;
+            // Fails with ENOATTR for files that were never quarantined, which is the common case
+            if (removexattr(path, QUARANTINE_ATTRIBUTE, XATTR_NOFOLLOW) == 0) {
+                cleared += 1;
+            }
+        }
+
+        if (cleared > 0) {
+            log.debug("Cleared the quarantine attribute from {} files", .{cleared});
+        }
+    }
+}
+
+// Explains a failure to start the VM when it looks like Gatekeeper got in the way
+pub fn explain_launch_error(err: anyerror, install_dir: []const u8) void {
+    if (builtin.os.tag != .macos) {
+        return;
+    }
+
+    switch (err) {
+        error.AccessDenied, error.PermissionDenied => {
+            log.err("MacOS refused to run the Erlang runtime, this is usually Gatekeeper blocking quarantined files.", .{});
+            log.err("Run `xattr -dr {s} {s}` and try again, or allow it under System Settings > Privacy & Security.", .{ QUARANTINE_ATTRIBUTE, install_dir });
+        },
+        else => {},
+    }
+}
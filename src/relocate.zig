@@ -0,0 +1,146 @@
+/////
+// Moves an existing install to a new root directory (copy, verify, then remove the old one), and remembers the new
+// root in a marker file inside the default install directory so later runs find it without re-extracting.
+/////
+
+const std = @import("std");
+const build_options = @import("build_options");
+
+const fs = std.fs;
+const log = std.log;
+const Sha256 = std.crypto.hash.sha2.Sha256;
+
+const install_suffix = build_options.INSTALL_SUFFIX;
+
+const MAX_MARKER_SIZE = fs.MAX_PATH_BYTES;
+const HASH_BUFFER_SIZE = 64 * 1024;
+
+// The marker lives in the default install directory, which is the one place we can find without any configuration
+pub fn get_marker_path(allocator: std.mem.Allocator) ![]const u8 {
+    const default_base = try fs.getAppDataDir(allocator, install_suffix);
+    const marker_name = try std.fmt.allocPrint(allocator, "{s}.location", .{build_options.RELEASE_NAME});
+    return try fs.path.join(allocator, &[_][]const u8{ default_base, marker_name });
+}
+
+// Returns the root an install was relocated to, or null if it never was
+pub fn read_marker(allocator: std.mem.Allocator) ?[]const u8 {
+    const marker_path = get_marker_path(allocator) catch return null;
+    const file = fs.openFileAbsolute(marker_path, .{}) catch return null;
+    defer file.close();
+
+    const content = file.readToEndAlloc(allocator, MAX_MARKER_SIZE) catch return null;
+    const root = std.mem.trim(u8, content, " \r\n");
+    if (root.len == 0) {
+        return null;
+    }
+    return root;
+}
+
+fn write_marker(allocator: std.mem.Allocator, new_root: []const u8) !void {
+    const marker_path = try get_marker_path(allocator);
+    try fs.cwd().makePath(fs.path.dirname(marker_path).?);
+
+    const file = try fs.createFileAbsolute(marker_path, .{ .truncate = true });
+    defer file.close();
+    try file.writeAll(new_root);
+}
+
+// Returns the base install directory for a given root, same layout as the `<APP>_INSTALL_DIR` override
+pub fn get_base_dir(allocator: std.mem.Allocator, root: []const u8) ![]const u8 {
+    return try fs.path.join(allocator, &[_][]const u8{ root, install_suffix });
+}
+
+// Copies `install_dir` under `new_root`, verifies the copy, points future runs at it and removes the original.
+// Returns the new install directory.
+pub fn relocate(allocator: std.mem.Allocator, install_dir: []const u8, new_root: []const u8) ![]const u8 {
+    const new_base = try get_base_dir(allocator, new_root);
+    const new_install_dir = try fs.path.join(allocator, &[_][]const u8{ new_base, fs.path.basename(install_dir) });
+
+    if (std.mem.eql(u8, new_install_dir, install_dir)) {
+        return error.SameLocation;
+    }
+
+    if (fs.accessAbsolute(new_install_dir, .{})) {
+        return error.DestinationExists;
+    } else |_| {}
+
+    try fs.cwd().makePath(new_install_dir);
+    errdefer fs.deleteTreeAbsolute(new_install_dir) catch {};
+
+    log.debug("Copying {s} to {s}", .{ install_dir, new_install_dir });
+    try copy_tree(allocator, install_dir, new_install_dir);
+
+    log.debug("Verifying the copy in {s}", .{new_install_dir});
+    try verify_tree(allocator, install_dir, new_install_dir);
+
+    try write_marker(allocator, new_root);
+
+    // Only once everything is in place, the old copy is no longer needed
+    try fs.deleteTreeAbsolute(install_dir);
+
+    return new_install_dir;
+}
+
+fn copy_tree(allocator: std.mem.Allocator, src_path: []const u8, dest_path: []const u8) !void {
+    var src_dir = try fs.openIterableDirAbsolute(src_path, .{});
+    defer src_dir.close();
+    var dest_dir = try fs.openDirAbsolute(dest_path, .{});
+    defer dest_dir.close();
+
+    var walker = try src_dir.walk(allocator);
+    defer walker.deinit();
+
+    while (try walker.next()) |entry| {
+        switch (entry.kind) {
+            .directory => try dest_dir.makePath(entry.path),
+            // `copyFile` keeps the permission bits, executables stay executable
+            .file => try src_dir.dir.copyFile(entry.path, dest_dir, entry.path, .{}),
+            .sym_link => {
+                var target_buf: [fs.MAX_PATH_BYTES]u8 = undefined;
+                const target = try src_dir.dir.readLink(entry.path, &target_buf);
+                try dest_dir.symLink(target, entry.path, .{});
+            },
+            else => log.warn("Skipping unsupported file type during relocation: {s}", .{entry.path}),
+        }
+    }
+}
+
+fn verify_tree(allocator: std.mem.Allocator, src_path: []const u8, dest_path: []const u8) !void {
+    var src_dir = try fs.openIterableDirAbsolute(src_path, .{});
+    defer src_dir.close();
+    var dest_dir = try fs.openDirAbsolute(dest_path, .{});
+    defer dest_dir.close();
+
+    var walker = try src_dir.walk(allocator);
+    defer walker.deinit();
+
+    while (try walker.next()) |entry| {
+        if (entry.kind != .file) {
+            continue;
+        }
+
+        const src_hash = try hash_file(src_dir.dir, entry.path);
+        const dest_hash = try hash_file(dest_dir, entry.path);
+        if (!std.mem.eql(u8, &src_hash, &dest_hash)) {
+            log.err("Relocated copy of {s} does not match the original", .{entry.path});
+            return error.VerificationFailed;
+        }
+    }
+}
+
+fn hash_file(dir: fs.Dir, path: []const u8) ![Sha256.digest_length]u8 {
+    const file = try dir.openFile(path, .{});
+    defer file.close();
+
+    var hasher = Sha256.init(.{});
+    var buffer: [HASH_BUFFER_SIZE]u8 = undefined;
+    while (true) {
+        const count = try file.read(&buffer);
+        if (count == 0) break;
+        hasher.update(buffer[0..count]);
+    }
+
+    var digest: [Sha256.digest_length]u8 = undefined;
+    hasher.final(&digest);
+    return digest;
+}
@@ -0,0 +1,51 @@
+/////
+// Raises the open file descriptor limit before launching the VM, apps handling lots of sockets
+// otherwise hit the common default soft limit of 1024 very quickly. POSIX only.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const os = std.os;
+const log = std.log;
+
+// MacOS reports an unlimited hard limit, but refuses anything above OPEN_MAX for the soft limit
+const DARWIN_OPEN_MAX = 10240;
+
+// Raises the soft RLIMIT_NOFILE to `wanted`, or to the hard limit if `wanted` is null
+pub fn raise_nofile(wanted: ?u64) void {
+    if (builtin.os.tag == .windows) {
+        return;
+    } else {
+        var limits = os.getrlimit(.NOFILE) catch |err| {
+            log.debug("Could not read the open file limit: {!}", .{err});
+            return;
+        };
+
+        const previous = limits.cur;
+        var target = limits.max;
+        if (wanted) |value| {
+            if (value > limits.max) {
+                log.warn("The open file limit can only be raised to the hard limit of {}, not {}", .{ limits.max, value });
+            }
+            target = @min(value, limits.max);
+        }
+
+        if (builtin.os.tag.isDarwin()) {
+            target = @min(target, DARWIN_OPEN_MAX);
+        }
+
+        if (target <= previous) {
+            log.debug("Open file limit is {}, leaving it as-is", .{previous});
+            return;
+        }
+
+        limits.cur = target;
+        os.setrlimit(.NOFILE, limits) catch |err| {
+            log.warn("Could not raise the open file limit from {} to {}: {!}", .{ previous, target, err });
+            return;
+        };
+
+        log.debug("Raised the open file limit from {} to {}", .{ previous, target });
+    }
+}
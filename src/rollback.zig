@@ -0,0 +1,132 @@
+/////
+// Rolling back to the previously installed version after a bad update.
+// A rollback pins the previous install tree with a marker in the install root, which the wrapper launches instead of
+// its own payload, and swaps the wrapper binary with the one kept by `--burrito update` if there is one. The marker
+// also records whether the binaries were swapped, so `--burrito rollback --undo` only swaps them back then.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const install = @import("install.zig");
+
+const fs = std.fs;
+const log = std.log;
+
+const Install = install.Install;
+
+const MAX_PIN_SIZE = fs.MAX_PATH_BYTES;
+
+fn get_pin_path(allocator: std.mem.Allocator, base_install_dir: []const u8) ![]const u8 {
+    const file_name = try std.fmt.allocPrint(allocator, "{s}.pinned", .{build_options.RELEASE_NAME});
+    return try fs.path.join(allocator, &[_][]const u8{ base_install_dir, file_name });
+}
+
+pub const Pin = struct {
+    // The previous install launched instead of our own payload, null if only the binary was rolled back
+    install: ?Install,
+    // Whether the wrapper binary was swapped with the one `--burrito update` kept
+    swapped_binary: bool,
+    // The version rolled back from
+    from_version: []const u8,
+};
+
+// Returns the active rollback, or null if there is none (or its install is gone since)
+// The marker holds the pinned install directory's name (empty for none), `1` if the binaries were swapped, and the
+// version rolled back from, one per line
+pub fn read_pin(allocator: std.mem.Allocator, base_install_dir: []const u8) ?Pin {
+    const pin_path = get_pin_path(allocator, base_install_dir) catch return null;
+    const file = fs.openFileAbsolute(pin_path, .{}) catch return null;
+    defer file.close();
+
+    const content = file.readToEndAlloc(allocator, MAX_PIN_SIZE) catch return null;
+    var lines = std.mem.splitScalar(u8, content, '\n');
+    const dir_name = std.mem.trim(u8, lines.next() orelse "", " \r");
+    const swapped_binary = std.mem.eql(u8, std.mem.trim(u8, lines.next() orelse "", " \r"), "1");
+    const from_version = std.mem.trim(u8, lines.next() orelse "", " \r");
+
+    var pinned: ?Install = null;
+    if (dir_name.len > 0) {
+        const install_dir = fs.path.join(allocator, &[_][]const u8{ base_install_dir, dir_name }) catch return null;
+        pinned = install.load_install_from_path(allocator, install_dir) catch null;
+    }
+    if (pinned == null and !swapped_binary) {
+        return null;
+    }
+
+    return Pin{ .install = pinned, .swapped_binary = swapped_binary, .from_version = from_version };
+}
+
+// The previous install the wrapper launches instead of its own payload, if a rollback pinned one
+pub fn read_pinned_install(allocator: std.mem.Allocator, base_install_dir: []const u8) ?Install {
+    const active = read_pin(allocator, base_install_dir) orelse return null;
+    return active.install;
+}
+
+pub fn pin(allocator: std.mem.Allocator, base_install_dir: []const u8, pinned: ?*const Install, swapped_binary: bool, from_version: []const u8) !void {
+    const pin_path = try get_pin_path(allocator, base_install_dir);
+    const file = try fs.createFileAbsolute(pin_path, .{ .truncate = true });
+    defer file.close();
+
+    const dir_name = if (pinned) |pinned_install| fs.path.basename(pinned_install.install_dir_path) else "";
+    try file.writer().print("{s}\n{s}\n{s}\n", .{ dir_name, if (swapped_binary) "1" else "0", from_version });
+}
+
+pub fn unpin(allocator: std.mem.Allocator, base_install_dir: []const u8) void {
+    const pin_path = get_pin_path(allocator, base_install_dir) catch return;
+    fs.deleteFileAbsolute(pin_path) catch {};
+}
+
+// Lists the installs of this app in the install root older than `current`, newest first
+pub fn find_older_installs(allocator: std.mem.Allocator, base_install_dir: []const u8, current: *const Install) ![]Install {
+    var older = std.ArrayList(Install).init(allocator);
+
+    var base_dir = try fs.openIterableDirAbsolute(base_install_dir, .{});
+    defer base_dir.close();
+
+    var itr = base_dir.iterate();
+    while (try itr.next()) |entry| {
+        if (entry.kind != .directory) {
+            continue;
+        }
+
+        const path = try fs.path.join(allocator, &[_][]const u8{ base_install_dir, entry.name });
+        const other = (try install.load_install_from_path(allocator, path)) orelse continue;
+
+        if (!std.mem.eql(u8, current.metadata.app_name, other.metadata.app_name)) {
+            continue;
+        }
+
+        if (std.SemanticVersion.order(current.version, other.version) == .gt) {
+            try older.append(other);
+        }
+    }
+
+    std.mem.sort(Install, older.items, {}, newer_first);
+    return older.items;
+}
+
+fn newer_first(_: void, lhs: Install, rhs: Install) bool {
+    return std.SemanticVersion.order(lhs.version, rhs.version) == .gt;
+}
+
+// Swaps the running binary with the one `--burrito update` kept, returns false if there isn't one
+pub fn swap_binaries(allocator: std.mem.Allocator) !bool {
+    const exe_path = try fs.selfExePathAlloc(allocator);
+    const backup_path = try std.fmt.allocPrint(allocator, "{s}.old", .{exe_path});
+    const swap_path = try std.fmt.allocPrint(allocator, "{s}.swap", .{exe_path});
+
+    fs.accessAbsolute(backup_path, .{}) catch return false;
+
+    // Same dance on every platform, a running executable can be renamed, but not always overwritten
+    try fs.renameAbsolute(exe_path, swap_path);
+    fs.renameAbsolute(backup_path, exe_path) catch |err| {
+        fs.renameAbsolute(swap_path, exe_path) catch {};
+        return err;
+    };
+    try fs.renameAbsolute(swap_path, backup_path);
+
+    log.debug("Swapped {s} with {s}", .{ exe_path, backup_path });
+    return true;
+}
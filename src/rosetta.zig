@@ -0,0 +1,34 @@
+/////
+// Detects an x86_64 build running under Rosetta 2 on an Apple Silicon Mac. It works, but the whole VM (and the JIT)
+// runs translated, so we point users at the native aarch64 build instead. MacOS only.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const log = std.log;
+
+extern "c" fn sysctlbyname(name: [*:0]const u8, oldp: ?*anyopaque, oldlenp: ?*usize, newp: ?*anyopaque, newlen: usize) c_int;
+
+// True when this process is being translated by Rosetta 2
+pub fn is_translated() bool {
+    if (builtin.os.tag != .macos or builtin.cpu.arch != .x86_64) {
+        return false;
+    } else {
+        // The sysctl doesn't exist on Intel Macs, which is just as well a "no"
+        var translated: c_int = 0;
+        var size: usize = @sizeOf(c_int);
+        if (sysctlbyname("sysctl.proc_translated", &translated, &size, null, 0) != 0) {
+            return false;
+        }
+        return translated == 1;
+    }
+}
+
+// Warns about running translated, does nothing on native hardware
+pub fn warn_if_translated() void {
+    if (is_translated()) {
+        log.warn("This x86_64 build is running under Rosetta 2 on an Apple Silicon Mac, expect reduced performance.", .{});
+        log.warn("Use the aarch64 (arm64) build of this application to run natively.", .{});
+    }
+}
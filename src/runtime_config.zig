@@ -0,0 +1,292 @@
+/////
+// The `runtime_config` option embeds a small TOML file into the wrapper, its settings override the release's Burrito
+// options at startup. Keys are the option names, a `[section]` is a prefix to the keys under it, so these are the same:
+//
+//   update_channel = "beta"
+//
+//   [update]
+//   channel = "beta"
+//
+// Only the part of TOML these options need is supported: sections, strings, integers, booleans and single-line
+// arrays of strings, with `#` comments. The file is checked when the wrapper is built (see build.zig), so a mistake
+// fails the build instead of every launch.
+/////
+
+const std = @import("std");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const mem = std.mem;
+
+pub const Value = union(enum) {
+    string: []const u8,
+    integer: i64,
+    boolean: bool,
+    array: []const []const u8,
+};
+
+pub const Entry = struct {
+    // With the section prefix, e.g. `update_channel`
+    key: []const u8,
+    // The parts of the key, e.g. `update` (`a.b` for nested sections) and `channel`
+    section: []const u8,
+    name: []const u8,
+    value: Value,
+    line: usize,
+};
+
+// Where and why the config was rejected
+pub const Diagnostic = struct {
+    line: usize = 0,
+    message: []const u8 = "",
+};
+
+// Facts about the build itself, a config can't change what the binary is
+const build_fields = [_][]const u8{
+    "app_name",
+    "zig_version",
+    "zig_build_arguments",
+    "app_version",
+    "options",
+    "erts_version",
+    "otp_version",
+    "target",
+    "static_erts",
+    "build_timestamp",
+    "git_sha",
+    "translations",
+};
+
+pub fn parse(allocator: mem.Allocator, source: []const u8, diagnostic: *Diagnostic) ![]Entry {
+    var entries = std.ArrayList(Entry).init(allocator);
+    var section: []const u8 = "";
+    var section_prefix: []const u8 = "";
+
+    var lines = mem.splitScalar(u8, source, '\n');
+    var line_number: usize = 0;
+    while (lines.next()) |raw_line| {
+        line_number += 1;
+        diagnostic.line = line_number;
+
+        const line = mem.trim(u8, raw_line, " \t\r");
+        if (line.len == 0 or line[0] == '#') {
+            continue;
+        }
+
+        if (line[0] == '[') {
+            const end = mem.indexOfScalar(u8, line, ']') orelse return fail(diagnostic, "expected `]` after the section name");
+            if (!is_comment_or_empty(line[end + 1 ..])) {
+                return fail(diagnostic, "unexpected text after the section name");
+            }
+            const name = mem.trim(u8, line[1..end], " \t");
+            if (!is_bare_key(name, true)) {
+                return fail(diagnostic, "invalid section name");
+            }
+            section = name;
+            section_prefix = try mem.replaceOwned(u8, allocator, name, ".", "_");
+            continue;
+        }
+
+        const equals = mem.indexOfScalar(u8, line, '=') orelse return fail(diagnostic, "expected `key = value`");
+        const key = mem.trim(u8, line[0..equals], " \t");
+        if (!is_bare_key(key, false)) {
+            return fail(diagnostic, "invalid key");
+        }
+
+        var cursor = Cursor{ .text = line[equals + 1 ..], .allocator = allocator, .diagnostic = diagnostic };
+        const value = try cursor.read_value();
+        if (!is_comment_or_empty(cursor.rest())) {
+            return fail(diagnostic, "unexpected text after the value");
+        }
+
+        const full_key = if (section.len > 0) try std.fmt.allocPrint(allocator, "{s}_{s}", .{ section_prefix, key }) else key;
+        for (entries.items) |existing| {
+            if (mem.eql(u8, existing.key, full_key)) {
+                return fail(diagnostic, "key is set twice");
+            }
+        }
+        try entries.append(.{ .key = full_key, .section = section, .name = key, .value = value, .line = line_number });
+    }
+
+    return entries.toOwnedSlice();
+}
+
+// Overrides the options in `meta` with the config's entries
+pub fn apply(meta: *MetaStruct, entries: []const Entry, diagnostic: *Diagnostic) !void {
+    for (entries) |entry| {
+        diagnostic.line = entry.line;
+        apply_entry(meta, entry) catch |err| {
+            return fail(diagnostic, switch (err) {
+                error.BuildField => "this is decided by the build and can't be changed",
+                error.UnknownKey => "unknown option",
+                error.WrongType => "wrong type for this option",
+                error.OutOfRange => "number out of range for this option",
+            });
+        };
+    }
+}
+
+fn apply_entry(meta: *MetaStruct, entry: Entry) error{ BuildField, UnknownKey, WrongType, OutOfRange }!void {
+    for (build_fields) |name| {
+        if (mem.eql(u8, name, entry.key)) {
+            return error.BuildField;
+        }
+    }
+
+    inline for (std.meta.fields(MetaStruct)) |field| {
+        if (comptime is_build_field(field.name)) continue;
+        if (mem.eql(u8, field.name, entry.key)) {
+            @field(meta, field.name) = try convert(field.type, entry.value);
+            return;
+        }
+    }
+    return error.UnknownKey;
+}
+
+fn convert(comptime T: type, value: Value) error{ WrongType, OutOfRange }!T {
+    if (T == []const u8) {
+        return if (value == .string) value.string else error.WrongType;
+    }
+    if (T == []const []const u8) {
+        return if (value == .array) value.array else error.WrongType;
+    }
+    return switch (@typeInfo(T)) {
+        .Optional => |optional| try convert(optional.child, value),
+        .Bool => if (value == .boolean) value.boolean else error.WrongType,
+        .Int => if (value == .integer) std.math.cast(T, value.integer) orelse error.OutOfRange else error.WrongType,
+        else => error.WrongType,
+    };
+}
+
+fn is_build_field(comptime name: []const u8) bool {
+    for (build_fields) |build_field| {
+        if (mem.eql(u8, build_field, name)) return true;
+    }
+    return false;
+}
+
+fn fail(diagnostic: *Diagnostic, message: []const u8) error{InvalidConfig} {
+    diagnostic.message = message;
+    return error.InvalidConfig;
+}
+
+fn is_comment_or_empty(text: []const u8) bool {
+    const trimmed = mem.trim(u8, text, " \t");
+    return trimmed.len == 0 or trimmed[0] == '#';
+}
+
+fn is_bare_key(key: []const u8, allow_dots: bool) bool {
+    if (key.len == 0) return false;
+    for (key) |c| {
+        if (!(std.ascii.isAlphanumeric(c) or c == '_' or (allow_dots and c == '.'))) return false;
+    }
+    return true;
+}
+
+// Reads one value from the text after the `=`
+const Cursor = struct {
+    text: []const u8,
+    pos: usize = 0,
+    allocator: mem.Allocator,
+    diagnostic: *Diagnostic,
+
+    fn rest(self: *const Cursor) []const u8 {
+        return self.text[self.pos..];
+    }
+
+    fn skip_spaces(self: *Cursor) void {
+        while (self.pos < self.text.len and (self.text[self.pos] == ' ' or self.text[self.pos] == '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(self: *const Cursor) ?u8 {
+        return if (self.pos < self.text.len) self.text[self.pos] else null;
+    }
+
+    fn read_value(self: *Cursor) !Value {
+        self.skip_spaces();
+        const c = self.peek() orelse return fail(self.diagnostic, "missing value");
+        return switch (c) {
+            '"', '\'' => .{ .string = try self.read_string() },
+            '[' => .{ .array = try self.read_array() },
+            't', 'f' => .{ .boolean = try self.read_boolean() },
+            else => .{ .integer = try self.read_integer() },
+        };
+    }
+
+    fn read_string(self: *Cursor) ![]const u8 {
+        const quote = self.text[self.pos];
+        self.pos += 1;
+
+        var string = std.ArrayList(u8).init(self.allocator);
+        while (self.peek()) |c| {
+            self.pos += 1;
+            if (c == quote) {
+                return string.toOwnedSlice();
+            }
+            // Single quoted strings are literal
+            if (c == '\\' and quote == '"') {
+                const escaped = self.peek() orelse break;
+                self.pos += 1;
+                try string.append(switch (escaped) {
+                    '"' => '"',
+                    '\\' => '\\',
+                    'n' => '\n',
+                    't' => '\t',
+                    else => return fail(self.diagnostic, "unsupported escape sequence"),
+                });
+                continue;
+            }
+            try string.append(c);
+        }
+        return fail(self.diagnostic, "unterminated string");
+    }
+
+    fn read_array(self: *Cursor) ![]const []const u8 {
+        self.pos += 1;
+
+        var items = std.ArrayList([]const u8).init(self.allocator);
+        while (true) {
+            self.skip_spaces();
+            const c = self.peek() orelse return fail(self.diagnostic, "expected `]`, arrays must be on one line");
+            if (c == ']') {
+                self.pos += 1;
+                return items.toOwnedSlice();
+            }
+            if (c != '"' and c != '\'') {
+                return fail(self.diagnostic, "arrays can only hold strings");
+            }
+            try items.append(try self.read_string());
+
+            self.skip_spaces();
+            switch (self.peek() orelse 0) {
+                ',' => self.pos += 1,
+                ']' => {},
+                else => return fail(self.diagnostic, "expected `,` or `]`"),
+            }
+        }
+    }
+
+    fn read_boolean(self: *Cursor) !bool {
+        const word = self.read_word();
+        if (mem.eql(u8, word, "true")) return true;
+        if (mem.eql(u8, word, "false")) return false;
+        return fail(self.diagnostic, "expected a string, number, boolean or array");
+    }
+
+    fn read_integer(self: *Cursor) !i64 {
+        const word = self.read_word();
+        return std.fmt.parseInt(i64, word, 10) catch return fail(self.diagnostic, "expected a string, number, boolean or array");
+    }
+
+    // Up to the next space or comment, `parseInt` accepts the `_` separators TOML allows
+    fn read_word(self: *Cursor) []const u8 {
+        const start = self.pos;
+        while (self.peek()) |c| {
+            if (c == ' ' or c == '\t' or c == '#') break;
+            self.pos += 1;
+        }
+        return self.text[start..self.pos];
+    }
+};
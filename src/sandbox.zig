@@ -0,0 +1,157 @@
+/////
+// Runs the payload extraction in a forked child that restricts itself first, so a malicious payload or a bug in the
+// archive parser can't be turned into writes outside of the install directory (Landlock), or into running programs
+// and opening sockets (seccomp). Both restrictions are permanent, which is why they never touch the process that
+// later becomes the VM.
+// Linux only, Landlock needs kernel 5.13 or newer and is skipped if it's unavailable.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const exit_codes = @import("exit_codes.zig");
+const logger = @import("logger.zig");
+
+const log = std.log;
+const os = std.os;
+const linux = os.linux;
+
+// Landlock ABI v1 filesystem access rights
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+
+// Only writes are handled by the ruleset, reading stays allowed everywhere
+const WRITE_ACCESS = ACCESS_FS_WRITE_FILE | ACCESS_FS_REMOVE_DIR | ACCESS_FS_REMOVE_FILE | ACCESS_FS_MAKE_CHAR |
+    ACCESS_FS_MAKE_DIR | ACCESS_FS_MAKE_REG | ACCESS_FS_MAKE_SOCK | ACCESS_FS_MAKE_FIFO | ACCESS_FS_MAKE_BLOCK | ACCESS_FS_MAKE_SYM;
+
+const LANDLOCK_RULE_PATH_BENEATH = 1;
+
+const RulesetAttr = extern struct {
+    handled_access_fs: u64,
+};
+
+const SockFilter = extern struct {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+};
+
+const SockFprog = extern struct {
+    len: u16,
+    filter: [*]const SockFilter,
+};
+
+const BPF_LD_W_ABS = 0x20;
+const BPF_JMP_JEQ_K = 0x15;
+const BPF_RET_K = 0x06;
+
+const SECCOMP_MODE_FILTER = 2;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff0000;
+const SECCOMP_RET_ERRNO: u32 = 0x00050000;
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x80000000;
+
+// Offsets into `struct seccomp_data`
+const SECCOMP_DATA_NR = 0;
+const SECCOMP_DATA_ARCH = 4;
+
+// Nothing the extraction does needs these. io_uring could open sockets without going through the filter, so the
+// extraction falls back to regular writes in here.
+const denied_syscalls = [_]linux.SYS{ .execve, .execveat, .ptrace, .process_vm_writev, .socket, .connect, .bind, .mount, .io_uring_setup };
+
+// Runs `work(context)` in a sandboxed child process and waits for it
+pub fn run(install_dir: []const u8, context: anytype, comptime work: fn (@TypeOf(context)) anyerror!void) !void {
+    if (builtin.os.tag != .linux) {
+        return work(context);
+    } else {
+        const pid = try os.fork();
+        if (pid == 0) {
+            restrict(install_dir) catch |err| {
+                logger.err("Failed to sandbox the payload extraction: {!}", .{err});
+                os.exit(1);
+            };
+            // The class of the failure survives through the exit code
+            work(context) catch |err| {
+                logger.err("Failed to install the application payload into {s}: {!}", .{ install_dir, err });
+                exit_codes.exit_with(err);
+            };
+            os.exit(0);
+        }
+
+        const result = os.waitpid(pid, 0);
+        if (!os.W.IFEXITED(result.status)) {
+            return error.SandboxedInstallFailed;
+        }
+        switch (os.W.EXITSTATUS(result.status)) {
+            0 => {},
+            1 => return error.SandboxedInstallFailed,
+            else => |code| return exit_codes.to_error(code),
+        }
+    }
+}
+
+fn restrict(install_dir: []const u8) !void {
+    // Required for an unprivileged process to install a seccomp filter or a Landlock ruleset
+    _ = try os.prctl(.SET_NO_NEW_PRIVS, .{ 1, 0, 0, 0 });
+
+    restrict_writes(install_dir) catch |err| {
+        log.debug("Landlock is not available, extraction is not limited to the install directory: {!}", .{err});
+    };
+
+    try install_seccomp_filter();
+}
+
+fn restrict_writes(install_dir: []const u8) !void {
+    const ruleset_attr = RulesetAttr{ .handled_access_fs = WRITE_ACCESS };
+    const ruleset_rc = linux.syscall3(.landlock_create_ruleset, @intFromPtr(&ruleset_attr), @sizeOf(RulesetAttr), 0);
+    if (linux.getErrno(ruleset_rc) != .SUCCESS) {
+        return error.LandlockUnsupported;
+    }
+    const ruleset_fd: os.fd_t = @intCast(ruleset_rc);
+    defer os.close(ruleset_fd);
+
+    const dir_fd = try os.open(install_dir, os.O.PATH | os.O.DIRECTORY | os.O.CLOEXEC, 0);
+    defer os.close(dir_fd);
+
+    // `struct landlock_path_beneath_attr` is packed, a u64 followed by an i32
+    var path_beneath: [12]u8 = undefined;
+    std.mem.writeIntLittle(u64, path_beneath[0..8], WRITE_ACCESS);
+    std.mem.writeIntLittle(i32, path_beneath[8..12], dir_fd);
+    if (linux.getErrno(linux.syscall4(.landlock_add_rule, @intCast(ruleset_fd), LANDLOCK_RULE_PATH_BENEATH, @intFromPtr(&path_beneath), 0)) != .SUCCESS) {
+        return error.LandlockAddRuleFailed;
+    }
+
+    if (linux.getErrno(linux.syscall2(.landlock_restrict_self, @intCast(ruleset_fd), 0)) != .SUCCESS) {
+        return error.LandlockRestrictFailed;
+    }
+
+    log.debug("Extraction is limited to {s}", .{install_dir});
+}
+
+fn install_seccomp_filter() !void {
+    const filter = comptime blk: {
+        var instructions: [4 + denied_syscalls.len * 2 + 1]SockFilter = undefined;
+        // Kill anything that isn't the native syscall ABI, the numbers below wouldn't mean the same there
+        instructions[0] = .{ .code = BPF_LD_W_ABS, .jt = 0, .jf = 0, .k = SECCOMP_DATA_ARCH };
+        instructions[1] = .{ .code = BPF_JMP_JEQ_K, .jt = 1, .jf = 0, .k = @intFromEnum(linux.AUDIT.ARCH.current) };
+        instructions[2] = .{ .code = BPF_RET_K, .jt = 0, .jf = 0, .k = SECCOMP_RET_KILL_PROCESS };
+        instructions[3] = .{ .code = BPF_LD_W_ABS, .jt = 0, .jf = 0, .k = SECCOMP_DATA_NR };
+        for (denied_syscalls, 0..) |syscall, i| {
+            instructions[4 + i * 2] = .{ .code = BPF_JMP_JEQ_K, .jt = 0, .jf = 1, .k = @intFromEnum(syscall) };
+            instructions[5 + i * 2] = .{ .code = BPF_RET_K, .jt = 0, .jf = 0, .k = SECCOMP_RET_ERRNO | @intFromEnum(linux.E.PERM) };
+        }
+        instructions[instructions.len - 1] = .{ .code = BPF_RET_K, .jt = 0, .jf = 0, .k = SECCOMP_RET_ALLOW };
+        break :blk instructions;
+    };
+
+    const program = SockFprog{ .len = filter.len, .filter = &filter };
+    _ = try os.prctl(.SET_SECCOMP, .{ SECCOMP_MODE_FILTER, @intFromPtr(&program), 0, 0 });
+}
@@ -0,0 +1,309 @@
+/////
+// Finds shared libraries the extracted ERTS binaries and NIFs need but the host doesn't have (libtinfo, libssl, ...),
+// by reading the DT_NEEDED entries of their ELF dynamic sections and looking for each library where the dynamic loader
+// would. Without this the VM dies with an opaque loader error, or `:crypto` fails to load much later. Only relevant on Linux.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const elf = std.elf;
+const fs = std.fs;
+const log = std.log;
+const mem = std.mem;
+
+// The Debian and Ubuntu multiarch tuple of the host
+const multiarch = switch (builtin.cpu.arch) {
+    .arm => "arm-linux-gnueabihf",
+    else => @tagName(builtin.cpu.arch) ++ "-linux-gnu",
+};
+
+// Where the dynamic loader looks when nothing else says otherwise
+const default_search_paths = [_][]const u8{
+    "/lib",
+    "/lib64",
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/local/lib",
+    "/lib/" ++ multiarch,
+    "/usr/lib/" ++ multiarch,
+};
+
+const LD_SO_CONF_DIR = "/etc/ld.so.conf.d";
+
+// Libraries the VM and the common NIFs are linked against, with the packages that usually provide them
+const PackageHint = struct {
+    prefix: []const u8,
+    packages: []const u8,
+};
+
+const package_hints = [_]PackageHint{
+    .{ .prefix = "libtinfo.so.5", .packages = "libtinfo5 (Debian/Ubuntu), ncurses-compat-libs (Fedora/RHEL)" },
+    .{ .prefix = "libtinfo.so.6", .packages = "libtinfo6 (Debian/Ubuntu), ncurses-libs (Fedora/RHEL/Alpine)" },
+    .{ .prefix = "libncurses.so.5", .packages = "libncurses5 (Debian/Ubuntu), ncurses-compat-libs (Fedora/RHEL)" },
+    .{ .prefix = "libncursesw.so.6", .packages = "libncursesw6 (Debian/Ubuntu), ncurses-libs (Fedora/RHEL/Alpine)" },
+    .{ .prefix = "libssl.so.1.1", .packages = "libssl1.1 (Debian/Ubuntu), compat-openssl11 (Fedora/RHEL), libssl1.1 (Alpine)" },
+    .{ .prefix = "libcrypto.so.1.1", .packages = "libssl1.1 (Debian/Ubuntu), compat-openssl11 (Fedora/RHEL), libcrypto1.1 (Alpine)" },
+    .{ .prefix = "libssl.so.3", .packages = "libssl3 (Debian/Ubuntu), openssl-libs (Fedora/RHEL), libssl3 (Alpine)" },
+    .{ .prefix = "libcrypto.so.3", .packages = "libssl3 (Debian/Ubuntu), openssl-libs (Fedora/RHEL), libcrypto3 (Alpine)" },
+    .{ .prefix = "libz.so", .packages = "zlib1g (Debian/Ubuntu), zlib (Fedora/RHEL/Alpine)" },
+    .{ .prefix = "libstdc++.so", .packages = "libstdc++6 (Debian/Ubuntu), libstdc++ (Fedora/RHEL/Alpine)" },
+    .{ .prefix = "libgcc_s.so", .packages = "libgcc-s1 (Debian/Ubuntu), libgcc (Fedora/RHEL/Alpine)" },
+};
+
+pub const Missing = struct {
+    library: []const u8,
+    // The first binary found needing it, relative to the install dir
+    needed_by: []const u8,
+    packages: ?[]const u8,
+};
+
+const Dependencies = struct {
+    needed: [][]const u8,
+    // From DT_RUNPATH / DT_RPATH, with `$ORIGIN` expanded
+    search_paths: [][]const u8,
+};
+
+// Reads the libraries an ELF file depends on, null if it's not a dynamically linked ELF file
+fn read_dependencies(allocator: std.mem.Allocator, path: []const u8) !?Dependencies {
+    const file = try fs.openFileAbsolute(path, .{});
+    defer file.close();
+
+    const header = elf.Header.read(file) catch return null;
+    if (header.endian != .Little) {
+        return null;
+    }
+
+    var loads = std.ArrayList(elf.Elf64_Phdr).init(allocator);
+    var dynamic: ?elf.Elf64_Phdr = null;
+
+    var phdrs = header.program_header_iterator(file);
+    while (try phdrs.next()) |phdr| {
+        switch (phdr.p_type) {
+            elf.PT_LOAD => try loads.append(phdr),
+            elf.PT_DYNAMIC => dynamic = phdr,
+            else => {},
+        }
+    }
+
+    const dynamic_phdr = dynamic orelse return null;
+    const dynamic_bytes = try allocator.alloc(u8, @intCast(dynamic_phdr.p_filesz));
+    _ = try file.preadAll(dynamic_bytes, dynamic_phdr.p_offset);
+
+    var needed_offsets = std.ArrayList(u64).init(allocator);
+    var runpath_offset: ?u64 = null;
+    var strtab_addr: ?u64 = null;
+    var strtab_size: u64 = 0;
+
+    // Each entry is a tag followed by a value, both 8 bytes on 64-bit and 4 bytes on 32-bit (armv7, riscv32)
+    const word_size: usize = if (header.is_64) 8 else 4;
+    var cursor: usize = 0;
+    while (cursor + 2 * word_size <= dynamic_bytes.len) : (cursor += 2 * word_size) {
+        const tag: i64 = if (header.is_64) mem.readIntLittle(i64, dynamic_bytes[cursor..][0..8]) else mem.readIntLittle(i32, dynamic_bytes[cursor..][0..4]);
+        const value: u64 = if (header.is_64) mem.readIntLittle(u64, dynamic_bytes[cursor + 8 ..][0..8]) else mem.readIntLittle(u32, dynamic_bytes[cursor + 4 ..][0..4]);
+        switch (tag) {
+            elf.DT_NULL => break,
+            elf.DT_NEEDED => try needed_offsets.append(value),
+            elf.DT_RUNPATH, elf.DT_RPATH => runpath_offset = value,
+            elf.DT_STRTAB => strtab_addr = value,
+            elf.DT_STRSZ => strtab_size = value,
+            else => {},
+        }
+    }
+
+    // DT_STRTAB is a virtual address, find the segment it's loaded from to get its place in the file
+    const strtab_offset = file_offset(loads.items, strtab_addr orelse return null) orelse return null;
+    const strtab = try allocator.alloc(u8, @intCast(strtab_size));
+    _ = try file.preadAll(strtab, strtab_offset);
+
+    var needed = std.ArrayList([]const u8).init(allocator);
+    for (needed_offsets.items) |offset| {
+        try needed.append(string_at(strtab, offset) orelse continue);
+    }
+
+    var search_paths = std.ArrayList([]const u8).init(allocator);
+    if (runpath_offset) |offset| {
+        const origin = fs.path.dirname(path) orelse "/";
+        var itr = mem.split(u8, string_at(strtab, offset) orelse "", ":");
+        while (itr.next()) |search_path| {
+            if (search_path.len > 0) {
+                try search_paths.append(try mem.replaceOwned(u8, allocator, search_path, "$ORIGIN", origin));
+            }
+        }
+    }
+
+    return Dependencies{ .needed = needed.items, .search_paths = search_paths.items };
+}
+
+fn file_offset(loads: []const elf.Elf64_Phdr, addr: u64) ?u64 {
+    for (loads) |load| {
+        if (addr >= load.p_vaddr and addr < load.p_vaddr + load.p_filesz) {
+            return load.p_offset + (addr - load.p_vaddr);
+        }
+    }
+    return null;
+}
+
+fn string_at(strtab: []const u8, offset: u64) ?[]const u8 {
+    if (offset >= strtab.len) {
+        return null;
+    }
+    const start: usize = @intCast(offset);
+    const end = mem.indexOfScalarPos(u8, strtab, start, 0) orelse return null;
+    return strtab[start..end];
+}
+
+// The system wide search paths: LD_LIBRARY_PATH, the ld.so.conf.d entries, then the defaults
+fn get_system_search_paths(allocator: std.mem.Allocator) ![][]const u8 {
+    var paths = std.ArrayList([]const u8).init(allocator);
+
+    if (std.os.getenv("LD_LIBRARY_PATH")) |ld_library_path| {
+        var itr = mem.split(u8, ld_library_path, ":");
+        while (itr.next()) |path| {
+            if (path.len > 0) {
+                try paths.append(path);
+            }
+        }
+    }
+
+    if (fs.openIterableDirAbsolute(LD_SO_CONF_DIR, .{})) |conf_dir_const| {
+        var conf_dir = conf_dir_const;
+        defer conf_dir.close();
+
+        var itr = conf_dir.iterate();
+        while (itr.next() catch null) |entry| {
+            if (!mem.endsWith(u8, entry.name, ".conf")) {
+                continue;
+            }
+            const content = conf_dir.dir.readFileAlloc(allocator, entry.name, 64 * 1024) catch continue;
+            var lines = mem.split(u8, content, "\n");
+            while (lines.next()) |line| {
+                const trimmed = mem.trim(u8, line, " \t\r");
+                if (mem.startsWith(u8, trimmed, "/")) {
+                    try paths.append(trimmed);
+                }
+            }
+        }
+    } else |_| {}
+
+    try paths.appendSlice(&default_search_paths);
+    return paths.items;
+}
+
+fn is_available(allocator: std.mem.Allocator, library: []const u8, search_paths: []const []const u8) bool {
+    // Anything with a slash is loaded from exactly that path
+    if (mem.indexOfScalar(u8, library, '/') != null) {
+        fs.accessAbsolute(library, .{}) catch return false;
+        return true;
+    }
+
+    for (search_paths) |search_path| {
+        const path = fs.path.join(allocator, &[_][]const u8{ search_path, library }) catch continue;
+        fs.accessAbsolute(path, .{}) catch continue;
+        return true;
+    }
+    return false;
+}
+
+fn get_package_hint(library: []const u8) ?[]const u8 {
+    for (package_hints) |hint| {
+        if (mem.startsWith(u8, library, hint.prefix)) {
+            return hint.packages;
+        }
+    }
+    return null;
+}
+
+// The binaries worth scanning: everything in the ERTS bin dir, and the NIFs shipped in `lib/`
+fn collect_binaries(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) ![][]const u8 {
+    var binaries = std.ArrayList([]const u8).init(allocator);
+
+    const erts_dir_name = try std.fmt.allocPrint(allocator, "erts-{s}", .{meta.erts_version});
+    const erts_bin_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, erts_dir_name, "bin" });
+    if (fs.openIterableDirAbsolute(erts_bin_path, .{})) |erts_bin_dir_const| {
+        var erts_bin_dir = erts_bin_dir_const;
+        defer erts_bin_dir.close();
+
+        var itr = erts_bin_dir.iterate();
+        while (try itr.next()) |entry| {
+            if (entry.kind == .file) {
+                try binaries.append(try fs.path.join(allocator, &[_][]const u8{ erts_bin_path, entry.name }));
+            }
+        }
+    } else |_| {}
+
+    const lib_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, "lib" });
+    if (fs.openIterableDirAbsolute(lib_path, .{})) |lib_dir_const| {
+        var lib_dir = lib_dir_const;
+        defer lib_dir.close();
+
+        var walker = try lib_dir.walk(allocator);
+        defer walker.deinit();
+        while (try walker.next()) |entry| {
+            if (entry.kind == .file and mem.endsWith(u8, entry.basename, ".so")) {
+                try binaries.append(try fs.path.join(allocator, &[_][]const u8{ lib_path, entry.path }));
+            }
+        }
+    } else |_| {}
+
+    return binaries.items;
+}
+
+// Scans the install for shared libraries that can't be found on this machine, each library is listed once
+pub fn scan(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) ![]Missing {
+    var missing = std.ArrayList(Missing).init(allocator);
+    if (builtin.os.tag != .linux) {
+        return missing.items;
+    }
+
+    const system_search_paths = try get_system_search_paths(allocator);
+    var seen = std.StringHashMap(void).init(allocator);
+
+    for (try collect_binaries(allocator, install_dir, meta)) |binary_path| {
+        const dependencies = (read_dependencies(allocator, binary_path) catch null) orelse continue;
+
+        for (dependencies.needed) |library| {
+            if (seen.contains(library)) {
+                continue;
+            }
+
+            const found = is_available(allocator, library, dependencies.search_paths) or
+                is_available(allocator, library, system_search_paths);
+            if (found) {
+                continue;
+            }
+
+            try seen.put(library, {});
+            try missing.append(.{
+                .library = library,
+                .needed_by = binary_path[install_dir.len + 1 ..],
+                .packages = get_package_hint(library),
+            });
+        }
+    }
+
+    return missing.items;
+}
+
+// Scans the install and logs a report of the missing libraries, if there are any
+pub fn check_install(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) void {
+    const missing = scan(allocator, install_dir, meta) catch |err| {
+        log.debug("Failed to scan the install for shared library dependencies: {!}", .{err});
+        return;
+    };
+
+    if (missing.len == 0) {
+        return;
+    }
+
+    log.warn("This machine is missing shared libraries the application needs, it may fail to start:", .{});
+    for (missing) |lib| {
+        if (lib.packages) |packages| {
+            log.warn("  {s} (needed by {s}), usually provided by: {s}", .{ lib.library, lib.needed_by, packages });
+        } else {
+            log.warn("  {s} (needed by {s})", .{ lib.library, lib.needed_by });
+        }
+    }
+}
@@ -1,7 +1,101 @@
 const std = @import("std");
 const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const PR_SET_NAME = 15;
+const PR_SET_PDEATHSIG = 1;
+
+// FreeBSD `procctl(2)`
+const P_PID = 0;
+const PROC_PDEATHSIG_CTL = 11;
+
+extern "c" fn procctl(idtype: c_int, id: c_int, cmd: c_int, data: ?*anyopaque) c_int;
+extern "c" fn setproctitle(fmt: ?[*:0]const u8, ...) void;
+
+// Sets the name shown by `ps -o comm` and `top` for this process (Linux and FreeBSD only, truncated to 15 bytes by the Linux kernel)
+pub fn set_process_title(allocator: std.mem.Allocator, title: []const u8) void {
+    const title_z = allocator.dupeZ(u8, title) catch return;
+    switch (builtin.os.tag) {
+        .linux => _ = std.os.linux.prctl(PR_SET_NAME, @intFromPtr(title_z.ptr), 0, 0, 0),
+        // The leading dash drops the "<program>:" prefix
+        .freebsd => setproctitle("-%s", title_z.ptr),
+        else => {},
+    }
+}
+
+// Called in a forked child: asks the kernel to send it SIGTERM when the wrapper dies,
+// so the VM doesn't outlive a wrapper that was killed without a chance to forward the signal (Linux and FreeBSD only)
+pub fn exit_with_parent() void {
+    switch (builtin.os.tag) {
+        .linux => _ = std.os.linux.prctl(PR_SET_PDEATHSIG, std.os.SIG.TERM, 0, 0, 0),
+        .freebsd => {
+            var signal: c_int = std.os.SIG.TERM;
+            _ = procctl(P_PID, 0, PROC_PDEATHSIG_CTL, &signal);
+        },
+        else => {},
+    }
+}
+
+// The system's temporary directory, without a trailing separator
+pub fn get_temp_dir(allocator: std.mem.Allocator) []const u8 {
+    const names = if (builtin.os.tag == .windows) [_][]const u8{ "TEMP", "TMP" } else [_][]const u8{"TMPDIR"};
+    for (names) |name| {
+        if (std.process.getEnvVarOwned(allocator, name)) |value| {
+            if (value.len > 0 and std.fs.path.isAbsolute(value)) {
+                return std.mem.trimRight(u8, value, "/\\");
+            }
+        } else |_| {}
+    }
+    return if (builtin.os.tag == .windows) "C:\\Windows\\Temp" else "/tmp";
+}
 
 pub fn is_tty() bool {
     var stdout = std.io.getStdOut();
     return stdout.isTty();
 }
+
+// Color is off when `NO_COLOR` is set to anything (https://no-color.org), or when our output isn't a terminal,
+// e.g. captured by CI or piped into a file
+pub fn wants_color(allocator: std.mem.Allocator) bool {
+    if (std.process.getEnvVarOwned(allocator, "NO_COLOR")) |no_color| {
+        if (no_color.len > 0) return false;
+    } else |_| {}
+    return std.io.getStdOut().isTty() and std.io.getStdErr().isTty();
+}
+
+// `<RELEASE_NAME>_`, built at compile time since every launch reads a dozen of these variables
+const release_env_prefix = blk: {
+    var upper_name: [build_options.RELEASE_NAME.len]u8 = undefined;
+    _ = std.ascii.upperString(&upper_name, build_options.RELEASE_NAME);
+    break :blk upper_name ++ "_";
+};
+
+// Reads the `<RELEASE_NAME>_<SUFFIX>` environment variable, returns null if it's not set
+pub fn get_release_env(allocator: std.mem.Allocator, comptime suffix: []const u8) ?[]u8 {
+    return std.process.getEnvVarOwned(allocator, release_env_prefix ++ suffix) catch null;
+}
+
+// Returns true if a `<RELEASE_NAME>_<SUFFIX>` environment variable is set to a truthy value
+pub fn release_env_enabled(allocator: std.mem.Allocator, comptime suffix: []const u8) bool {
+    const value = get_release_env(allocator, suffix) orelse return false;
+    return is_truthy(value);
+}
+
+pub fn is_truthy(value: []const u8) bool {
+    return std.mem.eql(u8, value, "1") or std.ascii.eqlIgnoreCase(value, "true") or std.ascii.eqlIgnoreCase(value, "yes");
+}
+
+// Wraps an argument in single quotes so it can be safely passed through `sh -c`
+pub fn shell_quote(allocator: std.mem.Allocator, arg: []const u8) ![]const u8 {
+    var quoted = std.ArrayList(u8).init(allocator);
+    try quoted.append('\'');
+    for (arg) |char| {
+        if (char == '\'') {
+            try quoted.appendSlice("'\\''");
+        } else {
+            try quoted.append(char);
+        }
+    }
+    try quoted.append('\'');
+    return quoted.items;
+}
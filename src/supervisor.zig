@@ -0,0 +1,210 @@
+/////
+// Runs the Erlang VM as a child process while the wrapper stays alive as its parent (POSIX only).
+// By default the wrapper simply `execve()`s into the VM, supervising is only used when a feature needs a parent process:
+// a PTY, or forwarding stop signals with a shutdown grace period.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const os = std.os;
+const log = std.log;
+
+const pty = @import("pty.zig");
+const shutil = @import("shutil.zig");
+
+const EnvMap = std.process.EnvMap;
+
+// How often we check on the child while waiting
+const POLL_INTERVAL_MS = 100;
+
+pub const Options = struct {
+    // Allocate a PTY for the VM and proxy our stdio through it
+    use_pty: bool = false,
+    // How long to wait for the VM to exit after forwarding a stop signal before sending SIGKILL, 0 waits forever
+    shutdown_timeout_ms: u64 = 0,
+    // Shown by `ps`/`top` for the wrapper process
+    title: []const u8 = "burrito",
+    // Wiped once the VM is started, the child already has its own copy
+    secret: ?[]u8 = null,
+};
+
+var child_pid: os.pid_t = 0;
+var child_is_group_leader: bool = false;
+var stop_requested = std.atomic.Atomic(bool).init(false);
+var resize_requested = std.atomic.Atomic(bool).init(false);
+// A signal that arrived before the child was started, forwarded once it is
+var pending_signal = std.atomic.Atomic(c_int).init(0);
+
+fn forward_signal(sig: c_int) callconv(.C) void {
+    if (sig == os.SIG.TERM or sig == os.SIG.INT) {
+        stop_requested.store(true, .SeqCst);
+    }
+
+    if (child_pid == 0) {
+        pending_signal.store(sig, .SeqCst);
+        return;
+    }
+
+    os.kill(child_pid, @intCast(sig)) catch {};
+}
+
+fn note_resize(_: c_int) callconv(.C) void {
+    resize_requested.store(true, .SeqCst);
+}
+
+fn install_handlers(use_pty: bool) void {
+    const action = os.Sigaction{
+        .handler = .{ .handler = forward_signal },
+        .mask = os.empty_sigset,
+        .flags = os.SA.RESTART,
+    };
+
+    os.sigaction(os.SIG.TERM, &action, null) catch {};
+    os.sigaction(os.SIG.INT, &action, null) catch {};
+    // Passed along as-is so "reload on HUP" works, see `Burrito.Util.Signals`
+    os.sigaction(os.SIG.HUP, &action, null) catch {};
+
+    // Without a PTY the VM shares our terminal and gets SIGWINCH from the kernel directly,
+    // with one we need to copy the new size over to the PTY ourselves
+    if (use_pty) {
+        const resize_action = os.Sigaction{
+            .handler = .{ .handler = note_resize },
+            .mask = os.empty_sigset,
+            .flags = os.SA.RESTART,
+        };
+        os.sigaction(os.SIG.WINCH, &resize_action, null) catch {};
+    }
+}
+
+// Launches the VM and waits for it to exit, then exits the wrapper with the VM's exit code
+pub fn supervise(allocator: std.mem.Allocator, argv: []const []const u8, env_map: *const EnvMap, options: Options) !noreturn {
+    var session: ?pty.Session = null;
+
+    // We stick around in the process list, make it obvious what we are
+    shutil.set_process_title(allocator, options.title);
+
+    // Before the child exists, so no stop signal can slip through in between
+    install_handlers(options.use_pty);
+
+    if (options.use_pty) {
+        session = try pty.spawn(allocator, argv, env_map);
+        child_pid = session.?.pid;
+        // The PTY child calls setsid(), so it leads its own process group
+        child_is_group_leader = true;
+    } else {
+        // The VM gets its own process group, so a SIGKILL after the shutdown timeout also takes down what it spawned
+        // (erl_child_setup, port programs). It becomes the terminal's foreground group in our place, if we have it.
+        const take_terminal = is_terminal_foreground();
+        const pid = try os.fork();
+        if (pid == 0) {
+            shutil.exit_with_parent();
+            os.setpgid(0, 0) catch {};
+            if (take_terminal) {
+                set_terminal_foreground(0);
+            }
+            const err = std.process.execve(allocator, argv, env_map);
+            log.err("Failed to launch the Erlang VM: {!}", .{err});
+            os.exit(127);
+        }
+        // Also done here, so the group exists whichever of us runs first. Already exec'd means the child set it itself.
+        child_is_group_leader = if (os.setpgid(pid, pid)) true else |err| err == error.ProcessAlreadyExec;
+        if (take_terminal) {
+            set_terminal_foreground(pid);
+        }
+        child_pid = pid;
+    }
+
+    const pending = pending_signal.swap(0, .SeqCst);
+    if (pending != 0) {
+        os.kill(child_pid, @intCast(pending)) catch {};
+    }
+
+    if (options.secret) |secret| {
+        std.crypto.utils.secureZero(u8, secret);
+    }
+
+    var kill_deadline: ?i64 = null;
+    var pty_open = session != null;
+
+    while (true) {
+        if (pty_open) {
+            if (resize_requested.swap(false, .SeqCst)) {
+                pty.sync_window_size(&session.?);
+            }
+            pty_open = pty.pump(&session.?, POLL_INTERVAL_MS);
+        } else {
+            std.time.sleep(POLL_INTERVAL_MS * std.time.ns_per_ms);
+        }
+
+        const result = os.waitpid(child_pid, os.W.NOHANG);
+        if (result.pid == child_pid) {
+            if (session) |*s| {
+                pty.close(s);
+            }
+            std.process.exit(exit_code_from_status(result.status));
+        }
+
+        if (stop_requested.load(.SeqCst) and options.shutdown_timeout_ms > 0) {
+            const now = std.time.milliTimestamp();
+            if (kill_deadline == null) {
+                kill_deadline = now + @as(i64, @intCast(options.shutdown_timeout_ms));
+                log.debug("Stop signal forwarded, waiting up to {}ms for the VM to exit", .{options.shutdown_timeout_ms});
+            } else if (now >= kill_deadline.?) {
+                log.warn("The VM did not exit within {}ms, sending SIGKILL", .{options.shutdown_timeout_ms});
+                kill_child();
+                kill_deadline = std.math.maxInt(i64);
+            }
+        }
+    }
+}
+
+extern "c" fn getpgrp() os.pid_t;
+
+fn is_terminal_foreground() bool {
+    if (!os.isatty(os.STDIN_FILENO)) {
+        return false;
+    }
+    const foreground = os.tcgetpgrp(os.STDIN_FILENO) catch return false;
+    const own_group: os.pid_t = if (builtin.os.tag == .linux) @intCast(os.linux.syscall1(.getpgid, 0)) else getpgrp();
+    return foreground == own_group;
+}
+
+// Hands the terminal to the process group of `pid`, 0 for our own. SIGTTOU would stop a background group asking.
+fn set_terminal_foreground(pid: os.pid_t) void {
+    const ignore = os.Sigaction{ .handler = .{ .handler = os.SIG.IGN }, .mask = os.empty_sigset, .flags = 0 };
+    var previous: os.Sigaction = undefined;
+    os.sigaction(os.SIG.TTOU, &ignore, &previous) catch return;
+    defer os.sigaction(os.SIG.TTOU, &previous, null) catch {};
+
+    const group = if (pid == 0) own_pid() else pid;
+    os.tcsetpgrp(os.STDIN_FILENO, group) catch |err| {
+        log.debug("Could not hand the terminal to the VM: {!}", .{err});
+    };
+}
+
+fn own_pid() os.pid_t {
+    return if (builtin.os.tag == .linux) os.linux.getpid() else std.c.getpid();
+}
+
+fn kill_child() void {
+    if (child_is_group_leader) {
+        // Take down anything the VM spawned in its process group as well
+        os.kill(-child_pid, os.SIG.KILL) catch {};
+    } else {
+        os.kill(child_pid, os.SIG.KILL) catch {};
+    }
+}
+
+fn exit_code_from_status(status: u32) u8 {
+    if (os.W.IFEXITED(status)) {
+        return os.W.EXITSTATUS(status);
+    }
+
+    if (os.W.IFSIGNALED(status)) {
+        // Same convention as shells, 128 + signal number
+        return @intCast(128 + os.W.TERMSIG(status));
+    }
+
+    return 1;
+}
@@ -0,0 +1,123 @@
+/////
+// Collects what we need to debug a failed install or launch into a single zip file users can attach to a bug report:
+// the wrapper's log for this run, a summary of the failure, the release metadata, the doctor output and the list of
+// files that made it into the install directory.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const doctor = @import("doctor.zig");
+const i18n = @import("i18n.zig");
+const logger = @import("logger.zig");
+const tamper = @import("tamper.zig");
+const wrapper = @import("wrapper.zig");
+const zip = @import("zip.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const log = std.log;
+
+// The install listing stops here, a partial install is what we're usually after
+const MAX_LISTED_FILES = 5000;
+
+// Writes the bundle into `dest_dir`, returns its path
+pub fn write(allocator: std.mem.Allocator, dest_dir: []const u8, install_dir: []const u8, meta: *const MetaStruct, failure: ?anyerror) ![]const u8 {
+    var archive = zip.Writer.init(allocator);
+
+    try archive.add_file("summary.txt", try get_summary(allocator, install_dir, meta, failure));
+    try archive.add_file("wrapper.log", logger.get_history());
+    try archive.add_file("metadata.json", wrapper.RELEASE_METADATA_JSON);
+
+    var doctor_output = std.ArrayList(u8).init(allocator);
+    _ = doctor.report(allocator, install_dir, meta, doctor_output.writer()) catch |err| {
+        try doctor_output.writer().print("\nThe checks could not complete: {!}\n", .{err});
+    };
+    try archive.add_file("doctor.txt", doctor_output.items);
+
+    try archive.add_file("install_files.txt", try list_install(allocator, install_dir));
+
+    const manifest_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, tamper.MANIFEST_NAME });
+    if (fs.cwd().readFileAlloc(allocator, manifest_path, 1024 * 1024)) |manifest| {
+        try archive.add_file("manifest.txt", manifest);
+    } else |_| {}
+
+    const name = try std.fmt.allocPrint(allocator, "{s}-support-{}.zip", .{ meta.app_name, std.time.timestamp() });
+    const path = try fs.path.join(allocator, &[_][]const u8{ dest_dir, name });
+    const file = try fs.createFileAbsolute(path, .{ .exclusive = true });
+    defer file.close();
+    try file.writeAll(try archive.finish());
+
+    return path;
+}
+
+// Asks an interactive user whether to write a bundle after a failure, otherwise says how to get one
+pub fn offer(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct, failure: anyerror) void {
+    if (!std.io.getStdIn().isTty()) {
+        logger.info("{s}", .{i18n.text(.support_bundle_hint)});
+        return;
+    }
+
+    logger.query("Write a support bundle with the details of this failure for a bug report? [y/n]: ", .{});
+    var buf: [8]u8 = undefined;
+    const answer = std.io.getStdIn().reader().readUntilDelimiterOrEof(&buf, '\n') catch return orelse return;
+    if (answer.len == 0 or (answer[0] != 'y' and answer[0] != 'Y')) {
+        return;
+    }
+
+    const cwd_path = fs.cwd().realpathAlloc(allocator, ".") catch return;
+    const path = write(allocator, cwd_path, install_dir, meta, failure) catch |err| {
+        logger.err("Could not write the support bundle: {!}", .{err});
+        return;
+    };
+    logger.info("{s}", .{i18n.format(.support_bundle_written, &.{path})});
+}
+
+fn get_summary(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct, failure: ?anyerror) ![]const u8 {
+    var summary = std.ArrayList(u8).init(allocator);
+    const writer = summary.writer();
+
+    try writer.print("app: {s} {s}\n", .{ meta.app_name, meta.app_version });
+    try writer.print("erts: {s}\n", .{meta.erts_version});
+    try writer.print("target: {s}\n", .{meta.target orelse "unknown"});
+    try writer.print("running on: {s} {s}\n", .{ @tagName(builtin.os.tag), @tagName(builtin.cpu.arch) });
+    try writer.print("install dir: {s}\n", .{install_dir});
+    try writer.print("created at: {}\n", .{std.time.timestamp()});
+    if (failure) |err| {
+        try writer.print("error: {!}\n", .{err});
+    }
+
+    return summary.items;
+}
+
+// Every file in the install directory with its size
+fn list_install(allocator: std.mem.Allocator, install_dir: []const u8) ![]const u8 {
+    var listing = std.ArrayList(u8).init(allocator);
+    const writer = listing.writer();
+
+    var dir = fs.openIterableDirAbsolute(install_dir, .{}) catch |err| {
+        try writer.print("could not open {s}: {!}\n", .{ install_dir, err });
+        return listing.items;
+    };
+    defer dir.close();
+
+    var walker = try dir.walk(allocator);
+    defer walker.deinit();
+
+    var count: usize = 0;
+    while (walker.next() catch null) |entry| {
+        if (entry.kind != .file) {
+            continue;
+        }
+        if (count == MAX_LISTED_FILES) {
+            try writer.print("... (stopped after {} files)\n", .{MAX_LISTED_FILES});
+            break;
+        }
+
+        const size = if (entry.dir.statFile(entry.basename)) |stat| stat.size else |_| 0;
+        try writer.print("{s} {}\n", .{ entry.path, size });
+        count += 1;
+    }
+
+    return listing.items;
+}
@@ -0,0 +1,72 @@
+/////
+// Minimal RFC 5424 syslog client, sends wrapper log messages to the local syslog socket.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const os = std.os;
+
+// The local syslog socket paths we try, in order
+const socket_paths = [_][]const u8{ "/dev/log", "/var/run/syslog", "/var/run/log" };
+
+// We log everything under the "user" facility
+const FACILITY_USER = 1;
+
+pub const Severity = enum(u8) {
+    critical = 2,
+    err = 3,
+    warning = 4,
+    info = 6,
+    debug = 7,
+};
+
+var socket: ?os.socket_t = null;
+
+fn connect() ?os.socket_t {
+    if (socket) |fd| {
+        return fd;
+    }
+
+    for (socket_paths) |path| {
+        const address = std.net.Address.initUnix(path) catch continue;
+        const fd = os.socket(os.AF.UNIX, os.SOCK.DGRAM | os.SOCK.CLOEXEC, 0) catch continue;
+        os.connect(fd, &address.any, address.getOsSockLen()) catch {
+            os.close(fd);
+            continue;
+        };
+
+        socket = fd;
+        return fd;
+    }
+
+    return null;
+}
+
+// Sends a single message to syslog, silently dropping it if syslog is unavailable
+pub fn send(severity: Severity, app_name: []const u8, message: []const u8) void {
+    if (builtin.os.tag == .windows) {
+        return;
+    } else {
+        send_posix(severity, app_name, message);
+    }
+}
+
+fn send_posix(severity: Severity, app_name: []const u8, message: []const u8) void {
+    const fd = connect() orelse return;
+
+    // <PRI>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+    // We leave the timestamp and hostname as NILVALUE, the syslog daemon fills those in for us
+    var buffer: [2048]u8 = undefined;
+    const pri = FACILITY_USER * 8 + @intFromEnum(severity);
+    const line = std.fmt.bufPrint(&buffer, "<{}>1 - - {s} {} - - {s}", .{ pri, app_name, get_pid(), message }) catch buffer[0..];
+
+    _ = os.send(fd, line, 0) catch {};
+}
+
+fn get_pid() i64 {
+    return switch (builtin.os.tag) {
+        .linux => std.os.linux.getpid(),
+        else => std.c.getpid(),
+    };
+}
@@ -0,0 +1,171 @@
+/////
+// A quick launch-time check that the files the VM boots from haven't changed since they were installed.
+// On install, the hashes of a small set of critical files (erlexec, the boot script and the app's own beams) are
+// written to a manifest in the install directory, along with their sizes and modification times. Every later launch
+// compares against it, and only hashes the files whose size or modification time changed, to keep launches fast.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const logger = @import("logger.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const log = std.log;
+const Sha256 = std.crypto.hash.sha2.Sha256;
+
+pub const MANIFEST_NAME = "_manifest";
+
+const erts_files = if (builtin.os.tag == .windows)
+    [_][]const u8{"erl.exe"}
+else
+    [_][]const u8{"erlexec"};
+
+pub const Policy = enum {
+    off,
+    warn,
+    refuse,
+};
+
+pub fn get_policy(meta: *const MetaStruct) Policy {
+    return std.meta.stringToEnum(Policy, meta.tamper_check) orelse .warn;
+}
+
+// Relative paths of the files covered by the manifest
+fn critical_files(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) ![]const []const u8 {
+    var files = std.ArrayList([]const u8).init(allocator);
+
+    const erts_bin = try std.fmt.allocPrint(allocator, "erts-{s}/bin", .{meta.erts_version});
+    for (erts_files) |name| {
+        try files.append(try fs.path.join(allocator, &[_][]const u8{ erts_bin, name }));
+    }
+    try files.append(try fs.path.join(allocator, &[_][]const u8{ "releases", meta.app_version, "start.boot" }));
+
+    const ebin = try std.fmt.allocPrint(allocator, "lib/{s}-{s}/ebin", .{ meta.app_name, meta.app_version });
+    const ebin_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, ebin });
+    var ebin_dir = fs.openIterableDirAbsolute(ebin_path, .{}) catch |err| {
+        log.debug("Could not list the app beams in {s}: {!}", .{ ebin_path, err });
+        return files.items;
+    };
+    defer ebin_dir.close();
+
+    var iterator = ebin_dir.iterate();
+    while (try iterator.next()) |entry| {
+        if (entry.kind == .file and std.mem.endsWith(u8, entry.name, ".beam")) {
+            try files.append(try fs.path.join(allocator, &[_][]const u8{ ebin, entry.name }));
+        }
+    }
+
+    return files.items;
+}
+
+// The size and modification time a file was installed with, a file that still has both wasn't touched since
+const FileStamp = struct {
+    size: u64,
+    mtime: i128,
+};
+
+fn stamp_file(allocator: std.mem.Allocator, install_dir: []const u8, relative_path: []const u8) !FileStamp {
+    const path = try fs.path.join(allocator, &[_][]const u8{ install_dir, relative_path });
+    const stat = try fs.cwd().statFile(path);
+    return FileStamp{ .size = stat.size, .mtime = stat.mtime };
+}
+
+fn hash_file(allocator: std.mem.Allocator, install_dir: []const u8, relative_path: []const u8) ![Sha256.digest_length * 2]u8 {
+    const path = try fs.path.join(allocator, &[_][]const u8{ install_dir, relative_path });
+    const file = try fs.openFileAbsolute(path, .{});
+    defer file.close();
+
+    var hasher = Sha256.init(.{});
+    var buffer: [64 * 1024]u8 = undefined;
+    while (true) {
+        const read = try file.read(&buffer);
+        if (read == 0) {
+            break;
+        }
+        hasher.update(buffer[0..read]);
+    }
+
+    var digest: [Sha256.digest_length]u8 = undefined;
+    hasher.final(&digest);
+    var hex: [Sha256.digest_length * 2]u8 = undefined;
+    _ = std.fmt.bufPrint(&hex, "{s}", .{std.fmt.fmtSliceHexLower(&digest)}) catch unreachable;
+    return hex;
+}
+
+// Writes the manifest right after the payload is extracted, one `<sha256> <size> <mtime>  <path>` line per file
+pub fn write_manifest(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) !void {
+    if (get_policy(meta) == .off) {
+        return;
+    }
+
+    var contents = std.ArrayList(u8).init(allocator);
+    for (try critical_files(allocator, install_dir, meta)) |relative_path| {
+        const hex = hash_file(allocator, install_dir, relative_path) catch |err| switch (err) {
+            // Not every ERTS build ships every executable
+            error.FileNotFound => continue,
+            else => return err,
+        };
+        const stamp = try stamp_file(allocator, install_dir, relative_path);
+        try contents.writer().print("{s} {} {}  {s}\n", .{ hex, stamp.size, stamp.mtime, relative_path });
+    }
+
+    const manifest_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, MANIFEST_NAME });
+    const file = try fs.createFileAbsolute(manifest_path, .{ .truncate = true });
+    defer file.close();
+    try file.writeAll(contents.items);
+}
+
+// Compares the installed files against the manifest, returns error.Tampered if the policy says not to launch
+pub fn check(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) !void {
+    const policy = get_policy(meta);
+    if (policy == .off) {
+        return;
+    }
+
+    const manifest_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, MANIFEST_NAME });
+    const manifest = fs.cwd().readFileAlloc(allocator, manifest_path, 1024 * 1024) catch |err| {
+        // Installs from before the manifest existed have nothing to check against
+        log.debug("Skipping the tamper check, could not read {s}: {!}", .{ manifest_path, err });
+        return;
+    };
+
+    var changed: usize = 0;
+    var lines = std.mem.tokenize(u8, manifest, "\n");
+    while (lines.next()) |line| {
+        const separator = std.mem.indexOf(u8, line, "  ") orelse continue;
+        var fields = std.mem.tokenizeScalar(u8, line[0..separator], ' ');
+        const expected = fields.next() orelse continue;
+        const relative_path = line[separator + 2 ..];
+
+        // Manifests written before the stamps were added only have the hash
+        const size = std.fmt.parseInt(u64, fields.next() orelse "", 10) catch null;
+        const mtime = std.fmt.parseInt(i128, fields.next() orelse "", 10) catch null;
+        if (size != null and mtime != null) {
+            if (stamp_file(allocator, install_dir, relative_path)) |stamp| {
+                if (stamp.size == size.? and stamp.mtime == mtime.?) continue;
+            } else |_| {}
+        }
+
+        const matches = if (hash_file(allocator, install_dir, relative_path)) |hex|
+            std.mem.eql(u8, &hex, expected)
+        else |_|
+            false;
+
+        if (!matches) {
+            logger.warn("{s} was modified after it was installed", .{relative_path});
+            changed += 1;
+        }
+    }
+
+    if (changed == 0) {
+        return;
+    }
+
+    if (policy == .refuse) {
+        logger.err("Refusing to launch, {} installed file(s) don't match what was installed", .{changed});
+        logger.err("Run `--burrito repair` to restore them.", .{});
+        return error.Tampered;
+    }
+}
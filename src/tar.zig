@@ -0,0 +1,94 @@
+/////
+// Writes POSIX ustar archives, the std library can only read them.
+/////
+
+const std = @import("std");
+
+const BLOCK_SIZE = 512;
+const NAME_SIZE = 100;
+const PREFIX_SIZE = 155;
+
+pub fn Writer(comptime WriterType: type) type {
+    return struct {
+        const Self = @This();
+
+        underlying: WriterType,
+        // Applied to every entry, keeps exports reproducible
+        mtime: u64 = 0,
+
+        // Writes a regular file entry
+        pub fn add_file(self: *Self, path: []const u8, mode: u32, data: []const u8) !void {
+            var header = [_]u8{0} ** BLOCK_SIZE;
+            try set_path(&header, path);
+
+            write_octal(header[100..108], mode & 0o7777);
+            write_octal(header[108..116], 0);
+            write_octal(header[116..124], 0);
+            write_octal(header[124..136], data.len);
+            write_octal(header[136..148], self.mtime);
+            header[156] = '0';
+            @memcpy(header[257..263], "ustar\x00");
+            @memcpy(header[263..265], "00");
+
+            // The checksum is computed with the checksum field itself set to spaces
+            @memset(header[148..156], ' ');
+            var checksum: u64 = 0;
+            for (header) |byte| {
+                checksum += byte;
+            }
+            write_octal(header[148..155], checksum);
+
+            try self.underlying.writeAll(&header);
+            try self.underlying.writeAll(data);
+            try self.pad(data.len);
+        }
+
+        // Two empty blocks mark the end of the archive
+        pub fn finish(self: *Self) !void {
+            try self.underlying.writeByteNTimes(0, BLOCK_SIZE * 2);
+        }
+
+        fn pad(self: *Self, len: usize) !void {
+            const remainder = len % BLOCK_SIZE;
+            if (remainder != 0) {
+                try self.underlying.writeByteNTimes(0, BLOCK_SIZE - remainder);
+            }
+        }
+    };
+}
+
+pub fn writer(underlying: anytype) Writer(@TypeOf(underlying)) {
+    return .{ .underlying = underlying };
+}
+
+// Long paths are split over the prefix and name fields at a directory separator
+fn set_path(header: *[BLOCK_SIZE]u8, path: []const u8) !void {
+    if (path.len <= NAME_SIZE) {
+        @memcpy(header[0..path.len], path);
+        return;
+    }
+
+    // The last separator that still leaves the prefix short enough
+    const split = std.mem.lastIndexOfScalar(u8, path[0..@min(path.len, PREFIX_SIZE + 1)], '/') orelse return error.NameTooLong;
+
+    const prefix = path[0..split];
+    const name = path[split + 1 ..];
+    if (name.len > NAME_SIZE) {
+        return error.NameTooLong;
+    }
+
+    @memcpy(header[0..name.len], name);
+    @memcpy(header[345 .. 345 + prefix.len], prefix);
+}
+
+// Numeric fields are zero padded octal, terminated by a NUL
+fn write_octal(field: []u8, value: u64) void {
+    var remaining = value;
+    var index = field.len - 1;
+    field[index] = 0;
+    while (index > 0) {
+        index -= 1;
+        field[index] = '0' + @as(u8, @intCast(remaining & 7));
+        remaining >>= 3;
+    }
+}
@@ -0,0 +1,90 @@
+/////
+// Opt-in install telemetry: when the release sets a `telemetry_url`, the wrapper POSTs a small JSON report after
+// installing the payload (app version, OS, CPU architecture, success or the error name, install duration), so
+// vendors can follow the rollout health of the binaries they distribute.
+// Nothing is sent if `<APP>_NO_TELEMETRY` or `DO_NOT_TRACK` is set, and only ever over HTTPS.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const features = @import("features.zig");
+const shutil = @import("shutil.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const log = std.log;
+
+const Report = struct {
+    event: []const u8 = "install",
+    app_name: []const u8,
+    app_version: []const u8,
+    os: []const u8 = @tagName(builtin.os.tag),
+    arch: []const u8 = @tagName(builtin.cpu.arch),
+    success: bool,
+    @"error": ?[]const u8,
+    duration_ms: i64,
+};
+
+pub fn is_opted_out(allocator: std.mem.Allocator) bool {
+    if (shutil.release_env_enabled(allocator, "NO_TELEMETRY")) {
+        return true;
+    }
+    const do_not_track = std.process.getEnvVarOwned(allocator, "DO_NOT_TRACK") catch return false;
+    return shutil.is_truthy(do_not_track);
+}
+
+// Sends the install report, failures are only logged in debug output, they must never get in the way of the app
+pub fn report_install(allocator: std.mem.Allocator, meta: *const MetaStruct, failure: ?anyerror, duration_ms: i64) void {
+    if (!features.enabled(.telemetry)) return;
+
+    const url = meta.telemetry_url orelse return;
+    if (is_opted_out(allocator)) {
+        log.debug("Telemetry is disabled through the environment", .{});
+        return;
+    }
+
+    const report = Report{
+        .app_name = meta.app_name,
+        .app_version = meta.app_version,
+        .success = failure == null,
+        .@"error" = if (failure) |err| @errorName(err) else null,
+        .duration_ms = duration_ms,
+    };
+
+    send(allocator, url, report) catch |err| {
+        log.debug("Could not send the install report to {s}: {!}", .{ url, err });
+    };
+}
+
+fn send(allocator: std.mem.Allocator, url: []const u8, report: Report) !void {
+    const uri = try std.Uri.parse(url);
+    if (!std.mem.eql(u8, uri.scheme, "https")) {
+        return error.InsecureTelemetryUrl;
+    }
+
+    var body = std.ArrayList(u8).init(allocator);
+    try std.json.stringify(report, .{}, body.writer());
+
+    var client = std.http.Client{ .allocator = allocator };
+    defer client.deinit();
+
+    var headers = std.http.Headers{ .allocator = allocator };
+    defer headers.deinit();
+    try headers.append("user-agent", "burrito-telemetry");
+    try headers.append("content-type", "application/json");
+
+    var request = try client.request(.POST, uri, headers, .{});
+    defer request.deinit();
+    request.transfer_encoding = .{ .content_length = body.items.len };
+
+    try request.start();
+    try request.writeAll(body.items);
+    try request.finish();
+    try request.wait();
+
+    if (request.response.status.class() != .success) {
+        return error.TelemetryRejected;
+    }
+
+    log.debug("Sent the install report to {s}", .{url});
+}
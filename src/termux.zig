@@ -0,0 +1,69 @@
+/////
+// Termux (Android) support. Termux lives in its own prefix (`/data/data/com.termux/files/usr`) instead of the usual
+// FHS layout, has no `/tmp`, and Android mounts shared storage (`/sdcard`, `/storage`) with `noexec`.
+// We install under the Termux prefix, and point the VM at the Termux tools and temp directory.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const fs = std.fs;
+const log = std.log;
+
+const EnvMap = std.process.EnvMap;
+
+const DEFAULT_PREFIX = "/data/data/com.termux/files/usr";
+
+// Mount points of Android shared storage, files there can never be executed
+const shared_storage_roots = [_][]const u8{ "/sdcard", "/storage", "/mnt/sdcard" };
+
+// Returns the Termux prefix if we are running inside of Termux, null otherwise
+pub fn get_prefix() ?[]const u8 {
+    if (builtin.os.tag != .linux) {
+        return null;
+    }
+
+    // Termux sets both, but `TERMUX_VERSION` is missing in some older versions
+    const prefix = std.os.getenv("PREFIX");
+    if (std.os.getenv("TERMUX_VERSION") != null) {
+        return prefix orelse DEFAULT_PREFIX;
+    }
+    if (prefix) |value| {
+        if (std.mem.indexOf(u8, value, "com.termux") != null) {
+            return value;
+        }
+    }
+    return null;
+}
+
+// Installs go into `$PREFIX/var/lib`, the Termux equivalent of `/var/lib`
+pub fn get_base_dir(allocator: std.mem.Allocator, prefix: []const u8) ![]const u8 {
+    return try fs.path.join(allocator, &[_][]const u8{ prefix, "var", "lib" });
+}
+
+pub fn is_shared_storage(path: []const u8) bool {
+    for (shared_storage_roots) |root| {
+        if (std.mem.startsWith(u8, path, root)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Makes sure the VM finds the Termux shell and tools, and has a temp directory it can write to
+pub fn apply_env(allocator: std.mem.Allocator, env_map: *EnvMap, prefix: []const u8) !void {
+    const bin_path = try fs.path.join(allocator, &[_][]const u8{ prefix, "bin" });
+
+    const path = env_map.get("PATH") orelse "";
+    if (std.mem.indexOf(u8, path, bin_path) == null) {
+        try env_map.put("PATH", try std.fmt.allocPrint(allocator, "{s}:{s}", .{ bin_path, path }));
+    }
+
+    if (env_map.get("SHELL") == null) {
+        try env_map.put("SHELL", try fs.path.join(allocator, &[_][]const u8{ bin_path, "sh" }));
+    }
+
+    if (env_map.get("TMPDIR") == null) {
+        try env_map.put("TMPDIR", try fs.path.join(allocator, &[_][]const u8{ prefix, "tmp" }));
+    }
+}
@@ -0,0 +1,144 @@
+/////
+// Timing spans for the phases of a wrapper run (metadata parsing, decompression, extraction, launch), to find out
+// where the time goes on a slow first start.
+// Every span is logged as a debug message (shown with `--burrito-verbose`) and as a `span` event for the JSON log
+// target. If `<APP>_TRACE_FILE` is set, spans are also written there in the Chrome trace event format, which can
+// be opened in `chrome://tracing` or https://ui.perfetto.dev.
+//
+// Work that is spread over many small operations (writing thousands of files) is summed up per `Category` instead,
+// for the breakdown logged before handing over to the VM in verbose mode, and printed by `--burrito timings`.
+/////
+
+const std = @import("std");
+
+const logger = @import("logger.zig");
+const shutil = @import("shutil.zig");
+
+const log = std.log;
+
+var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+var allocator = arena.allocator();
+
+var started_us: i64 = 0;
+
+var trace_file: ?std.fs.File = null;
+var trace_file_checked = false;
+
+pub const Span = struct {
+    name: []const u8,
+    started_us: i64,
+
+    pub fn end(self: Span) void {
+        const duration_us = std.time.microTimestamp() - self.started_us;
+        const duration_ms = @as(f64, @floatFromInt(duration_us)) / std.time.us_per_ms;
+
+        log.debug("{s} took {d:.1}ms", .{ self.name, duration_ms });
+        logger.event("span", .{ .name = self.name, .duration_ms = duration_ms });
+
+        if (get_trace_file()) |file| {
+            const trace_event = .{
+                .name = self.name,
+                .ph = "X",
+                .ts = self.started_us,
+                .dur = duration_us,
+                .pid = 1,
+                .tid = 1,
+            };
+            const writer = file.writer();
+            std.json.stringify(trace_event, .{}, writer) catch return;
+            writer.writeAll(",\n") catch {};
+        }
+    }
+};
+
+pub fn begin(name: []const u8) Span {
+    return .{ .name = name, .started_us = std.time.microTimestamp() };
+}
+
+pub const Category = enum {
+    decompress,
+    // Writing the contents of the files
+    write_files,
+    // Creating files and directories with their modes, clearing quarantine flags and relabeling for SELinux
+    set_permissions,
+    // Everything between deciding to launch and handing over to the VM
+    spawn_vm,
+
+    fn label(self: Category) []const u8 {
+        return switch (self) {
+            .decompress => "decompression",
+            .write_files => "disk writes",
+            .set_permissions => "file creation and permissions",
+            .spawn_vm => "VM spawn",
+        };
+    }
+};
+
+var totals_us = [_]i64{0} ** @typeInfo(Category).Enum.fields.len;
+
+pub const Timer = struct {
+    category: Category,
+    started_us: i64,
+
+    // Timers run on several extraction threads at once, their totals add up to more than the wall clock time
+    pub fn stop(self: Timer) void {
+        _ = @atomicRmw(i64, &totals_us[@intFromEnum(self.category)], .Add, std.time.microTimestamp() - self.started_us, .Monotonic);
+    }
+};
+
+pub fn start(category: Category) Timer {
+    return .{ .category = category, .started_us = std.time.microTimestamp() };
+}
+
+// Called first thing in the wrapper, so the summary has a total to compare the categories with
+pub fn mark_start() void {
+    started_us = std.time.microTimestamp();
+}
+
+// Time since `mark_start()`, what `--burrito bench-startup` reports for each run
+pub fn elapsed_us() i64 {
+    return std.time.microTimestamp() - started_us;
+}
+
+pub fn print_summary(writer: anytype) !void {
+    try writer.print("Startup timings:\n", .{});
+    inline for (@typeInfo(Category).Enum.fields) |field| {
+        const category: Category = @enumFromInt(field.value);
+        try writer.print("  {s: <30} {d:>10.1}ms\n", .{ category.label(), to_ms(totals_us[field.value]) });
+    }
+    if (started_us != 0) {
+        try writer.print("  {s: <30} {d:>10.1}ms\n", .{ "total", to_ms(std.time.microTimestamp() - started_us) });
+    }
+}
+
+// Logs the summary as debug messages, i.e. only in verbose mode
+pub fn log_summary() void {
+    var summary = std.ArrayList(u8).init(allocator);
+    print_summary(summary.writer()) catch return;
+    var lines = std.mem.tokenizeScalar(u8, summary.items, '\n');
+    while (lines.next()) |line| {
+        log.debug("{s}", .{line});
+    }
+}
+
+fn to_ms(duration_us: i64) f64 {
+    return @as(f64, @floatFromInt(duration_us)) / std.time.us_per_ms;
+}
+
+// The file is only created once the first span ends. The trace format allows leaving out the closing `]`,
+// which is what lets us keep appending until we exec into the VM.
+fn get_trace_file() ?std.fs.File {
+    if (trace_file_checked) {
+        return trace_file;
+    }
+    trace_file_checked = true;
+
+    const path = shutil.get_release_env(allocator, "TRACE_FILE") orelse return null;
+    const file = std.fs.cwd().createFile(path, .{ .truncate = true }) catch |err| {
+        logger.warn("Could not create the trace file {s}: {!}", .{ path, err });
+        return null;
+    };
+    file.writeAll("[\n") catch {};
+    trace_file = file;
+    return trace_file;
+}
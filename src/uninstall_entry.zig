@@ -0,0 +1,83 @@
+/////
+// Registers the app under "Apps & features" / "Add or Remove Programs" on Windows, with an uninstall command that runs
+// `--burrito uninstall`, so it shows up in the standard software inventory.
+// The entry is written per user (HKCU), which doesn't need admin rights.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const log = std.log;
+const windows = std.os.windows;
+const HKEY = windows.HKEY;
+const LPCWSTR = windows.LPCWSTR;
+const DWORD = windows.DWORD;
+
+extern "advapi32" fn RegCreateKeyExW(hKey: HKEY, lpSubKey: LPCWSTR, Reserved: DWORD, lpClass: ?LPCWSTR, dwOptions: DWORD, samDesired: DWORD, lpSecurityAttributes: ?*anyopaque, phkResult: *HKEY, lpdwDisposition: ?*DWORD) callconv(windows.WINAPI) windows.LSTATUS;
+extern "advapi32" fn RegSetValueExW(hKey: HKEY, lpValueName: LPCWSTR, Reserved: DWORD, dwType: DWORD, lpData: [*]const u8, cbData: DWORD) callconv(windows.WINAPI) windows.LSTATUS;
+extern "advapi32" fn RegDeleteTreeW(hKey: HKEY, lpSubKey: ?LPCWSTR) callconv(windows.WINAPI) windows.LSTATUS;
+extern "advapi32" fn RegCloseKey(hKey: HKEY) callconv(windows.WINAPI) windows.LSTATUS;
+
+const UNINSTALL_KEY_PREFIX = "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\";
+
+const HKEY_CURRENT_USER: HKEY = @ptrFromInt(0x80000001);
+const KEY_SET_VALUE: DWORD = 0x0002;
+const REG_SZ: DWORD = 1;
+const REG_DWORD: DWORD = 4;
+
+const key_path = UNINSTALL_KEY_PREFIX ++ "burrito_" ++ build_options.RELEASE_NAME;
+
+// Writes (or refreshes) the uninstall entry, does nothing on other platforms
+pub fn register(allocator: std.mem.Allocator, install_dir: []const u8, meta: *const MetaStruct) void {
+    if (builtin.os.tag != .windows) {
+        return;
+    } else {
+        const exe_path = fs.selfExePathAlloc(allocator) catch return;
+        const uninstall_command = std.fmt.allocPrint(allocator, "\"{s}\" --burrito uninstall", .{exe_path}) catch return;
+
+        const key_path_w = std.unicode.utf8ToUtf16LeWithNull(allocator, key_path) catch return;
+        var key: HKEY = undefined;
+        if (RegCreateKeyExW(HKEY_CURRENT_USER, key_path_w, 0, null, 0, KEY_SET_VALUE, null, &key, null) != 0) {
+            log.warn("Failed to register {s} in the list of installed programs", .{meta.app_name});
+            return;
+        }
+        defer _ = RegCloseKey(key);
+
+        set_string(allocator, key, "DisplayName", meta.windows_display_name orelse meta.app_name);
+        set_string(allocator, key, "DisplayVersion", meta.app_version);
+        set_string(allocator, key, "DisplayIcon", exe_path);
+        set_string(allocator, key, "InstallLocation", install_dir);
+        set_string(allocator, key, "UninstallString", uninstall_command);
+        if (meta.windows_publisher) |publisher| {
+            set_string(allocator, key, "Publisher", publisher);
+        }
+        set_dword(allocator, key, "NoModify", 1);
+        set_dword(allocator, key, "NoRepair", 1);
+    }
+}
+
+// Removes the uninstall entry, if there is one
+pub fn unregister(allocator: std.mem.Allocator) void {
+    if (builtin.os.tag != .windows) {
+        return;
+    } else {
+        const key_path_w = std.unicode.utf8ToUtf16LeWithNull(allocator, key_path) catch return;
+        _ = RegDeleteTreeW(HKEY_CURRENT_USER, key_path_w);
+    }
+}
+
+fn set_string(allocator: std.mem.Allocator, key: HKEY, name: []const u8, value: []const u8) void {
+    const name_w = std.unicode.utf8ToUtf16LeWithNull(allocator, name) catch return;
+    const value_w = std.unicode.utf8ToUtf16LeWithNull(allocator, value) catch return;
+    const value_bytes = std.mem.sliceAsBytes(value_w[0 .. value_w.len + 1]);
+    _ = RegSetValueExW(key, name_w, 0, REG_SZ, value_bytes.ptr, @intCast(value_bytes.len));
+}
+
+fn set_dword(allocator: std.mem.Allocator, key: HKEY, name: []const u8, value: DWORD) void {
+    const name_w = std.unicode.utf8ToUtf16LeWithNull(allocator, name) catch return;
+    _ = RegSetValueExW(key, name_w, 0, REG_DWORD, std.mem.asBytes(&value), @sizeOf(DWORD));
+}
@@ -0,0 +1,272 @@
+/////
+// Self-update support: downloads a new wrapper binary over HTTP(S), verifies its Ed25519 signature against the
+// public key baked in at build time, and swaps it in place of the running binary.
+// The signature covers the binary's version and channel along with its hash (see `signed_message()`), so an older
+// signed binary can't be passed off as the latest one, and only newer versions are installed unless asked otherwise.
+// The binary being replaced is kept next to the new one (`<binary>.old`) so it can be rolled back to.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+const build_options = @import("build_options");
+
+const delta = @import("delta.zig");
+const logger = @import("logger.zig");
+const shutil = @import("shutil.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const Ed25519 = std.crypto.sign.Ed25519;
+
+// Wrapper binaries include the whole release, so this is generous
+const MAX_DOWNLOAD_SIZE = 1024 * 1024 * 1024;
+const MAX_SIGNATURE_SIZE = 1024;
+const MAX_CHANNEL_SIZE = 256;
+const MAX_VERSION_SIZE = 256;
+
+pub const Config = struct {
+    // Where the new binary is downloaded from, its signature is expected at `<url>.sig`
+    url: []const u8,
+    channel: []const u8,
+    public_key: Ed25519.PublicKey,
+};
+
+// Builds the update config from the metadata and `<APP>_UPDATE_URL`, null if updates aren't configured.
+// `channel` is the channel picked on the command line, if any.
+pub fn get_config(allocator: std.mem.Allocator, meta: *const MetaStruct, channel: ?[]const u8) !?Config {
+    const url_template = shutil.get_release_env(allocator, "UPDATE_URL") orelse meta.update_url orelse return null;
+
+    // Most specific wins: command line, environment, the last channel picked, then the build default
+    const selected_channel = channel orelse
+        shutil.get_release_env(allocator, "UPDATE_CHANNEL") orelse
+        read_channel(allocator) orelse
+        meta.update_channel;
+
+    const url = try std.mem.replaceOwned(u8, allocator, url_template, "{channel}", selected_channel);
+
+    // Never accept an update we can't verify
+    const key_hex = meta.update_public_key orelse {
+        logger.err("An update URL is configured, but this binary was built without an update public key", .{});
+        return error.MissingPublicKey;
+    };
+
+    var key_bytes: [Ed25519.PublicKey.encoded_length]u8 = undefined;
+    _ = std.fmt.hexToBytes(&key_bytes, key_hex) catch return error.InvalidPublicKey;
+
+    return Config{
+        .url = url,
+        .channel = selected_channel,
+        .public_key = try Ed25519.PublicKey.fromBytes(key_bytes),
+    };
+}
+
+// The channel picked with `--channel` is remembered in the default install directory, next to other wrapper state
+fn get_channel_path(allocator: std.mem.Allocator) ![]const u8 {
+    const default_base = try fs.getAppDataDir(allocator, build_options.INSTALL_SUFFIX);
+    const file_name = try std.fmt.allocPrint(allocator, "{s}.channel", .{build_options.RELEASE_NAME});
+    return try fs.path.join(allocator, &[_][]const u8{ default_base, file_name });
+}
+
+fn read_channel(allocator: std.mem.Allocator) ?[]const u8 {
+    const channel_path = get_channel_path(allocator) catch return null;
+    const file = fs.openFileAbsolute(channel_path, .{}) catch return null;
+    defer file.close();
+
+    const content = file.readToEndAlloc(allocator, MAX_CHANNEL_SIZE) catch return null;
+    const channel = std.mem.trim(u8, content, " \r\n");
+    return if (channel.len > 0) channel else null;
+}
+
+// Makes `channel` the one future updates follow
+pub fn save_channel(allocator: std.mem.Allocator, channel: []const u8) !void {
+    const channel_path = try get_channel_path(allocator);
+    try fs.cwd().makePath(fs.path.dirname(channel_path).?);
+
+    const file = try fs.createFileAbsolute(channel_path, .{ .truncate = true });
+    defer file.close();
+    try file.writeAll(channel);
+}
+
+// Downloads the body of `url` into memory
+pub fn download(allocator: std.mem.Allocator, url: []const u8, max_size: usize) ![]u8 {
+    const uri = try std.Uri.parse(url);
+
+    var client = std.http.Client{ .allocator = allocator };
+    defer client.deinit();
+
+    var headers = std.http.Headers{ .allocator = allocator };
+    defer headers.deinit();
+    try headers.append("user-agent", "burrito-updater");
+
+    var request = try client.request(.GET, uri, headers, .{});
+    defer request.deinit();
+
+    try request.start();
+    try request.wait();
+
+    if (request.response.status != .ok) {
+        logger.err("Download of {s} failed with HTTP status {}", .{ url, @intFromEnum(request.response.status) });
+        return error.DownloadFailed;
+    }
+
+    return try request.reader().readAllAlloc(allocator, max_size);
+}
+
+pub const CheckResult = struct {
+    current_version: []const u8,
+    latest_version: []const u8,
+    update_available: bool,
+    channel: []const u8,
+    url: []const u8,
+};
+
+// Asks the update endpoint for the latest version, published as plain text at `<url>.version`, without changing anything
+pub fn check(allocator: std.mem.Allocator, config: Config, current_version: []const u8) !CheckResult {
+    const version_url = try std.fmt.allocPrint(allocator, "{s}.version", .{config.url});
+    const latest_version = std.mem.trim(u8, try download(allocator, version_url, MAX_VERSION_SIZE), " \r\n");
+
+    const current = try std.SemanticVersion.parse(current_version);
+    const latest = std.SemanticVersion.parse(latest_version) catch {
+        logger.err("The update endpoint returned an invalid version: {s}", .{latest_version});
+        return error.InvalidVersion;
+    };
+
+    return CheckResult{
+        .current_version = current_version,
+        .latest_version = latest_version,
+        .update_available = std.SemanticVersion.order(latest, current) == .gt,
+        .channel = config.channel,
+        .url = config.url,
+    };
+}
+
+// Tries to rebuild the new binary from a delta against our own, published at `<url>.from-<our sha256>.delta`.
+// Returns null when there's no delta for us, the caller then falls back to the full download.
+// The result is checked against the signature of the full binary like any other download.
+fn download_delta(allocator: std.mem.Allocator, exe_path: []const u8, url: []const u8) ?[]u8 {
+    const exe_file = fs.openFileAbsolute(exe_path, .{}) catch return null;
+    defer exe_file.close();
+    const current_binary = exe_file.readToEndAlloc(allocator, MAX_DOWNLOAD_SIZE) catch return null;
+
+    var digest: [std.crypto.hash.sha2.Sha256.digest_length]u8 = undefined;
+    std.crypto.hash.sha2.Sha256.hash(current_binary, &digest, .{});
+
+    const delta_url = std.fmt.allocPrint(allocator, "{s}.from-{s}.delta", .{ url, std.fmt.fmtSliceHexLower(&digest) }) catch return null;
+    const compressed_delta = download(allocator, delta_url, MAX_DOWNLOAD_SIZE) catch {
+        logger.info("No delta update available, downloading the full binary", .{});
+        return null;
+    };
+
+    const new_binary = delta.apply(allocator, current_binary, compressed_delta) catch |err| {
+        logger.warn("Failed to apply the delta update ({!}), downloading the full binary", .{err});
+        return null;
+    };
+
+    logger.info("Rebuilt the update from a {} byte delta", .{compressed_delta.len});
+    return new_binary;
+}
+
+// Checks `data` against a signature, which may be the raw 64 bytes or hex encoded
+pub fn verify(data: []const u8, signature: []const u8, public_key: Ed25519.PublicKey) !void {
+    var signature_bytes: [Ed25519.Signature.encoded_length]u8 = undefined;
+
+    const trimmed = std.mem.trim(u8, signature, " \r\n");
+    if (trimmed.len == Ed25519.Signature.encoded_length * 2) {
+        const decoded = std.fmt.hexToBytes(&signature_bytes, trimmed) catch return error.InvalidSignature;
+        if (decoded.len != signature_bytes.len) {
+            return error.InvalidSignature;
+        }
+    } else if (trimmed.len == Ed25519.Signature.encoded_length) {
+        @memcpy(&signature_bytes, trimmed);
+    } else if (signature.len == Ed25519.Signature.encoded_length) {
+        // Raw bytes that start or end with what looks like whitespace
+        @memcpy(&signature_bytes, signature);
+    } else {
+        return error.InvalidSignature;
+    }
+
+    const sig = Ed25519.Signature.fromBytes(signature_bytes);
+    sig.verify(data, public_key) catch return error.SignatureVerificationFailed;
+}
+
+// Returns the path the previous binary is kept at after an update
+pub fn get_backup_path(allocator: std.mem.Allocator, exe_path: []const u8) ![]const u8 {
+    return try std.fmt.allocPrint(allocator, "{s}.old", .{exe_path});
+}
+
+// Replaces the binary at `exe_path` with `new_binary`, keeping the current one as `<exe_path>.old`
+pub fn replace_binary(allocator: std.mem.Allocator, exe_path: []const u8, new_binary: []const u8) !void {
+    const staged_path = try std.fmt.allocPrint(allocator, "{s}.new", .{exe_path});
+    const backup_path = try get_backup_path(allocator, exe_path);
+
+    // Stage the new binary next to the current one, renames are only atomic within the same file system
+    {
+        const staged = try fs.createFileAbsolute(staged_path, .{ .truncate = true, .mode = 0o755 });
+        defer staged.close();
+        try staged.writeAll(new_binary);
+        try staged.sync();
+    }
+    errdefer fs.deleteFileAbsolute(staged_path) catch {};
+
+    fs.deleteFileAbsolute(backup_path) catch |err| switch (err) {
+        error.FileNotFound => {},
+        else => return err,
+    };
+
+    if (builtin.os.tag == .windows) {
+        // A running executable can't be overwritten on Windows, but it can be moved out of the way
+        try fs.renameAbsolute(exe_path, backup_path);
+        fs.renameAbsolute(staged_path, exe_path) catch |err| {
+            fs.renameAbsolute(backup_path, exe_path) catch {};
+            return err;
+        };
+    } else {
+        // Keep a second link to the current binary, then atomically rename the new one over it
+        std.os.link(exe_path, backup_path, 0) catch {
+            try fs.copyFileAbsolute(exe_path, backup_path, .{});
+        };
+        try fs.renameAbsolute(staged_path, exe_path);
+    }
+}
+
+// What an update's signature covers, the binary is only represented by its SHA-256 hash:
+//
+//   burrito-update-v1\n<version>\n<channel>\n<sha256 of the binary, lowercase hex>\n
+pub fn signed_message(allocator: std.mem.Allocator, binary: []const u8, version: []const u8, channel: []const u8) ![]const u8 {
+    var digest: [std.crypto.hash.sha2.Sha256.digest_length]u8 = undefined;
+    std.crypto.hash.sha2.Sha256.hash(binary, &digest, .{});
+    return try std.fmt.allocPrint(allocator, "burrito-update-v1\n{s}\n{s}\n{s}\n", .{ version, channel, std.fmt.fmtSliceHexLower(&digest) });
+}
+
+// Downloads, verifies and installs the binary described by `config` over the running one, if it's newer than
+// `current_version` or `allow_downgrade` is set
+pub fn run(allocator: std.mem.Allocator, config: Config, current_version: []const u8, allow_downgrade: bool) !void {
+    const exe_path = try fs.selfExePathAlloc(allocator);
+
+    const result = try check(allocator, config, current_version);
+    if (!result.update_available and !allow_downgrade) {
+        if (std.mem.eql(u8, result.latest_version, current_version)) {
+            logger.info("Already up to date on the {s} channel (v{s})", .{ config.channel, current_version });
+            return;
+        }
+        logger.err("The {s} channel offers v{s}, which is older than v{s}, pass `--allow-downgrade` to install it anyway", .{ config.channel, result.latest_version, current_version });
+        return error.DowngradeRefused;
+    }
+
+    logger.info("Downloading v{s} from the {s} channel: {s}", .{ result.latest_version, config.channel, config.url });
+    const new_binary = download_delta(allocator, exe_path, config.url) orelse try download(allocator, config.url, MAX_DOWNLOAD_SIZE);
+
+    const signature_url = try std.fmt.allocPrint(allocator, "{s}.sig", .{config.url});
+    const signature = try download(allocator, signature_url, MAX_SIGNATURE_SIZE);
+
+    const message = try signed_message(allocator, new_binary, result.latest_version, config.channel);
+    verify(message, signature, config.public_key) catch |err| {
+        logger.err("The downloaded update failed signature verification for v{s} on the {s} channel, refusing to install it", .{ result.latest_version, config.channel });
+        return err;
+    };
+
+    logger.info("Signature verified, installing update over {s}", .{exe_path});
+    try replace_binary(allocator, exe_path, new_binary);
+
+    logger.info("Update complete! The previous binary was kept at {s}", .{try get_backup_path(allocator, exe_path)});
+}
@@ -0,0 +1,185 @@
+/////
+// A per-user config file, so desktop users have a documented way to configure an application without touching the
+// release: `config.toml` or `config.json` in the profile's config directory (`BURRITO_CONFIG_DIR`, see profile.zig).
+// The wrapper checks it and converts it into Erlang terms, which `runtime.exs` can read without any dependency:
+//
+//   if path = System.get_env("BURRITO_USER_CONFIG") do
+//     {:ok, [user_config]} = :file.consult(path)
+//     config :my_app, port: get_in(user_config, ["server", "port"]) || 4000
+//   end
+//
+// Keys and strings become binaries, TOML sections and JSON objects become maps, JSON `null` becomes `nil`. TOML files
+// are limited to what runtime_config.zig understands. A broken file is reported and left out, it never stops a launch.
+/////
+
+const std = @import("std");
+
+const profile = @import("profile.zig");
+const runtime_config = @import("runtime_config.zig");
+const MetaStruct = @import("metadata.zig").MetaStruct;
+
+const fs = std.fs;
+const log = std.log;
+const mem = std.mem;
+
+const Value = std.json.Value;
+
+const MAX_FILE_SIZE = 1024 * 1024;
+
+// Checked in this order, the first one found is used
+const file_names = [_][]const u8{ "config.toml", "config.json" };
+
+// Converts the user's config file, if there is one, and points `BURRITO_USER_CONFIG` at the result
+pub fn apply_env(allocator: mem.Allocator, env_map: *std.process.EnvMap, meta: *const MetaStruct) !void {
+    const config_dir = try profile.get_config_dir(allocator, meta);
+
+    for (file_names) |file_name| {
+        const path = try fs.path.join(allocator, &[_][]const u8{ config_dir, file_name });
+        const source = fs.cwd().readFileAlloc(allocator, path, MAX_FILE_SIZE) catch |err| switch (err) {
+            error.FileNotFound => continue,
+            else => {
+                log.warn("Could not read the config file {s}: {!}", .{ path, err });
+                return;
+            },
+        };
+
+        const config = (if (mem.endsWith(u8, file_name, ".toml")) from_toml(allocator, path, source) else from_json(allocator, path, source)) orelse return;
+
+        const terms_path = try fs.path.join(allocator, &[_][]const u8{ try profile.get_data_dir(allocator, meta), "user_config.terms" });
+        write_terms(terms_path, config) catch |err| {
+            log.warn("Could not write the converted config file {s}: {!}", .{ terms_path, err });
+            return;
+        };
+
+        log.debug("Loaded the config file {s}", .{path});
+        try env_map.put("BURRITO_USER_CONFIG", terms_path);
+        return;
+    }
+}
+
+fn from_toml(allocator: mem.Allocator, path: []const u8, source: []const u8) ?Value {
+    var diagnostic = runtime_config.Diagnostic{};
+    const entries = runtime_config.parse(allocator, source, &diagnostic) catch |err| {
+        log.warn("Ignoring the config file {s}, line {}: {s} ({!})", .{ path, diagnostic.line, diagnostic.message, err });
+        return null;
+    };
+
+    return entries_to_value(allocator, entries) catch |err| {
+        log.warn("Ignoring the config file {s}: {!}", .{ path, err });
+        return null;
+    };
+}
+
+fn entries_to_value(allocator: mem.Allocator, entries: []const runtime_config.Entry) !Value {
+    var root = std.json.ObjectMap.init(allocator);
+
+    for (entries) |entry| {
+        // Nested sections (`[a.b]`) become nested maps
+        var object = &root;
+        var sections = mem.tokenizeScalar(u8, entry.section, '.');
+        while (sections.next()) |section| {
+            const slot = try object.getOrPut(section);
+            if (!slot.found_existing) {
+                slot.value_ptr.* = .{ .object = std.json.ObjectMap.init(allocator) };
+            } else if (slot.value_ptr.* != .object) {
+                return error.KeyIsNotASection;
+            }
+            object = &slot.value_ptr.object;
+        }
+
+        try object.put(entry.name, switch (entry.value) {
+            .string => |string| .{ .string = string },
+            .integer => |integer| .{ .integer = integer },
+            .boolean => |boolean| .{ .bool = boolean },
+            .array => |strings| blk: {
+                var array = std.json.Array.init(allocator);
+                for (strings) |string| {
+                    try array.append(.{ .string = string });
+                }
+                break :blk .{ .array = array };
+            },
+        });
+    }
+
+    return .{ .object = root };
+}
+
+fn from_json(allocator: mem.Allocator, path: []const u8, source: []const u8) ?Value {
+    const parsed = std.json.parseFromSlice(Value, allocator, source, .{}) catch |err| {
+        log.warn("Ignoring the config file {s}: {!}", .{ path, err });
+        return null;
+    };
+    return parsed.value;
+}
+
+fn write_terms(path: []const u8, config: Value) !void {
+    if (fs.path.dirname(path)) |dir| {
+        try fs.cwd().makePath(dir);
+    }
+
+    const file = try fs.cwd().createFile(path, .{ .truncate = true });
+    defer file.close();
+
+    var buffered = std.io.bufferedWriter(file.writer());
+    const writer = buffered.writer();
+    try writer.writeAll("%% Generated by the wrapper from the user's config file, edit that one instead\n");
+    try write_term(writer, config);
+    try writer.writeAll(".\n");
+    try buffered.flush();
+}
+
+// Recursive, so its error set has to be spelled out
+fn write_term(writer: anytype, value: Value) (@TypeOf(writer).Error || error{NoSpaceLeft})!void {
+    switch (value) {
+        .null => try writer.writeAll("nil"),
+        .bool => |boolean| try writer.writeAll(if (boolean) "true" else "false"),
+        .integer => |integer| try writer.print("{}", .{integer}),
+        .float => |float| {
+            // Erlang floats always need a fractional part
+            var buffer: [512]u8 = undefined;
+            const text = try std.fmt.bufPrint(&buffer, "{d}", .{float});
+            try writer.writeAll(text);
+            if (mem.indexOfAny(u8, text, ".e") == null) {
+                try writer.writeAll(".0");
+            }
+        },
+        .number_string => |number| try writer.writeAll(number),
+        .string => |string| try write_binary(writer, string),
+        .array => |array| {
+            try writer.writeByte('[');
+            for (array.items, 0..) |item, i| {
+                if (i > 0) try writer.writeAll(", ");
+                try write_term(writer, item);
+            }
+            try writer.writeByte(']');
+        },
+        .object => |object| {
+            try writer.writeAll("#{");
+            var it = object.iterator();
+            var first = true;
+            while (it.next()) |entry| {
+                if (!first) try writer.writeAll(", ");
+                first = false;
+                try write_binary(writer, entry.key_ptr.*);
+                try writer.writeAll(" => ");
+                try write_term(writer, entry.value_ptr.*);
+            }
+            try writer.writeByte('}');
+        },
+    }
+}
+
+fn write_binary(writer: anytype, string: []const u8) !void {
+    try writer.writeAll("<<\"");
+    for (string) |c| {
+        switch (c) {
+            '"' => try writer.writeAll("\\\""),
+            '\\' => try writer.writeAll("\\\\"),
+            '\n' => try writer.writeAll("\\n"),
+            '\t' => try writer.writeAll("\\t"),
+            0...8, 11...31, 127 => try writer.print("\\{o:0>3}", .{c}),
+            else => try writer.writeByte(c),
+        }
+    }
+    try writer.writeAll("\"/utf8>>");
+}
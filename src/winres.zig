@@ -0,0 +1,210 @@
+/////
+// Writes a compiled Windows resource file (`.res`) with the app's icon and version information, which build.zig links
+// into the wrapper on Windows targets, so Explorer shows the icon and the "Details" tab of the file's properties.
+// Only the two resource types we need are supported, so no resource compiler is needed on the build host.
+/////
+
+const std = @import("std");
+
+const mem = std.mem;
+
+const RT_ICON = 3;
+const RT_GROUP_ICON = 14;
+const RT_VERSION = 16;
+
+// English (United States), with the strings in UTF-16 (code page 1200)
+const LANGUAGE_ID = 0x0409;
+const CODE_PAGE = 1200;
+const STRING_TABLE_KEY = "040904B0";
+
+const MOVEABLE = 0x0010;
+const PURE = 0x0020;
+const DISCARDABLE = 0x1000;
+
+// The strings shown in the file's properties, empty ones are left out
+pub const VersionInfo = struct {
+    product_name: []const u8 = "",
+    company_name: []const u8 = "",
+    file_description: []const u8 = "",
+    legal_copyright: []const u8 = "",
+    original_filename: []const u8 = "",
+    // The release version, e.g. `1.2.3-rc.1`, its numeric part also goes into the fixed version fields
+    version: []const u8 = "",
+};
+
+// `icon` is the contents of an `.ico` file
+pub fn write(allocator: mem.Allocator, writer: anytype, icon: ?[]const u8, info: VersionInfo) !void {
+    // A .res file starts with an empty resource, so tools can tell it from a 16-bit one
+    try write_resource(writer, 0, 0, 0, &.{});
+
+    if (icon) |ico| {
+        try write_icons(allocator, writer, ico);
+    }
+
+    const version_info = try build_version_info(allocator, info);
+    try write_resource(writer, RT_VERSION, 1, MOVEABLE | PURE, version_info);
+}
+
+fn write_resource(writer: anytype, resource_type: u16, name: u16, memory_flags: u16, data: []const u8) !void {
+    const header_size = 32;
+    try writer.writeIntLittle(u32, @intCast(data.len));
+    try writer.writeIntLittle(u32, header_size);
+    // Type and name as ordinals
+    try writer.writeIntLittle(u16, 0xFFFF);
+    try writer.writeIntLittle(u16, resource_type);
+    try writer.writeIntLittle(u16, 0xFFFF);
+    try writer.writeIntLittle(u16, name);
+    // Data version
+    try writer.writeIntLittle(u32, 0);
+    try writer.writeIntLittle(u16, memory_flags);
+    try writer.writeIntLittle(u16, if (resource_type == 0) 0 else LANGUAGE_ID);
+    // Version and characteristics
+    try writer.writeIntLittle(u32, 0);
+    try writer.writeIntLittle(u32, 0);
+
+    try writer.writeAll(data);
+    try writer.writeByteNTimes(0, padding(data.len));
+}
+
+// Each image of the `.ico` file becomes an icon resource, the group resource lists them by their ids
+fn write_icons(allocator: mem.Allocator, writer: anytype, ico: []const u8) !void {
+    const dir_size = 6;
+    const entry_size = 16;
+    if (ico.len < dir_size or mem.readIntLittle(u16, ico[0..2]) != 0 or mem.readIntLittle(u16, ico[2..4]) != 1) {
+        return error.InvalidIcon;
+    }
+    const count = mem.readIntLittle(u16, ico[4..6]);
+    if (count == 0 or ico.len < dir_size + @as(usize, count) * entry_size) {
+        return error.InvalidIcon;
+    }
+
+    var group = std.ArrayList(u8).init(allocator);
+    try group.appendSlice(ico[0..dir_size]);
+
+    for (0..count) |i| {
+        const entry = ico[dir_size + i * entry_size ..][0..entry_size];
+        const size = mem.readIntLittle(u32, entry[8..12]);
+        const offset = mem.readIntLittle(u32, entry[12..16]);
+        if (@as(u64, offset) + size > ico.len) {
+            return error.InvalidIcon;
+        }
+
+        const id: u16 = @intCast(i + 1);
+        try write_resource(writer, RT_ICON, id, MOVEABLE | DISCARDABLE, ico[offset..][0..size]);
+
+        // Same as the file's entry, with the resource id in place of the offset
+        try group.appendSlice(entry[0..12]);
+        try group.writer().writeIntLittle(u16, id);
+    }
+
+    try write_resource(writer, RT_GROUP_ICON, 1, MOVEABLE | PURE | DISCARDABLE, group.items);
+}
+
+fn build_version_info(allocator: mem.Allocator, info: VersionInfo) ![]const u8 {
+    var buffer = std.ArrayList(u8).init(allocator);
+    const writer = buffer.writer();
+    const version = parse_version(info.version);
+
+    const root = try begin_node(allocator, &buffer, "VS_VERSION_INFO", .binary);
+    // VS_FIXEDFILEINFO
+    set_value_length(&buffer, root, 52);
+    try writer.writeIntLittle(u32, 0xFEEF04BD);
+    try writer.writeIntLittle(u32, 0x00010000);
+    for (0..2) |_| {
+        // File and product version
+        try writer.writeIntLittle(u32, @as(u32, version[0]) << 16 | version[1]);
+        try writer.writeIntLittle(u32, @as(u32, version[2]) << 16 | version[3]);
+    }
+    // Flags mask and flags
+    try writer.writeIntLittle(u32, 0x3F);
+    try writer.writeIntLittle(u32, 0);
+    // VOS_NT_WINDOWS32, VFT_APP, no subtype and no date
+    try writer.writeIntLittle(u32, 0x00040004);
+    try writer.writeIntLittle(u32, 1);
+    try writer.writeIntLittle(u32, 0);
+    try writer.writeIntLittle(u32, 0);
+    try writer.writeIntLittle(u32, 0);
+
+    const string_file_info = try begin_node(allocator, &buffer, "StringFileInfo", .text);
+    const string_table = try begin_node(allocator, &buffer, STRING_TABLE_KEY, .text);
+    const strings = [_][2][]const u8{
+        .{ "CompanyName", info.company_name },
+        .{ "FileDescription", info.file_description },
+        .{ "FileVersion", info.version },
+        .{ "InternalName", info.product_name },
+        .{ "LegalCopyright", info.legal_copyright },
+        .{ "OriginalFilename", info.original_filename },
+        .{ "ProductName", info.product_name },
+        .{ "ProductVersion", info.version },
+    };
+    for (strings) |string| {
+        if (string[1].len == 0) continue;
+        const node = try begin_node(allocator, &buffer, string[0], .text);
+        set_value_length(&buffer, node, try write_utf16(allocator, &buffer, string[1]));
+        end_node(&buffer, node);
+    }
+    end_node(&buffer, string_table);
+    end_node(&buffer, string_file_info);
+
+    const var_file_info = try begin_node(allocator, &buffer, "VarFileInfo", .text);
+    const translation = try begin_node(allocator, &buffer, "Translation", .binary);
+    set_value_length(&buffer, translation, 4);
+    try writer.writeIntLittle(u16, LANGUAGE_ID);
+    try writer.writeIntLittle(u16, CODE_PAGE);
+    end_node(&buffer, translation);
+    end_node(&buffer, var_file_info);
+
+    end_node(&buffer, root);
+    return buffer.toOwnedSlice();
+}
+
+const ValueType = enum(u16) { binary = 0, text = 1 };
+
+// Every node of the version information is `wLength, wValueLength, wType, szKey`, aligned to 4 bytes,
+// followed by its value and children. Returns where it starts, to fill in the lengths later
+fn begin_node(allocator: mem.Allocator, buffer: *std.ArrayList(u8), key: []const u8, value_type: ValueType) !usize {
+    try buffer.appendNTimes(0, padding(buffer.items.len));
+    const start = buffer.items.len;
+    try buffer.writer().writeIntLittle(u16, 0);
+    try buffer.writer().writeIntLittle(u16, 0);
+    try buffer.writer().writeIntLittle(u16, @intFromEnum(value_type));
+    _ = try write_utf16(allocator, buffer, key);
+    try buffer.appendNTimes(0, padding(buffer.items.len));
+    return start;
+}
+
+// In bytes for binary values, in characters for text
+fn set_value_length(buffer: *std.ArrayList(u8), start: usize, length: usize) void {
+    mem.writeIntLittle(u16, buffer.items[start + 2 ..][0..2], @intCast(length));
+}
+
+fn end_node(buffer: *std.ArrayList(u8), start: usize) void {
+    mem.writeIntLittle(u16, buffer.items[start..][0..2], @intCast(buffer.items.len - start));
+}
+
+// Writes `text` as null terminated UTF-16, returns its length in characters including the terminator
+fn write_utf16(allocator: mem.Allocator, buffer: *std.ArrayList(u8), text: []const u8) !usize {
+    const wide = try std.unicode.utf8ToUtf16LeWithNull(allocator, text);
+    defer allocator.free(wide);
+    for (wide) |char| {
+        try buffer.writer().writeIntLittle(u16, char);
+    }
+    try buffer.writer().writeIntLittle(u16, 0);
+    return wide.len + 1;
+}
+
+// `1.2.3-rc.1` is 1.2.3.0, Windows versions have four numbers and no pre-release part
+fn parse_version(version: []const u8) [4]u16 {
+    var numbers = [_]u16{ 0, 0, 0, 0 };
+    const end = mem.indexOfAny(u8, version, "-+") orelse version.len;
+    var parts = mem.splitScalar(u8, version[0..end], '.');
+    for (&numbers) |*number| {
+        const part = parts.next() orelse break;
+        number.* = std.fmt.parseInt(u16, part, 10) catch 0;
+    }
+    return numbers;
+}
+
+fn padding(len: usize) usize {
+    return mem.alignForward(usize, len, 4) - len;
+}
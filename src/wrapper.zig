@@ -16,22 +16,74 @@ const Base64 = std.base64.url_safe_no_pad.Encoder;
 // Foilz Archive Util
 const foilz = @import("archiver.zig");
 
+// The steps of a run, public so plugins can drive them through `@import("root").burrito`
+pub const burrito = @import("burrito.zig");
+
 // Maint utils
+const app_bundle = @import("app_bundle.zig");
+const components = @import("components.zig");
+const crash_dumps = @import("crash_dumps.zig");
+const exit_codes = @import("exit_codes.zig");
+const features = @import("features.zig");
+const flags = @import("flags.zig");
+const i18n = @import("i18n.zig");
+const install = @import("install.zig");
 const logger = @import("logger.zig");
+const mac_policy = @import("mac_policy.zig");
 const maint = @import("maintenance.zig");
+const panic_handler = @import("panic_handler.zig");
+const profile = @import("profile.zig");
+const progress = @import("progress.zig");
+const quarantine = @import("quarantine.zig");
+const rollback = @import("rollback.zig");
+const sandbox = @import("sandbox.zig");
 const shutil = @import("shutil.zig");
+const tamper = @import("tamper.zig");
+const trace = @import("trace.zig");
+const termux = @import("termux.zig");
+const user_config = @import("user_config.zig");
 
-// Install dir suffix
-const install_suffix = ".burrito";
+// More threads than this only add contention on the file system
+const MAX_EXTRACT_THREADS = 16;
 
 const plugin = @import("burrito_plugin");
 
 const metadata = @import("metadata.zig");
 const MetaStruct = metadata.MetaStruct;
 
-// Payload
-pub const FOILZ_PAYLOAD = @embedFile("payload.foilz.xz");
+// Payload, either embedded or shipped next to the binary (`sidecar_payload: true`), so a code signature covers
+// only the wrapper itself and a new payload doesn't invalidate it.
+// The embedded payload is split into chunks when the wrapper is built, which keeps the compiler's memory use down.
+const EMBEDDED_PAYLOAD_CHUNKS = blk: {
+    var chunks: [build_options.PAYLOAD_CHUNK_COUNT][]const u8 = undefined;
+    for (&chunks, 0..) |*chunk, i| {
+        chunk.* = @embedFile(std.fmt.comptimePrint("payload_chunks/{d}.bin", .{i}));
+    }
+    break :blk chunks;
+};
+var sidecar_payload: ?[]const u8 = null;
+var stitched_payload: ?[]const u8 = null;
+
 pub const RELEASE_METADATA_JSON = @embedFile("_metadata.json");
+// Uncompressed FOILZ archive of the license and notice files of the app and its dependencies
+pub const LICENSES_FOILZ = @embedFile("licenses.foilz");
+pub const SBOM = @embedFile("sbom.bin");
+// Overrides for the release's options, see runtime_config.zig
+pub const RUNTIME_CONFIG = @embedFile("runtime_config.toml");
+// The optional components left out of the payload, see components.zig
+pub const COMPONENTS_JSON = @embedFile("components.json");
+
+// Route `std.log` through our logger so it respects the configured log target,
+// all levels are compiled in and filtered at runtime by the verbosity setting
+pub const std_options = struct {
+    pub const log_level = .debug;
+    pub const logFn = logger.std_log_fn;
+};
+
+// End users get a short message and a crash log instead of a stack trace
+pub fn panic(message: []const u8, error_return_trace: ?*std.builtin.StackTrace, ret_addr: ?usize) noreturn {
+    panic_handler.handle(message, error_return_trace, ret_addr);
+}
 
 // Memory allocator
 var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
@@ -45,6 +97,29 @@ pub extern "kernel32" fn GetCommandLineW() LPWSTR;
 pub extern "shell32" fn CommandLineToArgvW(lpCmdLine: LPCWSTR, out_pNumArgs: *c_int) ?[*]LPWSTR;
 
 pub fn main() anyerror!void {
+    run() catch |err| {
+        // Tools asking for JSON errors get the report as the last line on stderr, not Zig's error trace
+        if (logger.json_errors_enabled()) {
+            logger.report_failure(err, .{});
+            exit_codes.exit_with(err);
+        }
+        logger.report_final_error(err);
+        if (i18n.explain_failure(err)) |explanation| {
+            logger.err("{s}", .{explanation});
+        }
+        // The error trace only helps while working on the wrapper itself
+        if (!build_options.IS_PROD) {
+            if (@errorReturnTrace()) |error_trace| {
+                std.debug.dumpStackTrace(error_trace.*);
+            }
+        }
+        exit_codes.exit_with(err);
+    };
+}
+
+fn run() anyerror!void {
+    trace.mark_start();
+
     var args: ?[][]u8 = null;
 
     // Get argvs -- on Windows we need to call CommandLineToArgvW() with GetCommandLineW()
@@ -68,64 +143,191 @@ pub fn main() anyerror!void {
     }
 
     // Trim args to only what we actually want to pass to erlang
-    const args_trimmed = args.?[1..];
+    var wrapper_flags = flags.Flags{};
+    var args_trimmed = flags.parse(args.?[1..], &wrapper_flags);
+    logger.set_json_errors(wrapper_flags.json_errors or shutil.release_env_enabled(allocator, "JSON_ERRORS"));
+    logger.set_color(!wrapper_flags.no_color and shutil.wants_color(allocator));
+
+    // `<APP>_DEBUG=1` makes a production build as chatty as a development one, for debugging in the field.
+    // Flags win over the environment
+    if (shutil.release_env_enabled(allocator, "DEBUG")) {
+        logger.set_verbosity(.verbose);
+    }
+    if (shutil.get_release_env(allocator, "VERBOSITY")) |name| {
+        logger.set_verbosity_name(name);
+    }
+    if (wrapper_flags.verbosity) |verbosity| {
+        logger.set_verbosity(verbosity);
+    }
+
+    // `--burrito daemon` goes through the regular install flow, but starts the VM under `run_erl`
+    var launch_mode: launcher.LaunchMode = .foreground;
+    if (args_trimmed.len > 1 and maint.is_namespace(args_trimmed[0]) and std.mem.eql(u8, args_trimmed[1], "daemon")) {
+        launch_mode = .daemon;
+        args_trimmed = args_trimmed[2..];
+    }
 
     // If this is not a production build, we always want a clean install
     const wants_clean_install = !build_options.IS_PROD;
 
-    const meta = metadata.parse(allocator, RELEASE_METADATA_JSON).?;
-    const install_dir = (try get_install_dir(&meta))[0..];
+    const parse_span = trace.begin("parse metadata");
+    const meta = try burrito.get_metadata(allocator);
+    parse_span.end();
+    i18n.init(&meta);
+    panic_handler.init(&meta);
+    app_bundle.init(allocator, &meta);
+    if (app_bundle.get() != null) {
+        args_trimmed = app_bundle.remove_finder_args(args_trimmed);
+    }
+
+    // Pick where our own log messages go, before we log anything else.
+    // A double-clicked app has no console, its messages go to the system log unless the release picked a target
+    const default_log_target = if (app_bundle.is_gui_launch() and std.mem.eql(u8, meta.log_target, "console")) "syslog" else meta.log_target;
+    logger.set_target(wrapper_flags.log_target orelse shutil.get_release_env(allocator, "LOG_TARGET") orelse default_log_target);
+    logger.set_release_version(meta.app_version);
+
+    const profile_name = wrapper_flags.profile orelse shutil.get_release_env(allocator, "PROFILE") orelse profile.DEFAULT;
+    profile.set(profile_name) catch {
+        logger.err("Invalid profile name `{s}`, use letters, digits, `_`, `-` and `.` only", .{profile_name});
+        std.process.exit(1);
+    };
+
+    if (wrapper_flags.config orelse shutil.get_release_env(allocator, "CONFIG")) |config_path| {
+        launcher.set_extra_config(allocator, config_path) catch |err| {
+            logger.err("Can't use `{s}` as a config file: {!}, it must be an existing file ending in `.config`", .{ config_path, err });
+            std.process.exit(1);
+        };
+    }
+
+    if (logger.is_verbose()) {
+        log.debug("Wrapper arguments: {s}", .{args.?});
+        log.debug("Wrapper executable: {s}", .{fs.selfExePathAlloc(allocator) catch "unknown"});
+        log.debug("Release metadata: {s}", .{RELEASE_METADATA_JSON});
+    }
+
+    const install_dir = try burrito.resolve_install_dir(&meta);
     const metadata_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, "_metadata.json" });
 
-    // Check for maintenance commands
-    if (args_trimmed.len > 0 and std.mem.eql(u8, args_trimmed[0], "maintenance")) {
-        try maint.do_maint(args_trimmed[1..], install_dir);
+    // The elevated copy of ourselves started for an install that needs admin rights only installs, then exits
+    if (wrapper_flags.elevated_install) |elevated_install_dir| {
+        logger.set_phase("install");
+        const elevated_payload_changed = !install.is_payload_current(allocator, elevated_install_dir, build_options.PAYLOAD_SHA256);
+        try install_payload(&meta, elevated_install_dir, if (wants_clean_install or elevated_payload_changed) .clean else .overwrite);
+        try maint.do_clean_old_versions(fs.path.dirname(elevated_install_dir).?, elevated_install_dir, meta.keep_previous_version);
+        return;
+    }
+
+    // Check for maintenance commands, these are intercepted before anything is passed to the application
+    if (launch_mode == .foreground and args_trimmed.len > 0 and maint.is_namespace(args_trimmed[0])) {
+        if (!features.enabled(.maintenance)) {
+            logger.err("Maintenance commands were left out of this build of {s}", .{build_options.RELEASE_NAME});
+            std.process.exit(1);
+        }
+        logger.set_phase("maintenance");
+        try maint.do_maint(args_trimmed[1..], install_dir, &meta);
+        return;
+    }
+
+    // After a `--burrito rollback`, the pinned previous install is launched instead of our own payload
+    if (rollback.read_pinned_install(allocator, fs.path.dirname(install_dir).?)) |pinned| {
+        logger.warn("Running rolled back version v{s}, use `--burrito rollback --undo` to go forward again", .{pinned.metadata.app_version});
+
+        var pinned_env_map = try build_env_map(pinned.install_dir_path, &pinned.metadata, true);
+        logger.set_phase("launch");
+        launcher.launch(pinned.install_dir_path, &pinned_env_map, &pinned.metadata, args_trimmed, launch_mode) catch |err| {
+            logger.err("Failed to launch the Erlang VM: {!}", .{err});
+            logger.report_failure(err, .{ .install_dir = pinned.install_dir_path, .mode = @tagName(launch_mode), .rolled_back = true });
+            return err;
+        };
         return;
     }
 
-    log.debug("Size of embedded payload is: {}", .{FOILZ_PAYLOAD.len});
     log.debug("Install Directory: {s}", .{install_dir});
     log.debug("Metadata path: {s}", .{metadata_path});
 
-    // Ensure the destination directory is created
-    try std.fs.cwd().makePath(install_dir);
-
-    // If the metadata file exists, don't install again
-    var needs_install: bool = false;
-    std.fs.accessAbsolute(metadata_path, .{}) catch |err| {
-        if (err == error.FileNotFound) {
-            needs_install = true;
-        } else {
-            log.err("We failed to open the destination directory with an unexpected error: {!}", .{err});
-            return;
-        }
-    };
-
     log.debug("Passing args string: {s}", .{args_trimmed});
 
     // Execute plugin code
     plugin.burrito_plugin_entry(install_dir, RELEASE_METADATA_JSON);
 
-    // If we need an install, install the payload onto the target machine
-    if (needs_install or wants_clean_install) {
-        // If running a clean install (probably a debug build)
-        // delete existing install directory if it's present to prevent a MacOS SIP issue
-        // when "replacing" a mach-o in place
-        if (wants_clean_install and !needs_install) {
-            try fs.deleteTreeAbsolute(install_dir);
-            try std.fs.cwd().makePath(install_dir);
-        }
+    _ = try burrito.ensure_installed(&meta, install_dir, .{ .clean = wants_clean_install });
+    try burrito.launch(&meta, install_dir, args_trimmed, launch_mode);
+}
+
+// What happens to the files of an existing install when the payload is unpacked over it
+pub const ExistingInstall = enum {
+    // Files in the way are replaced
+    overwrite,
+    // The whole install directory is removed first
+    clean,
+    // Files that already match the payload are left alone, only missing or damaged ones are written
+    repair,
+};
+
+const PayloadInstall = struct {
+    meta: *const MetaStruct,
+    install_dir: []const u8,
+    metadata_path: []const u8,
+    skip_unchanged: bool,
+
+    fn run(self: PayloadInstall) anyerror!void {
+        try do_payload_install(self.meta, self.install_dir, self.metadata_path, self.skip_unchanged);
+    }
+};
+
+// Unpacks the payload into `install_dir`, `existing` says what happens to an install that's already there
+pub fn install_payload(meta: *const MetaStruct, install_dir: []const u8, existing: ExistingInstall) !void {
+    const metadata_path = try fs.path.join(allocator, &[_][]const u8{ install_dir, "_metadata.json" });
 
-        try do_payload_install(install_dir, metadata_path);
+    // If running a clean install (probably a debug build)
+    // delete existing install directory if it's present to prevent a MacOS SIP issue
+    // when "replacing" a mach-o in place
+    if (existing == .clean) {
+        fs.deleteTreeAbsolute(install_dir) catch |err| {
+            logger.set_error_context("removing the previous install", install_dir);
+            return err;
+        };
+    }
+    std.fs.cwd().makePath(install_dir) catch |err| {
+        logger.set_error_context("creating the install directory", install_dir);
+        return err;
+    };
+
+    const skip_unchanged = existing == .repair;
+    if (meta.sandbox_extraction) {
+        const context = PayloadInstall{ .meta = meta, .install_dir = install_dir, .metadata_path = metadata_path, .skip_unchanged = skip_unchanged };
+        try sandbox.run(install_dir, context, PayloadInstall.run);
     } else {
-        log.debug("Skipping archive unpacking, this machine already has the app installed!", .{});
+        do_payload_install(meta, install_dir, metadata_path, skip_unchanged) catch |err| {
+            logger.err("Failed to install the application payload into {s}: {!}", .{ install_dir, err });
+            logger.event("install_failed", .{ .install_dir = install_dir, .error_code = @errorName(err) });
+            return err;
+        };
     }
 
-    // Clean up older versions
-    const base_install_path = try get_base_install_dir();
-    try maint.do_clean_old_versions(base_install_path, install_dir);
+    const permissions_timer = trace.start(.set_permissions);
+    if (meta.clear_quarantine and !shutil.release_env_enabled(allocator, "KEEP_QUARANTINE")) {
+        quarantine.clear(allocator, install_dir);
+    }
+
+    if (meta.selinux_type) |selinux_type| {
+        mac_policy.label(allocator, install_dir, selinux_type);
+    }
+    permissions_timer.stop();
+
+    if (meta.short_install_dir) {
+        try install.write_name_marker(allocator, install_dir, meta);
+    }
+
+    try tamper.write_manifest(allocator, install_dir, meta);
+
+    // Last, an install without it is redone on the next launch
+    try install.write_payload_hash(allocator, install_dir, build_options.PAYLOAD_SHA256);
+}
 
-    // Get Env
+// Builds the environment the VM is started with, on top of our own.
+// `apply_policies` also cleans up old crash dumps, it's off when we only want to look at the result.
+pub fn build_env_map(install_dir: []const u8, meta: *const MetaStruct, apply_policies: bool) !std.process.EnvMap {
     var env_map = try std.process.getEnvMap(allocator);
 
     // Add _IS_TTY env variable
@@ -135,78 +337,161 @@ pub fn main() anyerror!void {
         try env_map.put("_IS_TTY", "0");
     }
 
-    log.debug("Launching erlang...", .{});
+    if (termux.get_prefix()) |prefix| {
+        try termux.apply_env(allocator, &env_map, prefix);
+    }
 
-    try launcher.launch(install_dir, &env_map, &meta, args_trimmed);
-}
+    try app_bundle.apply_env(allocator, &env_map);
+    try components.apply_env(allocator, &env_map, install_dir);
+    try profile.apply_env(allocator, &env_map, meta, apply_policies);
+    if (apply_policies) {
+        try user_config.apply_env(allocator, &env_map, meta);
+    }
 
-fn do_payload_install(install_dir: []const u8, metadata_path: []const u8) !void {
-    // Unpack the files
-    try foilz.unpack_files(FOILZ_PAYLOAD, install_dir, build_options.UNCOMPRESSED_SIZE);
+    // Point crash dumps into the install dir and apply the retention policy, unless the user picked a location
+    if (env_map.get("ERL_CRASH_DUMP") == null) {
+        const dump_dir = try crash_dumps.get_dump_dir(allocator, install_dir);
+
+        if (apply_policies) {
+            std.fs.cwd().makePath(dump_dir) catch |err| {
+                // e.g. an install made with admin rights, the VM then writes crash dumps into its working directory
+                log.warn("Could not create the crash dump directory {s}: {!}", .{ dump_dir, err });
+                return env_map;
+            };
+
+            const policy = crash_dumps.Policy{
+                .keep = meta.crash_dump_keep,
+                .max_bytes = meta.crash_dump_max_bytes,
+                .compress = meta.crash_dump_compress,
+            };
+            crash_dumps.apply_policy(allocator, dump_dir, policy) catch |err| {
+                log.warn("Failed to clean up old crash dumps: {!}", .{err});
+            };
+        }
 
-    // Write metadata file
-    const file = try fs.createFileAbsolute(metadata_path, .{ .truncate = true });
-    try file.writeAll(RELEASE_METADATA_JSON);
+        try env_map.put("ERL_CRASH_DUMP", try crash_dumps.get_next_dump_path(allocator, dump_dir));
+    }
+
+    return env_map;
 }
 
-fn get_base_install_dir() ![]const u8 {
-    // If we have a override for the install path, use that, otherwise, continue to return
-    // the standard install path
-    const upper_name = try std.ascii.allocUpperString(allocator, build_options.RELEASE_NAME);
-    const env_install_dir_name = try std.fmt.allocPrint(allocator, "{s}_INSTALL_DIR", .{upper_name});
-
-    if (std.process.getEnvVarOwned(allocator, env_install_dir_name)) |new_path| {
-        logger.info("Install path is being overriden using `{s}`", .{env_install_dir_name});
-        logger.info("New install path is: {s}", .{new_path});
-        return try fs.path.join(allocator, &[_][]const u8{ new_path, install_suffix });
-    } else |err| switch (err) {
-        error.InvalidUtf8 => {},
-        error.EnvironmentVariableNotFound => {},
-        error.OutOfMemory => {},
-    }
-
-    const app_dir = fs.getAppDataDir(allocator, install_suffix) catch {
-        install_dir_error();
-        return "";
-    };
+// Returns the compressed payload as the chunks it was embedded in, or the sidecar file as a single chunk
+pub fn get_payload_chunks() ![]const []const u8 {
+    if (!build_options.SIDECAR_PAYLOAD) {
+        return &EMBEDDED_PAYLOAD_CHUNKS;
+    }
 
-    return app_dir;
+    return try allocator.dupe([]const u8, &[_][]const u8{try read_sidecar_payload()});
 }
 
-fn get_install_dir(meta: *const MetaStruct) ![]u8 {
-    // Combine the hash of the payload and a base dir to get a safe install directory
-    const base_install_path = try get_base_install_dir();
+// Returns the compressed payload in one piece, stitching the embedded chunks together the first time
+pub fn get_payload() ![]const u8 {
+    if (stitched_payload) |payload| {
+        return payload;
+    }
 
-    // Parse the ERTS version and app version from the metadata JSON string
-    const dir_name = try std.fmt.allocPrint(allocator, "{s}_erts-{s}_{s}", .{ build_options.RELEASE_NAME, meta.erts_version, meta.app_version });
+    const chunks = try get_payload_chunks();
+    stitched_payload = if (chunks.len == 1) chunks[0] else try std.mem.concat(allocator, u8, chunks);
+    return stitched_payload.?;
+}
 
-    // Ensure that base directory is created
-    std.fs.cwd().makePath(base_install_path) catch {
-        install_dir_error();
-        return "";
-    };
+// Reads and verifies the sidecar file the first time
+fn read_sidecar_payload() ![]const u8 {
+    if (sidecar_payload) |payload| {
+        return payload;
+    }
 
-    // Construct the full app install path
-    const name = fs.path.join(allocator, &[_][]const u8{ base_install_path, dir_name }) catch {
-        install_dir_error();
-        return "";
+    const sidecar_path = try get_sidecar_path();
+    const file = fs.openFileAbsolute(sidecar_path, .{}) catch |err| {
+        logger.err("Could not open the payload file {s}: {!}", .{ sidecar_path, err });
+        logger.err("This binary keeps its payload in a separate file, make sure it was copied along with the binary.", .{});
+        return err;
     };
+    defer file.close();
+
+    const payload = try file.readToEndAlloc(allocator, foilz.MAX_READ_SIZE);
 
-    return name;
+    // The expected hash is part of the (signed) wrapper, so a swapped payload is caught here
+    if (!is_payload_intact(&[_][]const u8{payload})) {
+        logger.err("The payload file {s} does not belong to this binary", .{sidecar_path});
+        return error.PayloadMismatch;
+    }
+
+    sidecar_payload = payload;
+    return payload;
 }
 
-fn install_dir_error() void {
-    const upper_name = std.ascii.allocUpperString(allocator, build_options.RELEASE_NAME) catch {
-        return;
+// Compares the compressed payload against the hash recorded when the wrapper was built
+fn is_payload_intact(payload_chunks: []const []const u8) bool {
+    var hasher = std.crypto.hash.sha2.Sha256.init(.{});
+    for (payload_chunks) |chunk| {
+        hasher.update(chunk);
+    }
+    var digest: [std.crypto.hash.sha2.Sha256.digest_length]u8 = undefined;
+    hasher.final(&digest);
+    var hex: [digest.len * 2]u8 = undefined;
+    _ = std.fmt.bufPrint(&hex, "{s}", .{std.fmt.fmtSliceHexLower(&digest)}) catch unreachable;
+    return std.mem.eql(u8, &hex, build_options.PAYLOAD_SHA256);
+}
+
+// The sidecar lives next to the binary as `<binary>.payload`
+fn get_sidecar_path() ![]const u8 {
+    const exe_path = try fs.selfExePathAlloc(allocator);
+    return try std.fmt.allocPrint(allocator, "{s}.payload", .{exe_path});
+}
+
+// `skip_unchanged` leaves files that already match the payload alone, see `ExistingInstall.repair`
+pub fn do_payload_install(meta: *const MetaStruct, install_dir: []const u8, metadata_path: []const u8, skip_unchanged: bool) !void {
+    // Unpack the files
+    const payload_chunks = try get_payload_chunks();
+    log.debug("The payload is in {} chunks", .{payload_chunks.len});
+
+    // A truncated or bit-rotted binary would otherwise decompress into garbage, or fail deep inside xz.
+    // The sidecar payload was already checked when it was read.
+    const verify_span = trace.begin("verify payload");
+    progress.phase_started("verify");
+    if (!build_options.SIDECAR_PAYLOAD and !is_payload_intact(payload_chunks)) {
+        logger.err("The payload embedded in this binary does not match the checksum it was built with", .{});
+        return error.PayloadCorrupted;
+    }
+    progress.phase_finished("verify");
+    verify_span.end();
+
+    var extract_arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer extract_arena.deinit();
+
+    // Decompression and extraction overlap, the uncompressed payload is never in memory as a whole
+    const extract_span = trace.begin("decompress and extract");
+    progress.phase_started("extract");
+    try foilz.unpack_payload(extract_arena.allocator(), payload_chunks, install_dir, .{
+        .trusted_modes = meta.trusted_modes,
+        .threads = get_extract_threads(),
+        .io_uring = build_options.IO_URING,
+        .skip_unchanged = skip_unchanged,
+        .max_size = build_options.UNCOMPRESSED_SIZE,
+        .counter = .{ .phase = "extract", .total_files = build_options.PAYLOAD_FILE_COUNT, .total_bytes = build_options.PAYLOAD_FILE_BYTES },
+    });
+    progress.phase_finished("extract");
+    extract_span.end();
+
+    // Write metadata file
+    const file = fs.createFileAbsolute(metadata_path, .{ .truncate = true }) catch |err| {
+        logger.set_error_context("writing", metadata_path);
+        return err;
     };
-    const env_install_dir_name = std.fmt.allocPrint(allocator, "{s}_INSTALL_DIR", .{upper_name}) catch {
-        return;
+    file.writeAll(RELEASE_METADATA_JSON) catch |err| {
+        logger.set_error_context("writing", metadata_path);
+        return err;
     };
+}
 
-    logger.err("We could not install this application to the default directory.", .{});
-    logger.err("This may be due to a permission error.", .{});
-    logger.err("Please override the default {s} install directory using the `{s}` environment variable.", .{ build_options.RELEASE_NAME, env_install_dir_name });
-    logger.err("On Linux or MacOS you can run the command: `export {s}=/some/other/path`", .{env_install_dir_name});
-    logger.err("On Windows you can use: `SET {s}=D:\\some\\other\\path`", .{env_install_dir_name});
-    std.process.exit(1);
+// One thread per CPU writes the files unless `<APP>_EXTRACT_THREADS` says otherwise, `1` extracts sequentially
+fn get_extract_threads() usize {
+    const cpu_count = std.Thread.getCpuCount() catch 1;
+    const value = shutil.get_release_env(allocator, "EXTRACT_THREADS") orelse return @min(cpu_count, MAX_EXTRACT_THREADS);
+    const threads = std.fmt.parseInt(usize, value, 10) catch {
+        logger.warn("Ignoring `{s}` for the number of extraction threads, it is not a number", .{value});
+        return @min(cpu_count, MAX_EXTRACT_THREADS);
+    };
+    return @max(threads, 1);
 }
@@ -0,0 +1,64 @@
+/////
+// Windows Subsystem for Linux detection. Under WSL the Windows drives are mounted at `/mnt/<drive>` (DrvFs, or 9p on WSL 2),
+// where permissions and exec bits are emulated and every file operation is slow, so an install there takes ages
+// and may not even run. We keep installs on the Linux file system, and warn when one ends up on a Windows drive.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const fs = std.fs;
+
+const MAX_READ_SIZE = 1024 * 1024;
+
+pub fn is_wsl() bool {
+    if (builtin.os.tag != .linux) {
+        return false;
+    }
+
+    if (std.os.getenv("WSL_DISTRO_NAME") != null) {
+        return true;
+    }
+
+    // Both WSL 1 and 2 kernels have "microsoft" in their release string
+    var buffer: [256]u8 = undefined;
+    const file = fs.openFileAbsolute("/proc/sys/kernel/osrelease", .{}) catch return false;
+    defer file.close();
+    const len = file.readAll(&buffer) catch return false;
+    return std.ascii.indexOfIgnoreCase(buffer[0..len], "microsoft") != null;
+}
+
+// True when `path` lives on a Windows drive, going by the file system type of the mount it's on
+pub fn is_windows_drive(allocator: std.mem.Allocator, path: []const u8) bool {
+    const mounts = fs.cwd().readFileAlloc(allocator, "/proc/mounts", MAX_READ_SIZE) catch return false;
+
+    // The most specific mount point containing the path wins
+    var best_len: usize = 0;
+    var best_is_windows = false;
+
+    var lines = std.mem.split(u8, mounts, "\n");
+    while (lines.next()) |line| {
+        var fields = std.mem.tokenize(u8, line, " ");
+        _ = fields.next() orelse continue;
+        const mount_point = fields.next() orelse continue;
+        const fs_type = fields.next() orelse continue;
+
+        const contains = std.mem.startsWith(u8, path, mount_point) and
+            (path.len == mount_point.len or path[mount_point.len] == '/' or std.mem.eql(u8, mount_point, "/"));
+        if (contains and mount_point.len >= best_len) {
+            best_len = mount_point.len;
+            best_is_windows = std.mem.eql(u8, fs_type, "drvfs") or std.mem.eql(u8, fs_type, "9p");
+        }
+    }
+
+    return best_is_windows;
+}
+
+// The XDG fallback location in the Linux home directory, if the home directory itself is on the Linux file system
+pub fn get_linux_data_dir(allocator: std.mem.Allocator) ?[]const u8 {
+    const home = std.os.getenv("HOME") orelse return null;
+    if (is_windows_drive(allocator, home)) {
+        return null;
+    }
+    return fs.path.join(allocator, &[_][]const u8{ home, ".local", "share" }) catch null;
+}
@@ -0,0 +1,115 @@
+/////
+// Writes ZIP archives in memory, the std library can't write them. Entries are deflated, and carry a fixed
+// timestamp since nothing reading our archives cares about it.
+/////
+
+const std = @import("std");
+
+const deflate = std.compress.deflate;
+
+const LOCAL_HEADER_SIGNATURE = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE = 0x06054b50;
+
+const VERSION = 20;
+const FLAG_UTF8_NAMES = 0x0800;
+const METHOD_DEFLATE = 8;
+// 1980-01-01 00:00 in the MS-DOS format, the earliest date it can represent
+const DOS_TIME = 0;
+const DOS_DATE = (1 << 5) | 1;
+
+const Entry = struct {
+    name: []const u8,
+    crc32: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+};
+
+pub const Writer = struct {
+    allocator: std.mem.Allocator,
+    buffer: std.ArrayList(u8),
+    entries: std.ArrayList(Entry),
+
+    pub fn init(allocator: std.mem.Allocator) Writer {
+        return .{
+            .allocator = allocator,
+            .buffer = std.ArrayList(u8).init(allocator),
+            .entries = std.ArrayList(Entry).init(allocator),
+        };
+    }
+
+    pub fn add_file(self: *Writer, name: []const u8, data: []const u8) !void {
+        var compressed = std.ArrayList(u8).init(self.allocator);
+        defer compressed.deinit();
+
+        var compressor = try deflate.compressor(self.allocator, compressed.writer(), .{});
+        defer compressor.deinit();
+        _ = try compressor.write(data);
+        try compressor.close();
+
+        const entry = Entry{
+            .name = try self.allocator.dupe(u8, name),
+            .crc32 = std.hash.Crc32.hash(data),
+            .compressed_size = std.math.cast(u32, compressed.items.len) orelse return error.FileTooBig,
+            .size = std.math.cast(u32, data.len) orelse return error.FileTooBig,
+            .offset = std.math.cast(u32, self.buffer.items.len) orelse return error.FileTooBig,
+        };
+
+        const writer = self.buffer.writer();
+        try writer.writeIntLittle(u32, LOCAL_HEADER_SIGNATURE);
+        try writer.writeIntLittle(u16, VERSION);
+        try write_common_fields(writer, entry);
+        try writer.writeIntLittle(u16, 0); // extra field length
+        try writer.writeAll(entry.name);
+        try writer.writeAll(compressed.items);
+
+        try self.entries.append(entry);
+    }
+
+    // Writes the central directory, returns the complete archive
+    pub fn finish(self: *Writer) ![]const u8 {
+        const writer = self.buffer.writer();
+        const directory_offset = self.buffer.items.len;
+
+        for (self.entries.items) |entry| {
+            try writer.writeIntLittle(u32, CENTRAL_HEADER_SIGNATURE);
+            try writer.writeIntLittle(u16, VERSION); // version made by
+            try writer.writeIntLittle(u16, VERSION); // version needed to extract
+            try write_common_fields(writer, entry);
+            try writer.writeIntLittle(u16, 0); // extra field length
+            try writer.writeIntLittle(u16, 0); // comment length
+            try writer.writeIntLittle(u16, 0); // disk number
+            try writer.writeIntLittle(u16, 0); // internal attributes
+            try writer.writeIntLittle(u32, 0); // external attributes
+            try writer.writeIntLittle(u32, entry.offset);
+            try writer.writeAll(entry.name);
+        }
+
+        const directory_size = self.buffer.items.len - directory_offset;
+        const count: u16 = @intCast(self.entries.items.len);
+
+        try writer.writeIntLittle(u32, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        try writer.writeIntLittle(u16, 0); // this disk
+        try writer.writeIntLittle(u16, 0); // disk with the central directory
+        try writer.writeIntLittle(u16, count);
+        try writer.writeIntLittle(u16, count);
+        try writer.writeIntLittle(u32, @intCast(directory_size));
+        try writer.writeIntLittle(u32, @intCast(directory_offset));
+        try writer.writeIntLittle(u16, 0); // comment length
+
+        return self.buffer.items;
+    }
+};
+
+// The fields shared by the local and central headers, from the flags up to the name length
+fn write_common_fields(writer: anytype, entry: Entry) !void {
+    try writer.writeIntLittle(u16, FLAG_UTF8_NAMES);
+    try writer.writeIntLittle(u16, METHOD_DEFLATE);
+    try writer.writeIntLittle(u16, DOS_TIME);
+    try writer.writeIntLittle(u16, DOS_DATE);
+    try writer.writeIntLittle(u32, entry.crc32);
+    try writer.writeIntLittle(u32, entry.compressed_size);
+    try writer.writeIntLittle(u32, entry.size);
+    try writer.writeIntLittle(u16, @intCast(entry.name.len));
+}
@@ -0,0 +1,143 @@
+/////
+// Fuzzing target for the FOILZ record parsers, the streaming one used while installing and the in-memory one used by
+// `--burrito export` and `--burrito licenses`. Malformed input must fail with an error, never panic, read out of
+// bounds or allocate more than the input could hold.
+//
+// As a test, random mutations of a small valid archive are run through both parsers:
+//
+//   zig test --main-pkg-path . test/fuzz_archive.zig
+//
+// For longer runs, build it as an executable that reads one input from stdin, e.g. for AFL++ or honggfuzz:
+//
+//   zig build-exe -O ReleaseSafe --main-pkg-path . test/fuzz_archive.zig
+//   afl-fuzz -n -i corpus -o findings -- ./fuzz_archive
+/////
+
+const std = @import("std");
+
+const foilz = @import("../src/archiver.zig");
+
+const mem = std.mem;
+const testing = std.testing;
+
+const MAGIC = "FOILZ";
+
+// Far below what the parsers accept on their own, so a record claiming more than its input holds is caught
+const MAX_INPUT_SIZE = 1024 * 1024;
+
+// Backs the streaming parser's allocations, running out of it fails the run
+var parse_buffer: [2 * MAX_INPUT_SIZE]u8 = undefined;
+
+pub fn main() !void {
+    const allocator = std.heap.page_allocator;
+    const input = try std.io.getStdIn().readToEndAlloc(allocator, MAX_INPUT_SIZE);
+    fuzz_one(input);
+}
+
+// Runs `input` through both parsers, errors are expected, crashes are bugs
+pub fn fuzz_one(input: []const u8) void {
+    var fixed = std.heap.FixedBufferAllocator.init(&parse_buffer);
+
+    if (foilz.iterate_records(input)) |records_const| {
+        var records = records_const;
+        while (records.next() catch null) |record| {
+            std.debug.assert(record.name.len > 0);
+        }
+    } else |_| {}
+
+    var stream = std.io.fixedBufferStream(input);
+    const reader = stream.reader();
+    var header: [MAGIC.len]u8 = undefined;
+    reader.readNoEof(&header) catch return;
+    while (foilz.read_record(reader, fixed.allocator(), input.len) catch null) |record| {
+        fixed.allocator().free(record.data);
+        fixed.allocator().free(record.name);
+    }
+}
+
+fn append_record(archive: *std.ArrayList(u8), name: []const u8, data: []const u8, mode: u64) !void {
+    try archive.writer().writeIntLittle(u64, name.len);
+    try archive.appendSlice(name);
+    try archive.writer().writeIntLittle(u64, data.len);
+    try archive.appendSlice(data);
+    try archive.writer().writeIntLittle(u64, mode);
+}
+
+fn build_seed(allocator: mem.Allocator) ![]u8 {
+    var archive = std.ArrayList(u8).init(allocator);
+    try archive.appendSlice(MAGIC);
+    try append_record(&archive, "erts-14.0/bin/erlexec", "#!/bin/sh\n", 0o755);
+    try append_record(&archive, "releases/0.1.0/start.boot", "boot", 0o644);
+    try append_record(&archive, "releases/COOKIE", "COOKIE", 0o400);
+    try archive.appendSlice(MAGIC);
+    return archive.toOwnedSlice();
+}
+
+test "a valid archive parses the same both ways" {
+    const seed = try build_seed(testing.allocator);
+    defer testing.allocator.free(seed);
+
+    var records = try foilz.iterate_records(seed);
+    var stream = std.io.fixedBufferStream(seed[MAGIC.len..]);
+    var count: usize = 0;
+    while (try records.next()) |record| {
+        const streamed = (try foilz.read_record(stream.reader(), testing.allocator, seed.len)).?;
+        defer testing.allocator.free(streamed.name);
+        defer testing.allocator.free(streamed.data);
+
+        try testing.expectEqualStrings(record.name, streamed.name);
+        try testing.expectEqualStrings(record.data, streamed.data);
+        try testing.expectEqual(record.mode, streamed.mode);
+        count += 1;
+    }
+    try testing.expectEqual(@as(usize, 3), count);
+    try testing.expect(try foilz.read_record(stream.reader(), testing.allocator, seed.len) == null);
+}
+
+test "truncated and oversized records are rejected" {
+    const seed = try build_seed(testing.allocator);
+    defer testing.allocator.free(seed);
+
+    // Every prefix of the archive is missing its trailer or part of a record
+    for (0..seed.len) |len| {
+        var records = foilz.iterate_records(seed[0..len]) catch continue;
+        while (records.next()) |record| {
+            if (record == null) return error.TestUnexpectedResult;
+        } else |err| {
+            try testing.expectEqual(error.BadRecord, err);
+        }
+    }
+
+    // A data length far beyond the archive
+    const huge = try testing.allocator.dupe(u8, seed);
+    defer testing.allocator.free(huge);
+    const data_len_at = MAGIC.len + @sizeOf(u64) + "erts-14.0/bin/erlexec".len;
+    mem.writeIntLittle(u64, huge[data_len_at..][0..@sizeOf(u64)], std.math.maxInt(u64));
+
+    var records = try foilz.iterate_records(huge);
+    try testing.expectError(error.BadRecord, records.next());
+    var stream = std.io.fixedBufferStream(huge[MAGIC.len..]);
+    try testing.expectError(error.BadRecord, foilz.read_record(stream.reader(), testing.allocator, huge.len));
+}
+
+test "random mutations never crash the parsers" {
+    const seed = try build_seed(testing.allocator);
+    defer testing.allocator.free(seed);
+
+    var prng = std.rand.DefaultPrng.init(0x464f494c5a);
+    const random = prng.random();
+    const input = try testing.allocator.alloc(u8, seed.len);
+    defer testing.allocator.free(input);
+
+    for (0..20_000) |_| {
+        @memcpy(input, seed);
+
+        // A few bytes flipped, and sometimes cut short
+        for (0..random.intRangeAtMost(usize, 1, 8)) |_| {
+            input[random.uintLessThan(usize, input.len)] = random.int(u8);
+        }
+        const len = if (random.boolean()) input.len else random.uintLessThan(usize, input.len);
+
+        fuzz_one(input[0..len]);
+    }
+}
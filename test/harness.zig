@@ -0,0 +1,268 @@
+/////
+// Test support for running the wrapper end to end without a real OTP build: builds a small fake release with a
+// stub `erlexec` that prints what it was started with, wraps it with `zig build` just like `mix release` does, then
+// runs the binary against a temporary install directory and reads back what the "VM" saw.
+//
+// Only Unix-like systems are supported, the stub VM is a shell script.
+/////
+
+const std = @import("std");
+const builtin = @import("builtin");
+
+const foilz = @import("../src/archiver.zig");
+
+const fs = std.fs;
+const mem = std.mem;
+
+// First line the stub VM prints, anything before it is the wrapper's own output
+const STUB_MARKER = "burrito-stub-erl";
+
+// Stand-in for the VM, the exit code can be picked by the test through `BURRITO_STUB_EXIT`
+const stub_erlexec =
+    \\#!/bin/sh
+    \\echo "burrito-stub-erl"
+    \\for arg in "$@"; do echo "arg:$arg"; done
+    \\echo "release_root:$RELEASE_ROOT"
+    \\exit ${BURRITO_STUB_EXIT:-0}
+    \\
+;
+
+pub const ReleaseOptions = struct {
+    name: []const u8 = "burrito_test_app",
+    version: []const u8 = "0.1.0",
+    erts_version: []const u8 = "14.0",
+    // Extra metadata members, as they would appear in the JSON object, e.g. `"tamper_check": "refuse"`
+    metadata: []const u8 = "",
+    // Ship the payload as a separate file next to the binary
+    sidecar_payload: bool = false,
+};
+
+// A release directory laid out like the output of `mix release`, with just enough in it for the wrapper
+pub const FakeRelease = struct {
+    allocator: mem.Allocator,
+    options: ReleaseOptions,
+    tmp: std.testing.TmpDir,
+    path: []const u8,
+
+    pub fn create(allocator: mem.Allocator, options: ReleaseOptions) !FakeRelease {
+        var tmp = std.testing.tmpDir(.{});
+        errdefer tmp.cleanup();
+
+        var release = FakeRelease{
+            .allocator = allocator,
+            .options = options,
+            .tmp = tmp,
+            .path = try tmp.dir.realpathAlloc(allocator, "."),
+        };
+
+        const erts_bin = try std.fmt.allocPrint(allocator, "erts-{s}/bin", .{options.erts_version});
+        const rel_vsn = try std.fmt.allocPrint(allocator, "releases/{s}", .{options.version});
+        const app_lib = try std.fmt.allocPrint(allocator, "lib/{s}-{s}/ebin", .{ options.name, options.version });
+
+        try release.add_executable(try fs.path.join(allocator, &.{ erts_bin, "erlexec" }), stub_erlexec);
+        try release.add_file(try fs.path.join(allocator, &.{ rel_vsn, "start.boot" }), "fake boot script");
+        try release.add_file(try fs.path.join(allocator, &.{ rel_vsn, "vm.args" }), "-kernel inet_dist_use_interface {127,0,0,1}\n");
+        try release.add_file(try fs.path.join(allocator, &.{ rel_vsn, "sys.config" }), "[].\n");
+        try release.add_file("releases/COOKIE", "BURRITO_TEST_COOKIE");
+        try release.add_file(try fs.path.join(allocator, &.{ app_lib, "Elixir.BurritoTestApp.beam" }), "fake beam file");
+
+        return release;
+    }
+
+    pub fn add_file(self: *FakeRelease, sub_path: []const u8, contents: []const u8) !void {
+        if (fs.path.dirname(sub_path)) |dir_name| {
+            try self.tmp.dir.makePath(dir_name);
+        }
+        try self.tmp.dir.writeFile(sub_path, contents);
+    }
+
+    pub fn add_executable(self: *FakeRelease, sub_path: []const u8, contents: []const u8) !void {
+        try self.add_file(sub_path, contents);
+        const file = try self.tmp.dir.openFile(sub_path, .{});
+        defer file.close();
+        try file.chmod(0o755);
+    }
+
+    // Packs the release into a FOILZ archive at `archive_path`, like the build does before compressing it
+    pub fn pack(self: *const FakeRelease, archive_path: []const u8) !void {
+        try foilz.pack_directory(self.path, archive_path);
+    }
+
+    pub fn deinit(self: *FakeRelease) void {
+        self.tmp.cleanup();
+    }
+};
+
+// A wrapper binary built from a fake release, copied out of `zig-out` so later builds don't replace it
+pub const Wrapper = struct {
+    allocator: mem.Allocator,
+    options: ReleaseOptions,
+    tmp: std.testing.TmpDir,
+    exe_path: []const u8,
+    // Passed as `<APP>_INSTALL_DIR` to every run
+    install_root: []const u8,
+
+    // Builds the wrapper for `release` from this checkout, `zig build` must be run from the repository root
+    pub fn build(allocator: mem.Allocator, release: *const FakeRelease) !Wrapper {
+        const options = release.options;
+
+        const metadata = try std.fmt.allocPrint(allocator,
+            \\{{"app_name": "{s}", "zig_version": "{s}", "zig_build_arguments": [], "app_version": "{s}",
+            \\"options": "[]", "erts_version": "{s}", "build_timestamp": "1970-01-01T00:00:00Z"{s}{s}}}
+        , .{
+            options.name,
+            builtin.zig_version_string,
+            options.version,
+            options.erts_version,
+            if (options.metadata.len > 0) ", " else "",
+            options.metadata,
+        });
+        try fs.cwd().writeFile("src/_metadata.json", metadata);
+
+        var env_map = try std.process.getEnvMap(allocator);
+        try env_map.put("__BURRITO_IS_PROD", "false");
+        try env_map.put("__BURRITO_RELEASE_PATH", release.path);
+        try env_map.put("__BURRITO_RELEASE_NAME", options.name);
+        if (options.sidecar_payload) {
+            try env_map.put("__BURRITO_SIDECAR_PAYLOAD", "1");
+        }
+
+        const zig_exe = std.process.getEnvVarOwned(allocator, "ZIG") catch "zig";
+        const result = try std.ChildProcess.exec(.{
+            .allocator = allocator,
+            .argv = &.{ zig_exe, "build" },
+            .env_map = &env_map,
+            .max_output_bytes = 16 * 1024 * 1024,
+        });
+        if (result.term != .Exited or result.term.Exited != 0) {
+            std.debug.print("zig build failed:\n{s}{s}\n", .{ result.stdout, result.stderr });
+            return error.WrapperBuildFailed;
+        }
+
+        var tmp = std.testing.tmpDir(.{});
+        errdefer tmp.cleanup();
+        const tmp_path = try tmp.dir.realpathAlloc(allocator, ".");
+
+        const built_exe = try fs.path.join(allocator, &.{ "zig-out", "bin", options.name });
+        try fs.cwd().copyFile(built_exe, tmp.dir, options.name, .{});
+        if (options.sidecar_payload) {
+            const payload_name = try std.fmt.allocPrint(allocator, "{s}.payload", .{options.name});
+            try fs.cwd().copyFile(try fs.path.join(allocator, &.{ "zig-out", "bin", payload_name }), tmp.dir, payload_name, .{});
+        }
+
+        try tmp.dir.makeDir("install");
+
+        return Wrapper{
+            .allocator = allocator,
+            .options = options,
+            .tmp = tmp,
+            .exe_path = try fs.path.join(allocator, &.{ tmp_path, options.name }),
+            .install_root = try fs.path.join(allocator, &.{ tmp_path, "install" }),
+        };
+    }
+
+    // Runs the binary with `args`, `env` is added to the test's own environment
+    pub fn run(self: *const Wrapper, args: []const []const u8, env: []const [2][]const u8) !Run {
+        var env_map = try std.process.getEnvMap(self.allocator);
+        try env_map.put(try self.get_env_name("INSTALL_DIR"), self.install_root);
+        for (env) |pair| {
+            try env_map.put(pair[0], pair[1]);
+        }
+
+        const argv = try mem.concat(self.allocator, []const u8, &.{ &.{self.exe_path}, args });
+        const result = try std.ChildProcess.exec(.{
+            .allocator = self.allocator,
+            .argv = argv,
+            .env_map = &env_map,
+            .max_output_bytes = 16 * 1024 * 1024,
+        });
+
+        return Run.parse(self.allocator, result);
+    }
+
+    // The directory the payload was installed into, there is only ever one under the install root in these tests
+    pub fn get_install_dir(self: *const Wrapper) ![]const u8 {
+        const base = try fs.path.join(self.allocator, &.{ self.install_root, ".burrito" });
+        var dir = try fs.openIterableDirAbsolute(base, .{});
+        defer dir.close();
+
+        var it = dir.iterate();
+        while (try it.next()) |entry| {
+            if (entry.kind == .directory) {
+                return try fs.path.join(self.allocator, &.{ base, entry.name });
+            }
+        }
+        return error.NotInstalled;
+    }
+
+    pub fn get_sidecar_payload_path(self: *const Wrapper) ![]const u8 {
+        return try std.fmt.allocPrint(self.allocator, "{s}.payload", .{self.exe_path});
+    }
+
+    fn get_env_name(self: *const Wrapper, suffix: []const u8) ![]const u8 {
+        const upper_name = try std.ascii.allocUpperString(self.allocator, self.options.name);
+        return try std.fmt.allocPrint(self.allocator, "{s}_{s}", .{ upper_name, suffix });
+    }
+
+    pub fn deinit(self: *Wrapper) void {
+        self.tmp.cleanup();
+    }
+};
+
+// The outcome of running a wrapper binary
+pub const Run = struct {
+    exit_code: u8,
+    stdout: []const u8,
+    stderr: []const u8,
+    // Whether the stub VM was reached
+    launched: bool,
+    // The arguments the stub VM was started with
+    vm_args: []const []const u8,
+    release_root: ?[]const u8,
+
+    fn parse(allocator: mem.Allocator, result: std.ChildProcess.ExecResult) !Run {
+        var run = Run{
+            .exit_code = switch (result.term) {
+                .Exited => |code| code,
+                else => return error.WrapperCrashed,
+            },
+            .stdout = result.stdout,
+            .stderr = result.stderr,
+            .launched = false,
+            .vm_args = &.{},
+            .release_root = null,
+        };
+
+        const start = mem.indexOf(u8, result.stdout, STUB_MARKER) orelse return run;
+        run.launched = true;
+
+        var vm_args = std.ArrayList([]const u8).init(allocator);
+        var lines = mem.splitScalar(u8, result.stdout[start..], '\n');
+        while (lines.next()) |line| {
+            if (mem.startsWith(u8, line, "arg:")) {
+                try vm_args.append(line["arg:".len..]);
+            } else if (mem.startsWith(u8, line, "release_root:")) {
+                run.release_root = line["release_root:".len..];
+            }
+        }
+        run.vm_args = try vm_args.toOwnedSlice();
+
+        return run;
+    }
+
+    // The arguments that reach the application, everything after `-extra`
+    pub fn get_app_args(self: *const Run) []const []const u8 {
+        for (self.vm_args, 0..) |arg, i| {
+            if (mem.eql(u8, arg, "-extra")) {
+                return self.vm_args[i + 1 ..];
+            }
+        }
+        return &.{};
+    }
+};
+
+pub fn skip_unsupported() !void {
+    if (builtin.os.tag == .windows) {
+        return error.SkipZigTest;
+    }
+}
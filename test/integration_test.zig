@@ -0,0 +1,165 @@
+/////
+// End to end tests of the wrapper against fake releases, see harness.zig. Run from the repository root with:
+//
+//   zig test --main-pkg-path . test/integration_test.zig
+//
+// Each test builds its own wrapper, which takes a while.
+/////
+
+const std = @import("std");
+
+const exit_codes = @import("../src/exit_codes.zig");
+const foilz = @import("../src/archiver.zig");
+const harness = @import("harness.zig");
+
+const fs = std.fs;
+const mem = std.mem;
+const testing = std.testing;
+
+const FakeRelease = harness.FakeRelease;
+const Wrapper = harness.Wrapper;
+
+test "packing a release keeps every file and its contents" {
+    try harness.skip_unsupported();
+
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer arena.deinit();
+    const allocator = arena.allocator();
+
+    var release = try FakeRelease.create(allocator, .{});
+    defer release.deinit();
+    try release.add_file("lib/extra/priv/data.txt", "some data");
+
+    const archive_path = try fs.path.join(allocator, &.{ release.path, "..", "test_payload.foilz" });
+    try release.pack(archive_path);
+    defer fs.deleteFileAbsolute(archive_path) catch {};
+
+    const archive = try fs.cwd().readFileAlloc(allocator, archive_path, foilz.MAX_READ_SIZE);
+    var records = try foilz.iterate_records(archive);
+
+    var count: usize = 0;
+    while (try records.next()) |record| {
+        const on_disk = try release.tmp.dir.readFileAlloc(allocator, record.name, foilz.MAX_READ_SIZE);
+        try testing.expectEqualStrings(on_disk, record.data);
+        count += 1;
+    }
+    try testing.expectEqual(@as(usize, 7), count);
+}
+
+test "installs the payload and launches the VM with the app's arguments" {
+    try harness.skip_unsupported();
+
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer arena.deinit();
+    const allocator = arena.allocator();
+
+    var release = try FakeRelease.create(allocator, .{});
+    defer release.deinit();
+    var wrapper = try Wrapper.build(allocator, &release);
+    defer wrapper.deinit();
+
+    const run = try wrapper.run(&.{ "hello", "--flag" }, &.{});
+    try testing.expect(run.launched);
+    try testing.expectEqual(@as(u8, 0), run.exit_code);
+
+    const app_args = run.get_app_args();
+    try testing.expectEqual(@as(usize, 2), app_args.len);
+    try testing.expectEqualStrings("hello", app_args[0]);
+    try testing.expectEqualStrings("--flag", app_args[1]);
+
+    const install_dir = try wrapper.get_install_dir();
+    try testing.expectEqualStrings(install_dir, run.release_root.?);
+    const cookie = try fs.cwd().readFileAlloc(allocator, try fs.path.join(allocator, &.{ install_dir, "releases", "COOKIE" }), 1024);
+    try testing.expectEqualStrings("BURRITO_TEST_COOKIE", cookie);
+}
+
+test "passes the VM's exit code through" {
+    try harness.skip_unsupported();
+
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer arena.deinit();
+    const allocator = arena.allocator();
+
+    var release = try FakeRelease.create(allocator, .{});
+    defer release.deinit();
+    var wrapper = try Wrapper.build(allocator, &release);
+    defer wrapper.deinit();
+
+    const run = try wrapper.run(&.{}, &.{.{ "BURRITO_STUB_EXIT", "3" }});
+    try testing.expect(run.launched);
+    try testing.expectEqual(@as(u8, 3), run.exit_code);
+}
+
+test "keeps an existing install and repairs it on request" {
+    try harness.skip_unsupported();
+
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer arena.deinit();
+    const allocator = arena.allocator();
+
+    var release = try FakeRelease.create(allocator, .{});
+    defer release.deinit();
+    var wrapper = try Wrapper.build(allocator, &release);
+    defer wrapper.deinit();
+
+    _ = try wrapper.run(&.{}, &.{});
+    const install_dir = try wrapper.get_install_dir();
+
+    // A second run must not reinstall over files the app created
+    const marker_path = try fs.path.join(allocator, &.{ install_dir, "app_state" });
+    try fs.cwd().writeFile(marker_path, "kept");
+    const second = try wrapper.run(&.{}, &.{});
+    try testing.expect(second.launched);
+    try fs.accessAbsolute(marker_path, .{});
+
+    const vm_args_path = try fs.path.join(allocator, &.{ install_dir, "releases", release.options.version, "vm.args" });
+    try fs.deleteFileAbsolute(vm_args_path);
+    const repair = try wrapper.run(&.{ "--burrito", "repair" }, &.{});
+    try testing.expectEqual(@as(u8, 0), repair.exit_code);
+    try fs.accessAbsolute(vm_args_path, .{});
+}
+
+test "refuses to launch a tampered install" {
+    try harness.skip_unsupported();
+
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer arena.deinit();
+    const allocator = arena.allocator();
+
+    var release = try FakeRelease.create(allocator, .{ .metadata = "\"tamper_check\": \"refuse\"" });
+    defer release.deinit();
+    var wrapper = try Wrapper.build(allocator, &release);
+    defer wrapper.deinit();
+
+    _ = try wrapper.run(&.{}, &.{});
+    const install_dir = try wrapper.get_install_dir();
+    const config_path = try fs.path.join(allocator, &.{ install_dir, "releases", release.options.version, "sys.config" });
+    try fs.cwd().writeFile(config_path, "[{kernel, [{tampered, true}]}].\n");
+
+    const run = try wrapper.run(&.{}, &.{});
+    try testing.expect(!run.launched);
+    try testing.expectEqual(exit_codes.ExitCode.tampered.code(), run.exit_code);
+}
+
+test "rejects a corrupted sidecar payload" {
+    try harness.skip_unsupported();
+
+    var arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);
+    defer arena.deinit();
+    const allocator = arena.allocator();
+
+    var release = try FakeRelease.create(allocator, .{ .sidecar_payload = true });
+    defer release.deinit();
+    var wrapper = try Wrapper.build(allocator, &release);
+    defer wrapper.deinit();
+
+    const payload = try fs.openFileAbsolute(try wrapper.get_sidecar_payload_path(), .{ .mode = .read_write });
+    try payload.seekTo(32);
+    try payload.writeAll("not the payload");
+    payload.close();
+
+    const run = try wrapper.run(&.{}, &.{});
+    try testing.expect(!run.launched);
+    try testing.expectEqual(exit_codes.ExitCode.payload_corrupted.code(), run.exit_code);
+    try testing.expect(mem.indexOf(u8, run.stderr, "PayloadMismatch") != null);
+}